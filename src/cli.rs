@@ -1,5 +1,6 @@
 use clap::Parser;
-use std::path::PathBuf;
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
 
 use crate::ops;
 use crate::version;
@@ -22,7 +23,7 @@ struct InitArgs {
     disable_help_flag = false,
     disable_version_flag = true,
     about = "Orchestrate the Loopr workflow steps (PRD -> Spec -> Features -> Tasks -> Tests -> Execute). Requires --codex or --dry-run. Use --from/--to to run a contiguous range, or --step for a single step. When --codex is set, the prompt and handoff rules are enforced; when --dry-run is set, no Codex session is started.",
-    after_help = "Examples:\n  loopr run --codex --seed-prompt @seed.txt\n  loopr run --codex --from spec --to tests\n  loopr run --dry-run\n  loopr run --codex -- --model <model name>\n",
+    after_help = "Examples:\n  loopr run --codex --seed-prompt @seed.txt\n  cat seed.txt | loopr run --codex --seed-prompt @-\n  loopr run --codex --from spec --to tests\n  loopr run --codex --feature sync-api\n  loopr run --codex --resume\n  loopr run --codex --review\n  loopr run --dry-run\n  loopr run --dry-run --show-prompts --seed-prompt @seed.txt\n  loopr run --dry-run --format json\n  loopr run --codex -- --model <model name>\n",
     help_template = "{about}\n\nUsage: {usage}\n\nOptions:\n{options}\n\n{after-help}"
 )]
 struct RunArgs {
@@ -46,16 +47,42 @@ struct RunArgs {
     step: Option<String>,
     #[arg(
         long = "seed-prompt",
-        help = "Seed prompt text or @path to read from a file.",
-        long_help = "Seed prompt text or @path to read from a file. Required when running the prd step if specs/prd.md is missing."
+        help = "Seed prompt text, @path to read from a file, or @- to read stdin.",
+        long_help = "Seed prompt text, @path to read from a file, or @- to read stdin. Required when the prd step runs; if omitted in a terminal, Loopr opens $EDITOR on a seed template instead. The seed used is archived to specs/seed.md, with a copy per run under loopr/state/seeds/."
     )]
     seed_prompt: Option<String>,
+    #[arg(
+        long,
+        help = "Scope the tasks, tests and execute steps to one feature.",
+        long_help = "Scope the tasks, tests and execute steps to one feature key from specs/feature-order.yaml. Step inputs, outputs and prompts cover only that feature's files, and the feature's entries are merged into specs/task-order.yaml and specs/test-order.yaml without touching other features' entries. Without --from/--to/--step the run starts at the tasks step."
+    )]
+    feature: Option<String>,
     #[arg(
         long,
         help = "Ask for confirmation before each step.",
         long_help = "Ask for confirmation before each step when running with --codex."
     )]
     confirm: bool,
+    #[arg(
+        long,
+        conflicts_with = "no_prompt",
+        help = "Review each step's changes before moving on.",
+        long_help = "Review each step's changes before moving on. After a step finishes, Loopr prints a diffstat of the working tree and the output files it created or changed, then asks to approve, reject, edit the files in $EDITOR, or quit. Decisions and comments are appended to loopr/state/handoff.md; a rejected step is re-run with the comment added to its prompt."
+    )]
+    review: bool,
+    #[arg(
+        long,
+        help = "Run every selected step, even if its outputs are up to date.",
+        long_help = "Run every selected step, even if its outputs are up to date. Without --force, a step is skipped when its inputs are unchanged since its last successful run (loopr/state/run-state.json) and each of its outputs still exists, unless always_run is set. Edited outputs do not trigger a re-run; loopr stale lists them."
+    )]
+    force: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["from", "to", "step", "feature", "seed_prompt", "force", "no_prompt"],
+        help = "Continue the last run from its first incomplete step.",
+        long_help = "Continue the last run recorded in loopr/state/run-status.json from its first incomplete step, reusing its seed prompt, agent args and --force, --review and --confirm settings. Completed and skipped steps are not re-run."
+    )]
+    resume: bool,
     #[arg(
         long = "no-prompt",
         help = "Open Codex without a Loopr prompt (interactive mode).",
@@ -74,6 +101,22 @@ struct RunArgs {
         long_help = "Print planned steps without running Codex or reading prompts. Useful to preview which steps will run."
     )]
     dry_run: bool,
+    #[arg(
+        long = "show-prompts",
+        requires = "dry_run",
+        help = "With --dry-run, print the rendered prompt for each step.",
+        long_help = "With --dry-run, print the fully rendered prompt for each step, using --seed-prompt and any templates in loopr/prompts/."
+    )]
+    show_prompts: bool,
+    #[arg(
+        long,
+        requires = "dry_run",
+        value_parser = ["text", "json"],
+        default_value = "text",
+        help = "Dry-run output format: text or json.",
+        long_help = "Dry-run output format: text (default) or json. The JSON plan lists every step with its run/skip decision, expanded input and output files, and their status (present, pending or missing)."
+    )]
+    format: String,
     #[arg(
         long = "loopr-root",
         help = "Override Loopr root (defaults to nearest loopr/repo-id).",
@@ -87,7 +130,7 @@ struct RunArgs {
     disable_help_flag = false,
     disable_version_flag = true,
     about = "Run repeated Loopr execute iterations with safety gates (exit signals, missing-status limits, and optional per-task mode). Default mode runs a single execute prompt per iteration. Use --per-task to run one Codex session per test/task item with tests-first enforcement, using specs/task-order.yaml and specs/test-order.yaml.",
-    after_help = "Examples:\n  loopr loop\n  loopr loop --max-iterations 5\n  loopr loop --per-task\n  loopr loop --loopr-root /repo/app -- --model <model name>\n",
+    after_help = "Examples:\n  loopr loop\n  loopr loop --max-iterations 5\n  loopr loop --per-task\n  loopr loop --per-task --keep-going\n  loopr loop --per-task --jobs 4\n  loopr loop --loopr-root /repo/app -- --model <model name>\n",
     help_template = "{about}\n\nUsage: {usage}\n\nOptions:\n{options}\n\n{after-help}"
 )]
 struct LoopArgs {
@@ -110,6 +153,22 @@ struct LoopArgs {
         long_help = "Run one Codex session per test/task item. Tests are written and executed first; implementation runs only after tests are written. PBT tests must fail on the first run. Progress tracked in loopr/state/work-status.json and tests run via TEST_COMMAND (default: `just test`)."
     )]
     per_task: bool,
+    #[arg(
+        long = "keep-going",
+        requires = "per_task",
+        help = "Skip blocked/errored items and their dependents instead of stopping.",
+        long_help = "Skip blocked/errored items and everything that depends on them instead of stopping the loop. The remaining items keep running; the final report lists what was skipped and why. Requires --per-task."
+    )]
+    keep_going: bool,
+    #[arg(
+        long = "jobs",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..),
+        requires = "per_task",
+        help = "Run up to N independent items at once.",
+        long_help = "Run up to N independent items at once (default 1). Each item runs in its own git worktree under loopr/state/workspaces/ with its own Codex session and test run; results are merged back into the repo one at a time, in task/test order. An item whose changes do not apply cleanly is retried. Requires --per-task and a git repository."
+    )]
+    jobs: u32,
 }
 
 #[derive(Parser, Debug)]
@@ -129,6 +188,171 @@ struct IndexArgs {
     loopr_root: Option<String>,
 }
 
+#[derive(Parser, Debug)]
+#[command(
+    disable_help_flag = false,
+    disable_version_flag = true,
+    about = "List spec artifacts that are out of date. Walks the run pipeline (prd -> spec -> features -> tasks -> tests) and compares the input hashes each step's outputs were generated from (loopr/state/run-state.json) with the files on disk. A step is stale when one of its inputs changed or an upstream step is stale; the command prints the loopr run invocation that refreshes them.",
+    after_help = "Examples:\n  loopr stale\n  loopr stale --check\n  $(loopr stale --command)\n",
+    help_template = "{about}\n\nUsage: {usage}\n\nOptions:\n{options}\n\n{after-help}"
+)]
+struct StaleArgs {
+    #[arg(
+        long,
+        help = "Print only the loopr run command that refreshes stale artifacts.",
+        long_help = "Print only the loopr run command that refreshes stale artifacts (nothing when everything is up to date)."
+    )]
+    command: bool,
+    #[arg(
+        long,
+        help = "Exit non-zero when any artifact is stale.",
+        long_help = "Exit non-zero when any artifact is stale. Useful in CI."
+    )]
+    check: bool,
+    #[arg(
+        long = "loopr-root",
+        help = "Override Loopr root (defaults to nearest loopr/repo-id).",
+        long_help = "Override Loopr root (defaults to nearest loopr/repo-id). Use this when running from a different working directory."
+    )]
+    loopr_root: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    disable_help_flag = false,
+    disable_version_flag = true,
+    about = "Validate specs/feature-order.yaml, specs/task-order.yaml and specs/test-order.yaml: duplicate ids or keys, unknown depends_on ids, test task_id references, dependency cycles, and missing spec files. Exits non-zero when any problem is found.",
+    after_help = "Examples:\n  loopr validate\n  loopr validate --loopr-root /repo/app\n",
+    help_template = "{about}\n\nUsage: {usage}\n\nOptions:\n{options}\n\n{after-help}"
+)]
+struct ValidateArgs {
+    #[arg(
+        long = "loopr-root",
+        help = "Override Loopr root (defaults to nearest loopr/repo-id).",
+        long_help = "Override Loopr root (defaults to nearest loopr/repo-id). Use this when running from a different working directory."
+    )]
+    loopr_root: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    disable_help_flag = false,
+    disable_version_flag = true,
+    about = "Show what a loop iteration changed, using the snapshots stored under refs/loopr/<run-id>/<iteration>. Defaults to the most recent loop run.",
+    after_help = "Examples:\n  loopr diff --iteration 3\n  loopr diff --item foundation-test\n  loopr diff --run 20260131-120000-a1b2c3 --iteration 2\n",
+    help_template = "{about}\n\nUsage: {usage}\n\nOptions:\n{options}\n\n{after-help}"
+)]
+struct DiffArgs {
+    #[arg(
+        long = "loopr-root",
+        help = "Override Loopr root (defaults to nearest loopr/repo-id).",
+        long_help = "Override Loopr root (defaults to nearest loopr/repo-id). Use this when running from a different working directory."
+    )]
+    loopr_root: Option<String>,
+    #[arg(
+        long,
+        help = "Loop run id (defaults to the most recent run).",
+        long_help = "Loop run id (defaults to the most recent run). Printed as `Run ID` at the end of `loopr loop`."
+    )]
+    run: Option<String>,
+    #[arg(
+        long,
+        help = "Show the changes made by this iteration.",
+        conflicts_with = "item"
+    )]
+    iteration: Option<i64>,
+    #[arg(
+        long,
+        help = "Show the changes made by every iteration that worked on this item.",
+        long_help = "Show the changes made by every iteration that worked on this item (per-task mode only)."
+    )]
+    item: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    disable_help_flag = false,
+    disable_version_flag = true,
+    about = "Roll the working tree back to the snapshot taken after a loop iteration. HEAD, the branch, and the index are left untouched; the current tree is saved under refs/loopr/<run-id>/pre-restore-<ts> first.",
+    after_help = "Examples:\n  loopr restore --iteration 2\n  loopr restore --run 20260131-120000-a1b2c3 --iteration 0\n",
+    help_template = "{about}\n\nUsage: {usage}\n\nOptions:\n{options}\n\n{after-help}"
+)]
+struct RestoreArgs {
+    #[arg(
+        long = "loopr-root",
+        help = "Override Loopr root (defaults to nearest loopr/repo-id).",
+        long_help = "Override Loopr root (defaults to nearest loopr/repo-id). Use this when running from a different working directory."
+    )]
+    loopr_root: Option<String>,
+    #[arg(long, help = "Loop run id (defaults to the most recent run).")]
+    run: Option<String>,
+    #[arg(
+        long,
+        help = "Restore the tree as it was after this iteration.",
+        long_help = "Restore the tree as it was after this iteration (0 = before the first iteration)."
+    )]
+    iteration: i64,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    disable_help_flag = false,
+    disable_version_flag = true,
+    about = "Manage the prompt templates Loopr sends to Codex. Templates in loopr/prompts/*.tmpl override the built-in defaults (step, loop, per-task, and the context, rules and status-block partials). `eject` copies the built-in templates there so they can be edited.",
+    after_help = "Examples:\n  loopr prompts eject\n  loopr prompts eject --force\n",
+    help_template = "{about}\n\nUsage: {usage}\n\nArguments:\n{positionals}\n\nOptions:\n{options}\n\n{after-help}"
+)]
+struct PromptsArgs {
+    #[arg(value_parser = ["eject"], help = "Action to run (eject).")]
+    action: String,
+    #[arg(
+        long = "loopr-root",
+        help = "Override Loopr root (defaults to nearest loopr/repo-id).",
+        long_help = "Override Loopr root (defaults to nearest loopr/repo-id). Use this when running from a different working directory."
+    )]
+    loopr_root: Option<String>,
+    #[arg(
+        long,
+        help = "Overwrite templates that already exist in loopr/prompts/."
+    )]
+    force: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    disable_help_flag = false,
+    disable_version_flag = true,
+    about = "Manage the loopr-* skills that the workflow steps run (loopr-prd, loopr-specify, loopr-features, loopr-tasks, loopr-tests, loopr-execute). Canonical copies are bundled with Loopr and installed into the Codex skills directory: $CODEX_HOME/skills (default ~/.codex/skills), or <loopr root>/.codex/skills with --repo. Each installed skill gets a .loopr-skill.json version stamp so drift is detectable.",
+    after_help = "Examples:\n  loopr skills install\n  loopr skills list --repo\n  loopr skills diff loopr-execute\n  loopr skills update --force\n",
+    help_template = "{about}\n\nUsage: {usage}\n\nArguments:\n{positionals}\n\nOptions:\n{options}\n\n{after-help}"
+)]
+struct SkillsArgs {
+    #[arg(
+        value_parser = ["install", "list", "diff", "update"],
+        help = "Action to run.",
+        long_help = "Action to run: install (write missing skills), list (show install state), diff (compare installed skills with the bundled copies), update (refresh outdated skills)."
+    )]
+    action: String,
+    #[arg(help = "Skill names (defaults to every bundled skill).")]
+    skills: Vec<String>,
+    #[arg(
+        long,
+        help = "Use the repo-local skills directory (<loopr root>/.codex/skills) instead of the global one."
+    )]
+    repo: bool,
+    #[arg(
+        long = "loopr-root",
+        help = "Override Loopr root (defaults to nearest loopr/repo-id).",
+        long_help = "Override Loopr root (defaults to nearest loopr/repo-id). Only used with --repo."
+    )]
+    loopr_root: Option<String>,
+    #[arg(
+        long,
+        help = "Overwrite skills that were edited locally or not installed by Loopr."
+    )]
+    force: bool,
+}
+
 pub fn usage() {
     println!("loopr <command> [options]\n");
     println!("Commands:");
@@ -136,6 +360,12 @@ pub fn usage() {
     println!("  run        Orchestrate Loopr steps (requires --codex or --dry-run)");
     println!("  loop       Run the Loopr execute loop with safety gates");
     println!("  index      Refresh the Loopr docs index (loopr/state/docs-index.txt)");
+    println!("  validate   Check feature, task and test order files for problems");
+    println!("  stale      List spec artifacts whose inputs changed since they were generated");
+    println!("  diff       Show what a loop iteration or item changed");
+    println!("  restore    Roll the working tree back to a loop iteration snapshot");
+    println!("  prompts    Eject the built-in prompt templates to loopr/prompts/");
+    println!("  skills     Install, list, diff or update the bundled loopr-* skills");
     println!("  version     Show version info");
 }
 
@@ -185,22 +415,44 @@ pub fn run_run(args: Vec<String>) -> i32 {
     let mut codex = parsed.codex;
     let mut seed_prompt = parsed.seed_prompt.unwrap_or_default();
     let mut confirm = parsed.confirm;
+    let mut review = parsed.review;
     let mut no_prompt = parsed.no_prompt;
 
     if parsed.dry_run {
         codex = false;
         agent_args.clear();
-        seed_prompt = String::new();
+        if parsed.show_prompts {
+            match resolve_seed_prompt(seed_prompt) {
+                Ok(value) => seed_prompt = value,
+                Err(err) => return fail(&err.to_string()),
+            }
+        } else {
+            seed_prompt = String::new();
+        }
         confirm = false;
+        review = false;
         no_prompt = false;
     }
 
+    if parsed.resume && !agent_args.is_empty() {
+        return fail("--resume reuses the original agent args; do not pass new ones");
+    }
     if !agent_args.is_empty() && !codex && !parsed.dry_run {
         return fail("agent args provided but --codex not set");
     }
     if !codex && !parsed.dry_run {
         return fail("run requires --codex or --dry-run");
     }
+    if seed_prompt.trim() == "@-" && (confirm || review) {
+        return fail(
+            "--seed-prompt @- reads stdin and cannot be combined with --confirm or --review",
+        );
+    }
+    let edit_seed = codex
+        && !no_prompt
+        && seed_prompt.trim().is_empty()
+        && std::io::stdin().is_terminal()
+        && std::io::stdout().is_terminal();
     if codex && !no_prompt {
         match resolve_seed_prompt(seed_prompt) {
             Ok(value) => seed_prompt = value,
@@ -219,8 +471,14 @@ pub fn run_run(args: Vec<String>) -> i32 {
         from: parsed.from.unwrap_or_default(),
         to: parsed.to.unwrap_or_default(),
         step: parsed.step.unwrap_or_default(),
+        feature: parsed.feature.unwrap_or_default(),
         seed: seed_prompt,
+        edit_seed,
         confirm,
+        review,
+        show_prompts: parsed.show_prompts,
+        force: parsed.force,
+        resume: parsed.resume,
         no_prompt,
         codex,
         codex_args: agent_args,
@@ -236,22 +494,52 @@ pub fn run_run(args: Vec<String>) -> i32 {
         },
     };
 
+    if codex
+        && !no_prompt
+        && let Ok(root) = resolve_root(parsed.loopr_root.as_deref())
+        && let Ok(steps) = ops::run::load_run_steps(&root)
+        && let Ok(planned) = ops::run::plan_steps(&steps, &opts)
+    {
+        let skills: Vec<String> = planned.iter().map(|step| step.skill.clone()).collect();
+        warn_missing_skills(&root, &skills);
+    }
+
     let report = match ops::run::run_workflow(opts) {
         Ok(report) => report,
-        Err(err) => return fail(&err.to_string()),
+        Err(err) => {
+            let code = fail(&err.to_string());
+            if codex
+                && !no_prompt
+                && let Ok(root) = resolve_root(parsed.loopr_root.as_deref())
+                && let Ok(Some(status)) =
+                    ops::run_status::load_run_status(&ops::run_status::run_status_path(&root))
+                && status.state == ops::run_status::RUN_STATE_FAILED
+            {
+                eprintln!(
+                    "Run {} stopped at step {}; continue with: loopr run --codex --resume",
+                    status.run_id,
+                    status.current_step.unwrap_or_default()
+                );
+            }
+            return code;
+        }
     };
 
     if !codex {
-        for step in report.steps {
-            println!("Step: {}", step.name);
-            println!("  prompt: {}", step.skill);
-            for input in step.inputs {
-                println!("  input: {}", input);
-            }
-            for output in step.outputs {
-                println!("  output: {}", output);
-            }
+        if parsed.format == "json" {
+            let plan = DryRunPlan {
+                run_id: report.run_id.as_deref(),
+                steps: &report.plan,
+            };
+            return match serde_json::to_string_pretty(&plan) {
+                Ok(text) => {
+                    println!("{}", text);
+                    0
+                }
+                Err(err) => fail(&format!("serialize plan: {}", err)),
+            };
         }
+        print_dry_run_plan(&report.plan);
         return 0;
     }
 
@@ -259,9 +547,63 @@ pub fn run_run(args: Vec<String>) -> i32 {
         println!("Transcript: {}", session.log_path.display());
         println!("Metadata:   {}", session.meta_path.display());
     }
+    if let Some(run_id) = report.run_id {
+        println!("Run ID:     {}", run_id);
+    }
     0
 }
 
+#[derive(serde::Serialize)]
+struct DryRunPlan<'a> {
+    run_id: Option<&'a str>,
+    steps: &'a [ops::run::StepPlan],
+}
+
+fn print_dry_run_plan(plan: &[ops::run::StepPlan]) {
+    let mut missing = 0;
+    for step in plan {
+        println!("Step: {}", step.name);
+        println!("  prompt: {}", step.skill);
+        println!("  plan: {} ({})", step.action, step.reason);
+        for input in &step.inputs {
+            println!("  input: {}", describe_planned_path(input));
+            if input.status == ops::run::PATH_MISSING && step.action == "run" {
+                missing += 1;
+            }
+        }
+        for output in &step.outputs {
+            println!("  output: {}", describe_planned_path(output));
+        }
+        if let Some(prompt) = &step.prompt {
+            println!("  rendered prompt:");
+            for line in prompt.lines() {
+                println!("    {}", line);
+            }
+        }
+    }
+    let runs = plan.iter().filter(|step| step.action == "run").count();
+    println!(
+        "Plan: {} to run, {} to skip, {} missing inputs",
+        runs,
+        plan.len() - runs,
+        missing
+    );
+}
+
+fn describe_planned_path(path: &ops::run::PlannedPath) -> String {
+    let mut text = path.pattern.clone();
+    let expanded = path.files.len() != 1 || path.files[0] != path.pattern;
+    if !path.files.is_empty() && expanded {
+        text.push_str(&format!(" -> {}", path.files.join(", ")));
+    }
+    match (path.status.as_str(), &path.produced_by) {
+        (ops::run::PATH_PRESENT, _) => {}
+        (status, Some(step)) => text.push_str(&format!(" ({}; produced by step {})", status, step)),
+        (status, None) => text.push_str(&format!(" ({})", status)),
+    }
+    text
+}
+
 pub fn run_loop(args: Vec<String>) -> i32 {
     let (loopr_args, agent_args) = split_on_double_dash(&args);
 
@@ -282,6 +624,8 @@ pub fn run_loop(args: Vec<String>) -> i32 {
         loopr_root,
         max_iterations: parsed.max_iterations,
         per_task: parsed.per_task,
+        keep_going: parsed.keep_going,
+        jobs: parsed.jobs as usize,
         codex_args: agent_args,
         progress: Some(Box::new(|event| {
             if !event.details.is_empty() {
@@ -295,6 +639,13 @@ pub fn run_loop(args: Vec<String>) -> i32 {
         })),
     };
 
+    if let Ok(root) = resolve_root(parsed.loopr_root.as_deref())
+        && let Ok(steps) = ops::run::load_run_steps(&root)
+        && let Some(step) = ops::run::find_step(&steps, "execute")
+    {
+        warn_missing_skills(&root, &[step.skill]);
+    }
+
     let report = match ops::loop_run::run_loop(opts) {
         Ok(report) => report,
         Err(err) => return fail(&err.to_string()),
@@ -303,10 +654,17 @@ pub fn run_loop(args: Vec<String>) -> i32 {
     if !report.exit_reason.is_empty() {
         println!("Exit reason: {}", report.exit_reason);
     }
+    println!("Run ID:     {}", report.run_id);
     if let Some(session) = report.last_session {
         println!("Transcript: {}", session.log_path.display());
         println!("Metadata:   {}", session.meta_path.display());
     }
+    if !report.skipped.is_empty() {
+        println!("Skipped:");
+        for item in &report.skipped {
+            println!("  {} ({})", item.key, item.reason);
+        }
+    }
     0
 }
 
@@ -342,6 +700,295 @@ pub fn run_index(args: Vec<String>) -> i32 {
     0
 }
 
+pub fn run_validate(args: Vec<String>) -> i32 {
+    let mut argv = vec!["validate".to_string()];
+    argv.extend(args);
+    let parsed = match ValidateArgs::try_parse_from(argv) {
+        Ok(value) => value,
+        Err(err) => return handle_clap_error(err),
+    };
+
+    let root = match resolve_root(parsed.loopr_root.as_deref()) {
+        Ok(value) => value,
+        Err(err) => return fail(&err.to_string()),
+    };
+    let report = match ops::validate::validate_orders(&root, &[]) {
+        Ok(value) => value,
+        Err(err) => return fail(&err.to_string()),
+    };
+    if report.checked.is_empty() && report.issues.is_empty() {
+        return fail("no order files found under specs/ (run loopr run --codex --step features)");
+    }
+    for issue in &report.issues {
+        println!("{}", issue);
+    }
+    if !report.is_ok() {
+        return fail(&format!("{} validation issues", report.issues.len()));
+    }
+    for file in &report.checked {
+        println!("ok: {}", file);
+    }
+    0
+}
+
+pub fn run_stale(args: Vec<String>) -> i32 {
+    let mut argv = vec!["stale".to_string()];
+    argv.extend(args);
+    let parsed = match StaleArgs::try_parse_from(argv) {
+        Ok(value) => value,
+        Err(err) => return handle_clap_error(err),
+    };
+
+    let root = match resolve_root(parsed.loopr_root.as_deref()) {
+        Ok(value) => value,
+        Err(err) => return fail(&err.to_string()),
+    };
+    let report = match ops::stale::find_stale(&root) {
+        Ok(value) => value,
+        Err(err) => return fail(&err.to_string()),
+    };
+    let code = if parsed.check && report.is_stale() {
+        1
+    } else {
+        0
+    };
+    if parsed.command {
+        if let Some(command) = report.fix_command() {
+            println!("{}", command);
+        }
+        return code;
+    }
+
+    for step in &report.stale {
+        match &step.feature {
+            Some(feature) => println!("stale: {} (feature {})", step.step, feature),
+            None => println!("stale: {}", step.step),
+        }
+        for artifact in &step.artifacts {
+            println!("  artifact: {}", artifact);
+        }
+        for reason in &step.reasons {
+            println!("  {}", reason);
+        }
+    }
+    for edited in &report.edited {
+        for path in &edited.paths {
+            println!(
+                "edited: {} (changed after step {} generated it)",
+                path, edited.step
+            );
+        }
+    }
+    if !report.current.is_empty() {
+        println!("up to date: {}", report.current.join(", "));
+    }
+    if !report.not_run.is_empty() {
+        println!("not generated yet: {}", report.not_run.join(", "));
+    }
+    match report.fix_command() {
+        Some(command) => println!("Fix: {}", command),
+        None => println!("No stale artifacts."),
+    }
+    code
+}
+
+pub fn run_prompts(args: Vec<String>) -> i32 {
+    let mut argv = vec!["prompts".to_string()];
+    argv.extend(args);
+    let parsed = match PromptsArgs::try_parse_from(argv) {
+        Ok(value) => value,
+        Err(err) => return handle_clap_error(err),
+    };
+
+    let root = match resolve_root(parsed.loopr_root.as_deref()) {
+        Ok(value) => value,
+        Err(err) => return fail(&err.to_string()),
+    };
+    let report = match ops::prompt_template::eject_templates(&root, parsed.force) {
+        Ok(value) => value,
+        Err(err) => return fail(&err.to_string()),
+    };
+    for path in &report.written {
+        println!("wrote: {}", path.display());
+    }
+    for path in &report.skipped {
+        println!("skipped (exists): {}", path.display());
+    }
+    if !report.skipped.is_empty() {
+        println!("Use --force to overwrite existing templates.");
+    }
+    0
+}
+
+pub fn run_skills(args: Vec<String>) -> i32 {
+    let mut argv = vec!["skills".to_string()];
+    argv.extend(args);
+    let parsed = match SkillsArgs::try_parse_from(argv) {
+        Ok(value) => value,
+        Err(err) => return handle_clap_error(err),
+    };
+
+    let dir = if parsed.repo {
+        match resolve_root(parsed.loopr_root.as_deref()) {
+            Ok(root) => ops::skills::repo_skills_dir(&root),
+            Err(err) => return fail(&err.to_string()),
+        }
+    } else {
+        match ops::skills::global_skills_dir() {
+            Ok(value) => value,
+            Err(err) => return fail(&err.to_string()),
+        }
+    };
+    for name in &parsed.skills {
+        if ops::skills::bundled_skill(name).is_none() {
+            return fail(&format!(
+                "unknown skill: {} (bundled: {})",
+                name,
+                ops::skills::bundled_skill_names().join(", ")
+            ));
+        }
+    }
+
+    match parsed.action.as_str() {
+        "list" => {
+            println!("Skills dir: {}", dir.display());
+            let statuses = match ops::skills::list_skills(&dir) {
+                Ok(value) => value,
+                Err(err) => return fail(&err.to_string()),
+            };
+            for status in statuses {
+                if !parsed.skills.is_empty() && !parsed.skills.contains(&status.name) {
+                    continue;
+                }
+                match &status.stamp {
+                    Some(stamp) => println!(
+                        "{:<16} {:<10} (installed by loopr {} at {})",
+                        status.name,
+                        status.state.as_str(),
+                        stamp.loopr_version,
+                        stamp.installed_at
+                    ),
+                    None => println!("{:<16} {}", status.name, status.state.as_str()),
+                }
+            }
+            0
+        }
+        "diff" => {
+            let names = if parsed.skills.is_empty() {
+                ops::skills::bundled_skill_names()
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect()
+            } else {
+                parsed.skills.clone()
+            };
+            let mut drift = false;
+            for name in names {
+                match ops::skills::diff_skill(&dir, &name) {
+                    Ok(Some(diff)) => {
+                        drift = true;
+                        print!("{}", diff);
+                    }
+                    Ok(None) => {}
+                    Err(err) => return fail(&err.to_string()),
+                }
+            }
+            if drift { 1 } else { 0 }
+        }
+        action => {
+            let update = action == "update";
+            let report =
+                match ops::skills::install_skills(&dir, &parsed.skills, update, parsed.force) {
+                    Ok(value) => value,
+                    Err(err) => return fail(&err.to_string()),
+                };
+            for status in &report.written {
+                println!("installed: {} -> {}", status.name, status.path.display());
+            }
+            for (status, reason) in &report.skipped {
+                println!("skipped: {} ({})", status.name, reason);
+            }
+            0
+        }
+    }
+}
+
+fn warn_missing_skills(root: &Path, skills: &[String]) {
+    if let Ok(missing) = ops::skills::missing_skills(root, skills) {
+        for skill in missing {
+            eprintln!(
+                "warning: skill {} is not installed (run loopr skills install)",
+                skill
+            );
+        }
+    }
+}
+
+pub fn run_diff(args: Vec<String>) -> i32 {
+    let mut argv = vec!["diff".to_string()];
+    argv.extend(args);
+    let parsed = match DiffArgs::try_parse_from(argv) {
+        Ok(value) => value,
+        Err(err) => return handle_clap_error(err),
+    };
+
+    let root = match resolve_root(parsed.loopr_root.as_deref()) {
+        Ok(value) => value,
+        Err(err) => return fail(&err.to_string()),
+    };
+    let run_id = parsed
+        .run
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty());
+
+    let result = match (parsed.iteration, parsed.item.as_deref()) {
+        (Some(iteration), _) => ops::snapshot::diff_iteration(&root, run_id, iteration),
+        (None, Some(item)) => ops::snapshot::diff_item(&root, run_id, item),
+        (None, None) => return fail("diff requires --iteration or --item"),
+    };
+    match result {
+        Ok(diff) => {
+            print!("{}", diff);
+            0
+        }
+        Err(err) => fail(&err.to_string()),
+    }
+}
+
+pub fn run_restore(args: Vec<String>) -> i32 {
+    let mut argv = vec!["restore".to_string()];
+    argv.extend(args);
+    let parsed = match RestoreArgs::try_parse_from(argv) {
+        Ok(value) => value,
+        Err(err) => return handle_clap_error(err),
+    };
+
+    let root = match resolve_root(parsed.loopr_root.as_deref()) {
+        Ok(value) => value,
+        Err(err) => return fail(&err.to_string()),
+    };
+    let run_id = parsed
+        .run
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty());
+
+    let report = match ops::snapshot::restore_iteration(&root, run_id, parsed.iteration) {
+        Ok(value) => value,
+        Err(err) => return fail(&err.to_string()),
+    };
+    println!(
+        "Restored run {} iteration {}",
+        report.run_id, report.iteration
+    );
+    for path in &report.removed {
+        println!("  removed: {}", path);
+    }
+    println!("Backup:     {}", report.backup_ref);
+    0
+}
+
 pub fn run_version() -> i32 {
     println!("loopr {}", version::VERSION);
     if !version::COMMIT.is_empty() {
@@ -367,6 +1014,17 @@ fn handle_clap_error(err: clap::Error) -> i32 {
     }
 }
 
+fn resolve_root(loopr_root: Option<&str>) -> LooprResult<PathBuf> {
+    let loopr_root = loopr_root
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from);
+    let cwd =
+        std::env::current_dir().map_err(|err| LooprError::new(format!("current dir: {}", err)))?;
+    let (root, _) = ops::loopr_root::resolve_loopr_root(&cwd, loopr_root.as_deref())?;
+    Ok(root)
+}
+
 fn fail(message: &str) -> i32 {
     eprintln!("error: {}", message);
     1
@@ -381,6 +1039,16 @@ fn resolve_seed_prompt(raw: String) -> LooprResult<String> {
     if path.is_empty() {
         return Err(LooprError::new("seed prompt file path is empty"));
     }
+    if path == "-" {
+        let mut content = String::new();
+        std::io::stdin()
+            .read_to_string(&mut content)
+            .map_err(|err| LooprError::new(format!("read seed prompt from stdin: {}", err)))?;
+        if content.trim().is_empty() {
+            return Err(LooprError::new("seed prompt from stdin is empty"));
+        }
+        return Ok(content);
+    }
     let content = std::fs::read_to_string(path)
         .map_err(|err| LooprError::new(format!("read seed prompt file {}: {}", path, err)))?;
     Ok(content)
@@ -12,6 +12,12 @@ fn main() {
         "run" => loopr::cli::run_run(args[2..].to_vec()),
         "loop" => loopr::cli::run_loop(args[2..].to_vec()),
         "index" => loopr::cli::run_index(args[2..].to_vec()),
+        "validate" => loopr::cli::run_validate(args[2..].to_vec()),
+        "stale" => loopr::cli::run_stale(args[2..].to_vec()),
+        "diff" => loopr::cli::run_diff(args[2..].to_vec()),
+        "restore" => loopr::cli::run_restore(args[2..].to_vec()),
+        "prompts" => loopr::cli::run_prompts(args[2..].to_vec()),
+        "skills" => loopr::cli::run_skills(args[2..].to_vec()),
         "version" => loopr::cli::run_version(),
         "-h" | "--help" | "help" => {
             loopr::cli::usage();
@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::ops::test_results::xml_attr;
+use crate::ops::work_status::FileCoverage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverageFormat {
+    // lcov tracefiles (cargo llvm-cov --lcov, c8, coverage.py lcov, ...).
+    Lcov,
+    // Cobertura XML (cargo tarpaulin --out Xml, coverage.py xml, jest, ...).
+    Cobertura,
+}
+
+impl CoverageFormat {
+    pub fn parse(value: &str) -> Option<CoverageFormat> {
+        match value {
+            "lcov" => Some(CoverageFormat::Lcov),
+            "cobertura" => Some(CoverageFormat::Cobertura),
+            _ => None,
+        }
+    }
+}
+
+// Source file -> line number -> whether any hit was recorded for it.
+pub type LineCoverage = BTreeMap<String, BTreeMap<u32, bool>>;
+
+pub fn parse_coverage_report(format: CoverageFormat, data: &str) -> LineCoverage {
+    match format {
+        CoverageFormat::Lcov => parse_lcov(data),
+        CoverageFormat::Cobertura => parse_cobertura(data),
+    }
+}
+
+pub fn parse_lcov(data: &str) -> LineCoverage {
+    let mut files = LineCoverage::new();
+    let mut current: Option<String> = None;
+    for line in data.lines() {
+        let line = line.trim();
+        if let Some(path) = line.strip_prefix("SF:") {
+            current = Some(path.trim().to_string());
+        } else if line == "end_of_record" {
+            current = None;
+        } else if let Some(record) = line.strip_prefix("DA:")
+            && let Some(path) = &current
+        {
+            let mut fields = record.split(',');
+            let number = fields.next().and_then(|value| value.trim().parse().ok());
+            let hits = fields
+                .next()
+                .and_then(|value| value.trim().parse::<f64>().ok());
+            if let (Some(number), Some(hits)) = (number, hits) {
+                *files
+                    .entry(path.clone())
+                    .or_default()
+                    .entry(number)
+                    .or_insert(false) |= hits > 0.0;
+            }
+        }
+    }
+    files
+}
+
+// A small scanner for <class filename=...> elements and the <line number= hits=> elements inside
+// them; a line listed again under <methods> merges with the class-level entry.
+pub fn parse_cobertura(xml: &str) -> LineCoverage {
+    let mut files = LineCoverage::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find("<class") {
+        rest = &rest[start + "<class".len()..];
+        if !rest.starts_with(|ch: char| ch.is_whitespace()) {
+            continue;
+        }
+        let tag_end = match rest.find('>') {
+            Some(value) => value,
+            None => break,
+        };
+        let attrs = &rest[..tag_end];
+        rest = &rest[tag_end + 1..];
+        if attrs.ends_with('/') {
+            continue;
+        }
+        let end = rest.find("</class>").unwrap_or(rest.len());
+        let mut body = &rest[..end];
+        rest = &rest[end..];
+        let path = match xml_attr(attrs, "filename") {
+            Some(value) if !value.is_empty() => value,
+            _ => continue,
+        };
+        let lines = files.entry(path).or_default();
+        while let Some(start) = body.find("<line") {
+            body = &body[start + "<line".len()..];
+            if !body.starts_with(|ch: char| ch.is_whitespace()) {
+                continue;
+            }
+            let tag_end = body.find('>').unwrap_or(body.len());
+            let attrs = &body[..tag_end];
+            let number = xml_attr(attrs, "number").and_then(|value| value.parse().ok());
+            let hits = xml_attr(attrs, "hits").and_then(|value| value.parse::<f64>().ok());
+            if let (Some(number), Some(hits)) = (number, hits) {
+                *lines.entry(number).or_insert(false) |= hits > 0.0;
+            }
+        }
+    }
+    files
+}
+
+// Per-file line counts for the report files that belong to `changed` (paths relative to
+// `workdir`), or for every report file when the change set is unknown. Report paths are made
+// relative to `workdir` before matching.
+pub fn changed_file_coverage(
+    coverage: &LineCoverage,
+    workdir: &Path,
+    changed: Option<&[String]>,
+) -> Vec<FileCoverage> {
+    let prefix = format!("{}/", workdir.display());
+    let mut files: BTreeMap<String, BTreeMap<u32, bool>> = BTreeMap::new();
+    for (path, lines) in coverage {
+        let relative = path.strip_prefix(&prefix).unwrap_or(path);
+        let relative = relative.trim_start_matches("./");
+        let name = match changed {
+            Some(changed) => match changed_file(changed, relative) {
+                Some(file) => file.clone(),
+                None => continue,
+            },
+            None => relative.to_string(),
+        };
+        let merged = files.entry(name).or_default();
+        for (number, hit) in lines {
+            *merged.entry(*number).or_insert(false) |= *hit;
+        }
+    }
+    files
+        .into_iter()
+        .map(|(path, lines)| FileCoverage {
+            path,
+            lines_covered: lines.values().filter(|hit| **hit).count() as u64,
+            lines_total: lines.len() as u64,
+            uncovered_lines: lines
+                .iter()
+                .filter(|(_, hit)| !**hit)
+                .map(|(number, _)| *number)
+                .collect(),
+        })
+        .collect()
+}
+
+// An exact match on the relative path wins; otherwise a report path that ends with a changed
+// file (or the reverse) counts only when no other changed file matches the same way.
+fn changed_file<'a>(changed: &'a [String], report: &str) -> Option<&'a String> {
+    if let Some(file) = changed.iter().find(|file| file.as_str() == report) {
+        return Some(file);
+    }
+    let mut candidates = changed.iter().filter(|file| {
+        report.ends_with(&format!("/{}", file)) || file.ends_with(&format!("/{}", report))
+    });
+    match (candidates.next(), candidates.next()) {
+        (Some(file), None) => Some(file),
+        _ => None,
+    }
+}
@@ -25,6 +25,7 @@ fn collect_docs_entries(root: &Path) -> LooprResult<Vec<String>> {
     push_if_exists(&mut files, root, "README.md");
     push_if_exists(&mut files, root, "AGENTS.md");
     push_if_exists(&mut files, root, "loopr/config");
+    push_if_exists(&mut files, root, "loopr/steps.yaml");
 
     collect_dir_files(&mut files, root, root.join("docs"), &|path| is_markdown(path))?;
     collect_dir_files(
@@ -89,10 +90,11 @@ where
             .map_err(|err| LooprError::new(format!("metadata {}: {}", path.display(), err)))?;
         if metadata.is_dir() {
             collect_dir_files(files, root, path, filter)?;
-        } else if metadata.is_file() && filter(&path) {
-            if let Ok(rel) = path.strip_prefix(root) {
-                files.push(rel.display().to_string());
-            }
+        } else if metadata.is_file()
+            && filter(&path)
+            && let Ok(rel) = path.strip_prefix(root)
+        {
+            files.push(rel.display().to_string());
         }
     }
     Ok(())
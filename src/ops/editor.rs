@@ -0,0 +1,33 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::{LooprError, LooprResult};
+
+// Runs $VISUAL (or $EDITOR) through the shell so values like `code --wait` work.
+pub fn open_in_editor(root: &Path, files: &[String]) -> LooprResult<()> {
+    if files.is_empty() {
+        return Err(LooprError::new("no files to edit"));
+    }
+    let editor = std::env::var("VISUAL")
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|value| !value.trim().is_empty())
+        .ok_or_else(|| LooprError::new("EDITOR is not set"))?;
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg("editor")
+        .args(files)
+        .current_dir(root)
+        .status()
+        .map_err(|err| LooprError::new(format!("run {}: {}", editor, err)))?;
+    if !status.success() {
+        return Err(LooprError::new(format!(
+            "{} exited with {}",
+            editor,
+            status.code().unwrap_or(-1)
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde_yaml::Value;
+
+use crate::ops::fs::write_file_atomic;
+use crate::ops::run::RunStep;
+use crate::ops::validate::{FEATURE_ORDER_PATH, TASK_ORDER_PATH, TEST_ORDER_PATH};
+use crate::ops::work_plan::{FeatureSpec, load_feature_order};
+use crate::{LooprError, LooprResult};
+
+pub const SCOPED_STEPS: &[&str] = &["tasks", "tests", "execute"];
+
+pub struct OrderSnapshot {
+    pub path: String,
+    pub list_key: &'static str,
+    pub before: Option<String>,
+}
+
+pub fn load_feature(root: &Path, key: &str) -> LooprResult<(FeatureSpec, Vec<String>)> {
+    let order = load_feature_order(&root.join(FEATURE_ORDER_PATH))?;
+    let keys: Vec<String> = order.features.iter().map(|f| f.key.clone()).collect();
+    let feature = order
+        .features
+        .into_iter()
+        .find(|feature| feature.key == key)
+        .ok_or_else(|| {
+            LooprError::new(format!(
+                "unknown feature: {} (features: {})",
+                key,
+                keys.join(", ")
+            ))
+        })?;
+    Ok((feature, keys))
+}
+
+pub fn scope_steps(steps: &[RunStep], feature: &FeatureSpec) -> LooprResult<Vec<RunStep>> {
+    steps.iter().map(|step| scope_step(step, feature)).collect()
+}
+
+// Narrows `feature-*` globs to the feature's own files; order files stay whole and are merged
+// after the step.
+pub fn scope_step(step: &RunStep, feature: &FeatureSpec) -> LooprResult<RunStep> {
+    if !SCOPED_STEPS.contains(&step.name.as_str()) {
+        return Err(LooprError::new(format!(
+            "--feature only applies to the {} steps (got {})",
+            SCOPED_STEPS.join(", "),
+            step.name
+        )));
+    }
+    let scope = |pattern: &String| {
+        if pattern == "specs/feature-*.md" {
+            feature.file.clone()
+        } else {
+            pattern.replace("feature-*", &format!("feature-{}", feature.key))
+        }
+    };
+    let mut scoped = step.clone();
+    scoped.inputs = step.inputs.iter().map(scope).collect();
+    scoped.outputs = step.outputs.iter().map(scope).collect();
+    scoped.feature = Some(feature.clone());
+    Ok(scoped)
+}
+
+pub fn snapshot_orders(root: &Path, step: &RunStep) -> LooprResult<Vec<OrderSnapshot>> {
+    let mut snapshots = Vec::new();
+    for (path, list_key) in [(TASK_ORDER_PATH, "tasks"), (TEST_ORDER_PATH, "tests")] {
+        if !step.outputs.iter().any(|output| output == path) {
+            continue;
+        }
+        let full = root.join(path);
+        let before = match std::fs::read_to_string(&full) {
+            Ok(value) => Some(value),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(LooprError::new(format!("read {}: {}", full.display(), err))),
+        };
+        snapshots.push(OrderSnapshot {
+            path: path.to_string(),
+            list_key,
+            before,
+        });
+    }
+    Ok(snapshots)
+}
+
+// Keeps other features' entries from before the step and takes this feature's entries from the
+// rewritten file, placed where the feature's entries used to be. When the feature's tasks change
+// ids, the tests pointing at them follow.
+pub fn merge_scoped_orders(
+    root: &Path,
+    snapshots: &[OrderSnapshot],
+    feature: &str,
+    feature_keys: &[String],
+) -> LooprResult<()> {
+    let mut task_ids = HashMap::new();
+    for snapshot in snapshots {
+        let Some(before) = &snapshot.before else {
+            continue;
+        };
+        let path = root.join(&snapshot.path);
+        let after = std::fs::read_to_string(&path)
+            .map_err(|err| LooprError::new(format!("read {}: {}", path.display(), err)))?;
+        let merged = merge_order(
+            before,
+            &after,
+            snapshot.list_key,
+            feature,
+            feature_keys,
+            &task_ids,
+        )
+        .map_err(|err| LooprError::new(format!("merge {}: {}", path.display(), err)))?;
+        write_file_atomic(&path, merged.text.as_bytes(), 0o644)?;
+        if snapshot.list_key == "tasks" {
+            task_ids = merged.renumbered;
+        }
+    }
+    if task_ids.is_empty()
+        || snapshots
+            .iter()
+            .any(|snapshot| snapshot.list_key == "tests")
+    {
+        return Ok(());
+    }
+    let path = root.join(TEST_ORDER_PATH);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(value) => value,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(LooprError::new(format!("read {}: {}", path.display(), err))),
+    };
+    let remapped = remap_task_ids(&text, &task_ids)
+        .map_err(|err| LooprError::new(format!("update {}: {}", path.display(), err)))?;
+    if remapped != text {
+        write_file_atomic(&path, remapped.as_bytes(), 0o644)?;
+    }
+    Ok(())
+}
+
+pub struct MergedOrder {
+    pub text: String,
+    // Ids the feature's entries had before the step, mapped to the ids they have now.
+    pub renumbered: HashMap<i64, i64>,
+}
+
+// `task_ids` maps old task ids to new ones; kept tests' `task_id` is remapped through it. The
+// merged text reuses each entry's original lines so comments and layout survive.
+pub fn merge_order(
+    before: &str,
+    after: &str,
+    list_key: &str,
+    feature: &str,
+    feature_keys: &[String],
+    task_ids: &HashMap<i64, i64>,
+) -> LooprResult<MergedOrder> {
+    let old_doc: Value = serde_yaml::from_str(before)
+        .map_err(|err| LooprError::new(format!("parse previous version: {}", err)))?;
+    let mut new_doc: Value =
+        serde_yaml::from_str(after).map_err(|err| LooprError::new(format!("parse: {}", err)))?;
+    let old_items = order_items(&old_doc, list_key)?;
+    let new_items = order_items(&new_doc, list_key)?;
+
+    let mut kept = Vec::new();
+    let mut insert_at = None;
+    for (idx, item) in old_items.iter().enumerate() {
+        if item_feature(item, feature_keys) == Some(feature) {
+            insert_at.get_or_insert(kept.len());
+        } else {
+            kept.push(idx);
+        }
+    }
+    let scoped: Vec<usize> = (0..new_items.len())
+        .filter(|idx| item_feature(&new_items[*idx], feature_keys) == Some(feature))
+        .collect();
+
+    let used: HashMap<i64, &str> = kept
+        .iter()
+        .filter_map(|idx| item_id(&old_items[*idx]).map(|id| (id, item_key(&old_items[*idx]))))
+        .collect();
+    for idx in &scoped {
+        if let Some(id) = item_id(&new_items[*idx])
+            && let Some(owner) = used.get(&id)
+        {
+            return Err(LooprError::new(format!(
+                "id {} of {} is already used by {}; give the {} entries unused ids",
+                id,
+                item_key(&new_items[*idx]),
+                owner,
+                feature
+            )));
+        }
+    }
+    let new_ids: HashMap<&str, i64> = scoped
+        .iter()
+        .filter_map(|idx| item_id(&new_items[*idx]).map(|id| (item_key(&new_items[*idx]), id)))
+        .collect();
+    let renumbered: HashMap<i64, i64> = old_items
+        .iter()
+        .filter(|item| item_feature(item, feature_keys) == Some(feature))
+        .filter_map(|item| Some((item_id(item)?, *new_ids.get(item_key(item))?)))
+        .filter(|(old, new)| old != new)
+        .collect();
+
+    let remaps = [("depends_on", &renumbered), ("task_id", task_ids)];
+    let mut merged: Vec<Value> = kept
+        .iter()
+        .map(|idx| {
+            let mut item = old_items[*idx].clone();
+            for (field, ids) in remaps {
+                remap_value(&mut item, field, ids);
+            }
+            item
+        })
+        .collect();
+    let at = insert_at.unwrap_or(kept.len());
+    merged.splice(at..at, scoped.iter().map(|idx| new_items[*idx].clone()));
+
+    let blocks = match (split_order(before, list_key), split_order(after, list_key)) {
+        (Some(old), Some(new))
+            if old.items.len() == old_items.len() && new.items.len() == new_items.len() =>
+        {
+            let mut items: Vec<String> = kept
+                .iter()
+                .map(|idx| {
+                    let block = reindent(&old.items[*idx], old.indent, new.indent);
+                    remaps
+                        .iter()
+                        .fold(block, |block, (field, ids)| remap_block(&block, field, ids))
+                })
+                .collect();
+            items.splice(at..at, scoped.iter().map(|idx| new.items[*idx].clone()));
+            Some(OrderBlocks { items, ..new })
+        }
+        _ => None,
+    };
+    new_doc[list_key] = Value::Sequence(merged);
+    Ok(MergedOrder {
+        text: render_order(&new_doc, blocks)?,
+        renumbered,
+    })
+}
+
+pub fn remap_task_ids(text: &str, task_ids: &HashMap<i64, i64>) -> LooprResult<String> {
+    let mut doc: Value =
+        serde_yaml::from_str(text).map_err(|err| LooprError::new(format!("parse: {}", err)))?;
+    let count = order_items(&doc, "tests")?.len();
+    if let Some(Value::Sequence(items)) = doc.get_mut("tests") {
+        for item in items {
+            remap_value(item, "task_id", task_ids);
+        }
+    }
+    let blocks = split_order(text, "tests")
+        .filter(|blocks| blocks.items.len() == count)
+        .map(|blocks| OrderBlocks {
+            items: blocks
+                .items
+                .iter()
+                .map(|block| remap_block(block, "task_id", task_ids))
+                .collect(),
+            ..blocks
+        });
+    render_order(&doc, blocks)
+}
+
+// Uses the spliced text when it reads back as the merged document, and falls back to
+// re-serializing layouts the line splitter does not understand (flow lists, for one).
+fn render_order(doc: &Value, blocks: Option<OrderBlocks>) -> LooprResult<String> {
+    if let Some(blocks) = blocks {
+        let mut text = blocks.head;
+        for item in &blocks.items {
+            text.push_str(item);
+            if !text.ends_with('\n') {
+                text.push('\n');
+            }
+        }
+        text.push_str(&blocks.tail);
+        if serde_yaml::from_str::<Value>(&text).ok().as_ref() == Some(doc) {
+            return Ok(text);
+        }
+    }
+    serde_yaml::to_string(doc).map_err(|err| LooprError::new(format!("serialize: {}", err)))
+}
+
+struct OrderBlocks {
+    head: String,
+    indent: usize,
+    items: Vec<String>,
+    tail: String,
+}
+
+// Splits a block-style `<list_key>:` sequence into the text of each entry. Comments and blank
+// lines belong to the entry below them; ones after the last entry stay in the tail.
+fn split_order(text: &str, list_key: &str) -> Option<OrderBlocks> {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let header = format!("{}:", list_key);
+    let start = lines.iter().position(|line| line.trim_end() == header)? + 1;
+    let end = (start..lines.len())
+        .find(|idx| {
+            let line = lines[*idx];
+            !line.trim().is_empty() && !line.starts_with([' ', '\t', '#', '-'])
+        })
+        .unwrap_or(lines.len());
+    let is_entry = |line: &str, indent: usize| {
+        let trimmed = line.trim_start_matches(' ');
+        line.len() - trimmed.len() == indent
+            && (trimmed.starts_with("- ") || trimmed.trim_end() == "-")
+    };
+    let first = (start..end).find(|idx| lines[*idx].trim_start().starts_with('-'))?;
+    let indent = lines[first].len() - lines[first].trim_start_matches(' ').len();
+    let mut head = lines[..start].concat();
+    let mut items: Vec<String> = Vec::new();
+    let mut pending = String::new();
+    for line in &lines[start..end] {
+        let trimmed = line.trim();
+        if is_entry(line, indent) {
+            items.push(std::mem::take(&mut pending) + line);
+        } else if trimmed.is_empty() || trimmed.starts_with('#') {
+            pending.push_str(line);
+        } else {
+            let item = items.last_mut().unwrap_or(&mut head);
+            item.push_str(&std::mem::take(&mut pending));
+            item.push_str(line);
+        }
+    }
+    Some(OrderBlocks {
+        head,
+        indent,
+        items,
+        tail: pending + &lines[end..].concat(),
+    })
+}
+
+fn reindent(block: &str, from: usize, to: usize) -> String {
+    block
+        .split_inclusive('\n')
+        .map(|line| {
+            if line.trim().is_empty() {
+                line.to_string()
+            } else if to > from {
+                format!("{}{}", " ".repeat(to - from), line)
+            } else {
+                let spaces = line.len() - line.trim_start_matches(' ').len();
+                line[spaces.min(from - to)..].to_string()
+            }
+        })
+        .collect()
+}
+
+// Rewrites the ids of `field` in one entry's text, handling `field: 3`, `field: [1, 2]` and a
+// block list under `field:`. Lines without a remapped id are left untouched.
+fn remap_block(block: &str, field: &str, ids: &HashMap<i64, i64>) -> String {
+    if ids.is_empty() {
+        return block.to_string();
+    }
+    let prefix = format!("{}:", field);
+    let mut out = String::new();
+    let mut list_indent = None;
+    for line in block.split_inclusive('\n') {
+        let trimmed = line.trim_start_matches(' ');
+        let indent = line.len() - trimmed.len();
+        if let Some(at) = list_indent {
+            if indent >= at && trimmed.starts_with("- ") {
+                out.push_str(&remap_value_text(line, indent + 2, ids));
+                continue;
+            }
+            list_indent = None;
+        }
+        let body = trimmed.strip_prefix("- ").unwrap_or(trimmed);
+        let Some(value) = body.strip_prefix(&prefix) else {
+            out.push_str(line);
+            continue;
+        };
+        if value.trim().is_empty() {
+            list_indent = Some(line.len() - body.len());
+            out.push_str(line);
+            continue;
+        }
+        out.push_str(&remap_value_text(line, line.len() - value.len(), ids));
+    }
+    out
+}
+
+// Remaps the scalar or flow list that starts at byte `at` of `line`, keeping any trailing comment.
+fn remap_value_text(line: &str, at: usize, ids: &HashMap<i64, i64>) -> String {
+    let rest = &line[at..];
+    let newline = if rest.ends_with('\n') { "\n" } else { "" };
+    let rest = rest.trim_end_matches('\n');
+    let (value, comment) = rest.split_at(rest.find(" #").unwrap_or(rest.len()));
+    let value = value.trim();
+    let remap = |id: &str| {
+        id.parse::<i64>()
+            .ok()
+            .and_then(|id| ids.get(&id))
+            .map(|id| id.to_string())
+    };
+    let remapped = if let Some(inner) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        let parts: Vec<&str> = inner.split(',').map(str::trim).collect();
+        if parts.iter().all(|part| remap(part).is_none()) {
+            return line.to_string();
+        }
+        let parts: Vec<String> = parts
+            .iter()
+            .map(|part| remap(part).unwrap_or_else(|| part.to_string()))
+            .collect();
+        format!("[{}]", parts.join(", "))
+    } else {
+        match remap(value) {
+            Some(id) => id,
+            None => return line.to_string(),
+        }
+    };
+    format!("{} {}{}{}", &line[..at], remapped, comment, newline)
+}
+
+fn remap_value(item: &mut Value, field: &str, ids: &HashMap<i64, i64>) {
+    let remap = |value: &mut Value| {
+        if let Some(id) = value.as_i64().and_then(|id| ids.get(&id)) {
+            *value = Value::from(*id);
+        }
+    };
+    match item.get_mut(field) {
+        Some(Value::Sequence(values)) => values.iter_mut().for_each(remap),
+        Some(value) => remap(value),
+        None => {}
+    }
+}
+
+fn order_items<'a>(doc: &'a Value, list_key: &str) -> LooprResult<&'a Vec<Value>> {
+    match doc.get(list_key) {
+        Some(Value::Sequence(items)) => Ok(items),
+        Some(Value::Null) | None => Err(LooprError::new(format!("missing {} list", list_key))),
+        Some(_) => Err(LooprError::new(format!("{} must be a list", list_key))),
+    }
+}
+
+fn item_id(item: &Value) -> Option<i64> {
+    item.get("id").and_then(Value::as_i64)
+}
+
+fn item_key(item: &Value) -> &str {
+    item.get("key").and_then(Value::as_str).unwrap_or_default()
+}
+
+// The owning feature is the longest feature key that prefixes the item's file
+// (`specs/feature-<key>-task-...`) or, failing that, its key (`<key>-...`).
+fn item_feature<'a>(item: &Value, feature_keys: &'a [String]) -> Option<&'a str> {
+    let file = item.get("file").and_then(Value::as_str).unwrap_or_default();
+    let key = item.get("key").and_then(Value::as_str).unwrap_or_default();
+    let longest = |matches: &dyn Fn(&str) -> bool| {
+        feature_keys
+            .iter()
+            .filter(|feature| matches(feature))
+            .max_by_key(|feature| feature.len())
+            .map(String::as_str)
+    };
+    longest(&|feature| file.starts_with(&format!("specs/feature-{}-task-", feature)))
+        .or_else(|| longest(&|feature| key.starts_with(&format!("{}-", feature))))
+}
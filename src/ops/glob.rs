@@ -0,0 +1,97 @@
+use std::path::Path;
+
+use crate::{LooprError, LooprResult};
+
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains('*') || pattern.contains('?')
+}
+
+pub fn expand_glob(root: &Path, pattern: &str) -> LooprResult<Vec<String>> {
+    let segments: Vec<&str> = pattern.split('/').filter(|part| !part.is_empty()).collect();
+    let mut prefixes = vec![String::new()];
+    for (idx, segment) in segments.iter().enumerate() {
+        let last = idx + 1 == segments.len();
+        let mut next = Vec::new();
+        for prefix in &prefixes {
+            if !is_glob(segment) {
+                let candidate = join_rel(prefix, segment);
+                let path = root.join(&candidate);
+                if (last && path.is_file()) || (!last && path.is_dir()) {
+                    next.push(candidate);
+                }
+                continue;
+            }
+            let dir = root.join(prefix);
+            if !dir.is_dir() {
+                continue;
+            }
+            let entries = std::fs::read_dir(&dir)
+                .map_err(|err| LooprError::new(format!("read dir {}: {}", dir.display(), err)))?;
+            for entry in entries {
+                let entry =
+                    entry.map_err(|err| LooprError::new(format!("read dir entry: {}", err)))?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !glob_match(segment, &name) {
+                    continue;
+                }
+                let path = entry.path();
+                if (last && path.is_file()) || (!last && path.is_dir()) {
+                    next.push(join_rel(prefix, &name));
+                }
+            }
+        }
+        prefixes = next;
+    }
+    if segments.is_empty() {
+        return Ok(Vec::new());
+    }
+    prefixes.sort();
+    prefixes.dedup();
+    Ok(prefixes)
+}
+
+pub fn path_matches(pattern: &str, path: &str) -> bool {
+    let pattern_parts: Vec<&str> = pattern.split('/').filter(|part| !part.is_empty()).collect();
+    let path_parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+    pattern_parts.len() == path_parts.len()
+        && pattern_parts
+            .iter()
+            .zip(path_parts.iter())
+            .all(|(pattern, part)| glob_match(pattern, part))
+}
+
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<usize> = None;
+    let mut mark = 0;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some(p);
+            mark = t;
+            p += 1;
+        } else if let Some(star_idx) = star {
+            p = star_idx + 1;
+            mark += 1;
+            t = mark;
+        } else {
+            return false;
+        }
+    }
+    while p < pattern.len() && pattern[p] == '*' {
+        p += 1;
+    }
+    p == pattern.len()
+}
+
+fn join_rel(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
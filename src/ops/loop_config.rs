@@ -1,6 +1,9 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 
+use crate::ops::coverage::CoverageFormat;
+use crate::ops::test_results::TestResultFormat;
 use crate::{LooprError, LooprResult};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -8,7 +11,44 @@ pub struct LoopConfig {
     pub codex_timeout_minutes: i64,
     pub max_iterations: i64,
     pub max_missing_status: i64,
+    pub max_attempts_per_item: i64,
+    pub max_fix_rounds: i64,
     pub test_command: String,
+    pub test_timeout_minutes: i64,
+    pub test_reruns: i64,
+    pub test_result_format: Option<TestResultFormat>,
+    pub test_result_files: String,
+    pub gates: Vec<GateConfig>,
+    pub coverage_command: String,
+    pub coverage_report: String,
+    pub coverage_format: CoverageFormat,
+    pub coverage_min_percent: i64,
+    pub coverage_required: bool,
+    pub step_timeout_minutes: BTreeMap<String, i64>,
+}
+
+// A verification gate from GATES; it runs after the test commands in each phase it lists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GateConfig {
+    pub name: String,
+    pub command: String,
+    pub phases: Vec<String>,
+    pub required: bool,
+    pub timeout_minutes: Option<i64>,
+}
+
+impl LoopConfig {
+    // CODEX_TIMEOUT_MINUTES_<STEP> (step name upper-cased, '-' as '_') overrides the default.
+    pub fn codex_timeout_for_step(&self, step: &str) -> i64 {
+        self.step_timeout_minutes
+            .get(&step_config_suffix(step))
+            .copied()
+            .unwrap_or(self.codex_timeout_minutes)
+    }
+}
+
+fn step_config_suffix(step: &str) -> String {
+    step.to_uppercase().replace('-', "_")
 }
 
 pub fn default_loop_config() -> LoopConfig {
@@ -16,7 +56,20 @@ pub fn default_loop_config() -> LoopConfig {
         codex_timeout_minutes: 15,
         max_iterations: 50,
         max_missing_status: 2,
+        max_attempts_per_item: 3,
+        max_fix_rounds: 2,
         test_command: "just test".to_string(),
+        test_timeout_minutes: 30,
+        test_reruns: 0,
+        test_result_format: None,
+        test_result_files: String::new(),
+        gates: Vec::new(),
+        coverage_command: String::new(),
+        coverage_report: String::new(),
+        coverage_format: CoverageFormat::Lcov,
+        coverage_min_percent: 80,
+        coverage_required: true,
+        step_timeout_minutes: BTreeMap::new(),
     }
 }
 
@@ -28,6 +81,7 @@ pub fn load_loop_config(path: &Path) -> LooprResult<LoopConfig> {
         Err(err) => return Err(LooprError::new(format!("read {}: {}", path.display(), err))),
     };
 
+    let mut gate_lines = Vec::new();
     let mut line_no = 0;
     for line in data.lines() {
         line_no += 1;
@@ -64,9 +118,24 @@ pub fn load_loop_config(path: &Path) -> LooprResult<LoopConfig> {
                 key, line_no
             )));
         }
+        if key == "GATES" || key.starts_with("GATE_") {
+            gate_lines.push((key.to_string(), val.to_string(), line_no));
+            continue;
+        }
         apply_loop_config_value(&mut cfg, key, val, line_no)?;
     }
+    cfg.gates = parse_gates(&gate_lines)?;
 
+    if cfg.test_result_format == Some(TestResultFormat::Junit) && cfg.test_result_files.is_empty() {
+        return Err(LooprError::new(
+            "TEST_RESULT_FORMAT=junit needs TEST_RESULT_FILES (a glob of report files)",
+        ));
+    }
+    if !cfg.coverage_command.is_empty() && cfg.coverage_report.is_empty() {
+        return Err(LooprError::new(
+            "COVERAGE_COMMAND needs COVERAGE_REPORT (the report file it writes)",
+        ));
+    }
     Ok(cfg)
 }
 
@@ -84,12 +153,161 @@ fn apply_loop_config_value(
         "MAX_MISSING_STATUS" => {
             set_loop_config_int(&mut cfg.max_missing_status, key, val, line_no, true)
         }
+        "MAX_ATTEMPTS_PER_ITEM" => {
+            set_loop_config_int(&mut cfg.max_attempts_per_item, key, val, line_no, true)
+        }
+        "MAX_FIX_ROUNDS" => set_loop_config_int(&mut cfg.max_fix_rounds, key, val, line_no, false),
+        "TEST_TIMEOUT_MINUTES" => {
+            set_loop_config_int(&mut cfg.test_timeout_minutes, key, val, line_no, true)
+        }
+        "TEST_RERUNS" => set_loop_config_int(&mut cfg.test_reruns, key, val, line_no, false),
+        "TEST_RESULT_FORMAT" => {
+            cfg.test_result_format = Some(TestResultFormat::parse(val).ok_or_else(|| {
+                LooprError::new(format!(
+                    "invalid TEST_RESULT_FORMAT on line {}: \"{}\" (expected libtest-json, junit or tap)",
+                    line_no, val
+                ))
+            })?);
+            Ok(())
+        }
+        "TEST_RESULT_FILES" => {
+            cfg.test_result_files = val.to_string();
+            Ok(())
+        }
+        "COVERAGE_COMMAND" => {
+            cfg.coverage_command = val.to_string();
+            Ok(())
+        }
+        "COVERAGE_REPORT" => {
+            cfg.coverage_report = val.to_string();
+            Ok(())
+        }
+        "COVERAGE_FORMAT" => {
+            cfg.coverage_format = CoverageFormat::parse(val).ok_or_else(|| {
+                LooprError::new(format!(
+                    "invalid COVERAGE_FORMAT on line {}: \"{}\" (expected lcov or cobertura)",
+                    line_no, val
+                ))
+            })?;
+            Ok(())
+        }
+        "COVERAGE_MIN_PERCENT" => {
+            set_loop_config_int(&mut cfg.coverage_min_percent, key, val, line_no, false)?;
+            if cfg.coverage_min_percent > 100 {
+                return Err(LooprError::new(format!(
+                    "invalid COVERAGE_MIN_PERCENT on line {}: \"{}\" (expected 0 to 100)",
+                    line_no, val
+                )));
+            }
+            Ok(())
+        }
+        "COVERAGE_REQUIRED" => {
+            cfg.coverage_required = match val {
+                "true" => true,
+                "false" => false,
+                _ => {
+                    return Err(LooprError::new(format!(
+                        "invalid COVERAGE_REQUIRED on line {}: \"{}\" (expected true or false)",
+                        line_no, val
+                    )));
+                }
+            };
+            Ok(())
+        }
         "TEST_COMMAND" => {
             cfg.test_command = val.to_string();
             Ok(())
         }
-        _ => Ok(()),
+        _ => {
+            if let Some(step) = key.strip_prefix("CODEX_TIMEOUT_MINUTES_")
+                && !step.is_empty()
+            {
+                let mut minutes = 0;
+                set_loop_config_int(&mut minutes, key, val, line_no, true)?;
+                cfg.step_timeout_minutes
+                    .insert(step_config_suffix(step), minutes);
+            }
+            Ok(())
+        }
+    }
+}
+
+// GATES=fmt,lint names the gates in run order; each reads GATE_<NAME>_COMMAND (required),
+// GATE_<NAME>_PHASES (default validate), GATE_<NAME>_REQUIRED (default true) and
+// GATE_<NAME>_TIMEOUT_MINUTES (default TEST_TIMEOUT_MINUTES).
+fn parse_gates(lines: &[(String, String, usize)]) -> LooprResult<Vec<GateConfig>> {
+    let names = match lines.iter().rev().find(|(key, _, _)| key == "GATES") {
+        Some((_, value, _)) => value,
+        None => return Ok(Vec::new()),
+    };
+    let mut gates = Vec::new();
+    for name in names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        let prefix = format!("GATE_{}_", step_config_suffix(name));
+        let mut gate = GateConfig {
+            name: name.to_string(),
+            command: String::new(),
+            phases: vec!["validate".to_string()],
+            required: true,
+            timeout_minutes: None,
+        };
+        for (key, val, line_no) in lines {
+            let field = match key.strip_prefix(&prefix) {
+                Some(value) => value,
+                None => continue,
+            };
+            match field {
+                "COMMAND" => gate.command = val.clone(),
+                "PHASES" => {
+                    gate.phases = val
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|phase| !phase.is_empty())
+                        .map(str::to_string)
+                        .collect();
+                    if let Some(phase) = gate
+                        .phases
+                        .iter()
+                        .find(|phase| *phase != "tests" && *phase != "validate")
+                    {
+                        return Err(LooprError::new(format!(
+                            "invalid phase for {} on line {}: \"{}\" (expected tests or validate)",
+                            key, line_no, phase
+                        )));
+                    }
+                }
+                "REQUIRED" => {
+                    gate.required = match val.as_str() {
+                        "true" => true,
+                        "false" => false,
+                        _ => {
+                            return Err(LooprError::new(format!(
+                                "invalid bool for {} on line {}: \"{}\"",
+                                key, line_no, val
+                            )));
+                        }
+                    }
+                }
+                "TIMEOUT_MINUTES" => {
+                    let mut minutes = 0;
+                    set_loop_config_int(&mut minutes, key, val, *line_no, true)?;
+                    gate.timeout_minutes = Some(minutes);
+                }
+                _ => {}
+            }
+        }
+        if gate.command.is_empty() {
+            return Err(LooprError::new(format!(
+                "GATES lists {} but {}COMMAND is not set",
+                name, prefix
+            )));
+        }
+        gates.push(gate);
     }
+    Ok(gates)
 }
 
 fn set_loop_config_int(
@@ -1,7 +1,6 @@
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use serde::Serialize;
 use time::OffsetDateTime;
@@ -10,17 +9,25 @@ use time::format_description::well_known::Rfc3339;
 use crate::ops::codex::{
     CodexMode, CodexOptions, CodexRun, CodexSession, run_codex, run_codex_with_timeout,
 };
+use crate::ops::coverage::{changed_file_coverage, parse_coverage_report};
 use crate::ops::docs_index::write_docs_index;
 use crate::ops::fs::{ensure_dir, write_file_atomic};
 use crate::ops::loop_config::{LoopConfig, load_loop_config};
-use crate::ops::loop_status::{
-    LOOPR_STATUS_END, LOOPR_STATUS_START, LooprStatus, parse_loopr_status_from_log,
-};
+use crate::ops::loop_status::{LooprStatus, parse_loopr_status_from_log};
 use crate::ops::loopr_root::resolve_loopr_root;
-use crate::ops::run::{RunStep, build_prompt_lines, default_run_steps, find_step};
+use crate::ops::prompt_template::{PromptTemplates, TEMPLATE_LOOP, TEMPLATE_PER_TASK};
+use crate::ops::run::{RunStep, find_step, load_run_steps, prompt_context};
+use crate::ops::snapshot::{
+    Workspace, capture_released_snapshot, capture_snapshot, changed_files_since, create_workspace,
+    merge_workspace, new_run_id, remove_workspace, worktree_tree,
+};
+use crate::ops::test_command::{run_test_command, test_failure_excerpt};
+use crate::ops::test_results::{case_matches, collect_test_cases};
+use crate::ops::validate::{TASK_ORDER_PATH, TEST_ORDER_PATH, validate_orders};
 use crate::ops::work_plan::{TaskSpec, TestSpec, load_task_order, load_test_order};
 use crate::ops::work_status::{
-    TestRunResult, WorkItemState, WorkItemType, WorkStatusFile, ensure_item, load_work_status,
+    CoverageResult, GateResult, ItemSession, TestCaseOutcome, TestCaseResult, TestRunResult,
+    TestStability, WorkItemState, WorkItemType, WorkStatusFile, ensure_item, load_work_status,
     write_work_status,
 };
 use crate::{LooprError, LooprResult};
@@ -29,14 +36,23 @@ pub struct LoopOptions {
     pub loopr_root: Option<PathBuf>,
     pub max_iterations: i64,
     pub per_task: bool,
+    pub keep_going: bool,
+    pub jobs: usize,
     pub codex_args: Vec<String>,
     pub progress: Option<Box<dyn Fn(LoopEvent)>>,
 }
 
 pub struct LoopReport {
+    pub run_id: String,
     pub iterations: i64,
     pub exit_reason: String,
     pub last_session: Option<CodexSession>,
+    pub skipped: Vec<SkippedItem>,
+}
+
+pub struct SkippedItem {
+    pub key: String,
+    pub reason: String,
 }
 
 pub struct LoopEvent {
@@ -49,6 +65,11 @@ pub const LOOP_EVENT_START: &str = "start";
 pub const LOOP_EVENT_DONE: &str = "done";
 pub const LOOP_EVENT_EXIT: &str = "exit";
 pub const LOOP_EVENT_ERROR: &str = "error";
+pub const LOOP_EVENT_RETRY: &str = "retry";
+pub const LOOP_EVENT_SKIP: &str = "skip";
+pub const LOOP_EVENT_FIX: &str = "fix";
+pub const LOOP_EVENT_GATE: &str = "gate";
+pub const LOOP_EVENT_COVERAGE: &str = "coverage";
 
 struct LoopState {
     iteration: i64,
@@ -70,11 +91,11 @@ struct LoopStatusPayload {
 
 pub fn run_loop(opts: LoopOptions) -> LooprResult<LoopReport> {
     let cwd = std::env::current_dir()?;
-    let (root, _) = resolve_loopr_root(&cwd, opts.loopr_root.as_deref())?;
+    let (root, repo_id) = resolve_loopr_root(&cwd, opts.loopr_root.as_deref())?;
     write_docs_index(&root)?;
     let handoff_path = ensure_handoff(&root)?;
-    let step = find_step(&default_run_steps(), "execute")
-        .ok_or_else(|| LooprError::new("execute step not found"))?;
+    let step = find_step(&load_run_steps(&root)?, "execute")
+        .ok_or_else(|| LooprError::new("execute step not found (check loopr/steps.yaml)"))?;
 
     let loopr_dir = root.join("loopr");
     let loopr_state_dir = loopr_dir.join("state");
@@ -82,10 +103,18 @@ pub fn run_loop(opts: LoopOptions) -> LooprResult<LoopReport> {
     let status_path = loopr_state_dir.join("status.json");
 
     let mut cfg = load_loop_config(&config_path)?;
+    cfg.codex_timeout_minutes = cfg.codex_timeout_for_step(&step.name);
     if opts.max_iterations > 0 {
         cfg.max_iterations = opts.max_iterations;
     }
 
+    if opts.per_task {
+        validate_orders(&root, &[TASK_ORDER_PATH, TEST_ORDER_PATH])?.into_result()?;
+    }
+
+    let run_id = new_run_id()?;
+    capture_snapshot(&root, &run_id, 0, None)?;
+
     if opts.per_task {
         return run_loop_per_task(
             &opts,
@@ -95,17 +124,22 @@ pub fn run_loop(opts: LoopOptions) -> LooprResult<LoopReport> {
             status_path,
             loopr_state_dir,
             step,
+            run_id,
+            repo_id,
         );
     }
 
+    let templates = PromptTemplates::load(&root)?;
     let mut state = LoopState {
         iteration: 0,
         missing_status_count: 0,
     };
     let mut report = LoopReport {
+        run_id: run_id.clone(),
         iterations: 0,
         exit_reason: String::new(),
         last_session: None,
+        skipped: Vec::new(),
     };
 
     loop {
@@ -134,7 +168,7 @@ pub fn run_loop(opts: LoopOptions) -> LooprResult<LoopReport> {
             });
         }
 
-        let prompt = build_loop_prompt(&step, &handoff_path, &root, next_iteration);
+        let prompt = build_loop_prompt(&templates, &step, &handoff_path, &root, next_iteration)?;
         let mut args = vec!["--cd".to_string(), root.display().to_string()];
         args.extend(opts.codex_args.clone());
         args.push(prompt);
@@ -161,6 +195,7 @@ pub fn run_loop(opts: LoopOptions) -> LooprResult<LoopReport> {
         let mut run_error = codex_error(&run);
         report.last_session = Some(run.session);
         state.iteration = next_iteration;
+        capture_snapshot(&root, &run_id, state.iteration, None)?;
 
         let mut status = LooprStatus::default();
         let mut status_found = false;
@@ -248,6 +283,7 @@ pub fn run_loop(opts: LoopOptions) -> LooprResult<LoopReport> {
     Ok(report)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run_loop_per_task(
     opts: &LoopOptions,
     root: PathBuf,
@@ -256,7 +292,10 @@ fn run_loop_per_task(
     status_path: PathBuf,
     loopr_state_dir: PathBuf,
     step: RunStep,
+    run_id: String,
+    repo_id: String,
 ) -> LooprResult<LoopReport> {
+    let templates = PromptTemplates::load(&root)?;
     let task_order_path = root.join("specs").join("task-order.yaml");
     let test_order_path = root.join("specs").join("test-order.yaml");
     let task_order = load_task_order(&task_order_path)?;
@@ -301,15 +340,30 @@ fn run_loop_per_task(
     }
     work_status.updated_at = now;
     write_work_status(&work_status_path, &work_status)?;
+    let lp = PerTaskLoop {
+        opts,
+        cfg: &cfg,
+        root: &root,
+        run_id: &run_id,
+        templates: &templates,
+        step: &step,
+        handoff_path: &handoff_path,
+        status_path: &status_path,
+        work_status_path: &work_status_path,
+        workspaces_dir: loopr_state_dir.join("workspaces").join(&run_id),
+        test_logs_dir: loopr_state_dir.join("transcripts").join(&repo_id),
+    };
 
     let mut state = LoopState {
         iteration: 0,
         missing_status_count: 0,
     };
     let mut report = LoopReport {
+        run_id: run_id.clone(),
         iterations: 0,
         exit_reason: String::new(),
         last_session: None,
+        skipped: Vec::new(),
     };
 
     loop {
@@ -329,7 +383,9 @@ fn run_loop_per_task(
             break;
         }
 
-        if let Some(reason) = blocked_reason(&work_status) {
+        if !opts.keep_going
+            && let Some(reason) = blocked_reason(&work_status)
+        {
             report.exit_reason = reason.clone();
             write_loop_status(
                 &status_path,
@@ -374,11 +430,18 @@ fn run_loop_per_task(
         let selection = match selection {
             Some(value) => value,
             None => {
-                report.exit_reason = "blocked".to_string();
+                // With --keep-going, failed items are only reported once nothing else can run.
+                report.exit_reason =
+                    blocked_reason(&work_status).unwrap_or_else(|| "blocked".to_string());
                 write_loop_status(
                     &status_path,
                     LoopStatusPayload {
-                        state: "blocked".to_string(),
+                        state: if report.exit_reason == "error" {
+                            "error"
+                        } else {
+                            "blocked"
+                        }
+                        .to_string(),
                         iteration: state.iteration,
                         updated_at: now_rfc3339()?,
                         exit_reason: Some(report.exit_reason.clone()),
@@ -390,407 +453,1376 @@ fn run_loop_per_task(
             }
         };
 
-        let next_iteration = state.iteration + 1;
-        let details = selection.describe();
-        if let Some(progress) = &opts.progress {
-            progress(LoopEvent {
-                iteration: next_iteration,
-                status: LOOP_EVENT_START.to_string(),
-                details,
-            });
+        let remaining = if cfg.max_iterations > 0 {
+            cfg.max_iterations - state.iteration
+        } else {
+            i64::MAX
+        };
+        let batch_size = remaining.min(opts.jobs.max(1) as i64);
+        let mut batch = vec![prepare_item(
+            &lp,
+            &mut work_status,
+            selection,
+            state.iteration + 1,
+        )?];
+        while (batch.len() as i64) < batch_size {
+            let selection = select_next_item(
+                &tasks,
+                &tests,
+                &task_by_id,
+                &test_by_id,
+                &tests_by_task,
+                &work_status,
+            );
+            let Some(selection) = selection else {
+                break;
+            };
+            let iteration = state.iteration + batch.len() as i64 + 1;
+            batch.push(prepare_item(&lp, &mut work_status, selection, iteration)?);
         }
-
-        let item_key = selection.key().to_string();
-        let item_type = selection.item_type();
-        let phase = selection.phase().to_string();
-        update_item_in_progress(&mut work_status, &item_key, &phase, &now_rfc3339()?);
         write_work_status(&work_status_path, &work_status)?;
 
-        let prompt_inputs = selection.prompt_inputs(&step);
-        let prompt = build_per_task_prompt(
-            &step,
-            &handoff_path,
-            &root,
-            next_iteration,
-            selection.key(),
-            item_type,
-            &phase,
-            &prompt_inputs,
-            selection.is_pbt(),
-        );
+        // Items in a batch run concurrently but are merged and recorded one at a time, in the
+        // order they were selected. An item whose agent could not be started at all is handed
+        // back and its error returned once the rest of the batch is recorded or released.
+        let runs = run_items(&lp, &batch);
+        let mut pending = batch.into_iter().zip(runs);
+        let mut flow = ItemFlow::Continue;
+        let mut failure: Option<LooprError> = None;
+        for (item, item_run) in pending.by_ref() {
+            let item_run = match item_run {
+                Ok(value) => value,
+                Err(err) => {
+                    release_item(&lp, &mut work_status, item)?;
+                    failure.get_or_insert(err);
+                    continue;
+                }
+            };
+            match finish_item(
+                &lp,
+                &mut state,
+                &mut report,
+                &mut work_status,
+                item,
+                item_run,
+            ) {
+                Ok(value) => flow = value,
+                Err(err) => {
+                    failure = Some(err);
+                    flow = ItemFlow::Stop;
+                }
+            }
+            if flow == ItemFlow::Stop {
+                break;
+            }
+        }
+        if flow == ItemFlow::Stop || failure.is_some() {
+            for (item, _) in pending {
+                release_item(&lp, &mut work_status, item)?;
+            }
+            write_work_status(&work_status_path, &work_status)?;
+        }
+        if let Some(err) = failure {
+            return Err(err);
+        }
+        if flow == ItemFlow::Stop {
+            break;
+        }
+    }
 
-        let run = run_codex_for_prompt(&root, &cfg, &opts.codex_args, prompt)?;
-        let mut run_error = codex_error(&run);
-        report.last_session = Some(run.session);
-        state.iteration = next_iteration;
+    report.iterations = state.iteration;
+    if opts.keep_going {
+        report.skipped = skipped_items(&tasks, &tests, &task_by_id, &test_by_id, &work_status);
+    }
+    Ok(report)
+}
 
-        let mut status = LooprStatus::default();
-        let mut status_found = false;
-        if let Some(session) = &report.last_session {
-            match parse_loopr_status_from_log(&session.log_path) {
-                Ok((parsed, found)) => {
-                    status = parsed;
-                    status_found = found;
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ItemFlow {
+    Continue,
+    Stop,
+}
+
+struct PerTaskLoop<'a> {
+    opts: &'a LoopOptions,
+    cfg: &'a LoopConfig,
+    root: &'a Path,
+    run_id: &'a str,
+    templates: &'a PromptTemplates,
+    step: &'a RunStep,
+    handoff_path: &'a Path,
+    status_path: &'a Path,
+    work_status_path: &'a Path,
+    workspaces_dir: PathBuf,
+    test_logs_dir: PathBuf,
+}
+
+struct PreparedItem {
+    selection: WorkItemSelection,
+    key: String,
+    iteration: i64,
+    attempt: u32,
+    phase: String,
+    prompt_inputs: Vec<String>,
+    prompt: String,
+    workdir: PathBuf,
+    test_log: PathBuf,
+    workspace: Option<Workspace>,
+    // The tree the item started from, for COVERAGE_COMMAND's change set; None outside git.
+    base_tree: Option<String>,
+}
+
+// The parts of the per-task loop an item run needs; shared with the worker threads under --jobs.
+struct ItemRunner<'a> {
+    root: &'a Path,
+    cfg: &'a LoopConfig,
+    codex_args: &'a [String],
+    templates: &'a PromptTemplates,
+    step: &'a RunStep,
+    handoff_path: &'a Path,
+}
+
+struct ItemRun {
+    run: CodexRun,
+    run_error: Option<LooprError>,
+    status: LooprStatus,
+    status_found: bool,
+    test_run: Option<LooprResult<TestRunResult>>,
+    gates: Vec<GateResult>,
+    coverage: Option<CoverageResult>,
+    sessions: Vec<ItemSession>,
+}
+
+impl ItemRun {
+    fn fail(&mut self, err: LooprError) {
+        if self.status.summary.is_empty() {
+            self.status.summary = err.message.clone();
+        }
+        self.status.status = "ERROR".to_string();
+        self.status.exit_signal = false;
+        self.run_error = Some(err);
+    }
+
+    fn record_session(&mut self, item: &PreparedItem, round: u32) {
+        self.sessions.push(ItemSession {
+            attempt: item.attempt,
+            round,
+            phase: item.phase.clone(),
+            transcript: self.run.session.log_path.display().to_string(),
+            status: self.status.status.clone(),
+            tests_passed: None,
+            gates_passed: None,
+            coverage_passed: None,
+        });
+    }
+
+    // Runs the test commands, then the phase's gates unless the test run errored, timed out, was
+    // flaky, or failed validation, then COVERAGE_COMMAND once a task's tests and gates pass.
+    fn run_tests(&mut self, cfg: &LoopConfig, item: &PreparedItem) {
+        let result = run_item_tests(&item.workdir, cfg, &item.selection, &item.test_log);
+        let run_gates_now = result.as_ref().is_ok_and(|result| {
+            !result.timed_out
+                && result.stability != Some(TestStability::Flaky)
+                && (result.passed || item.selection.test_phase() == "tests")
+        });
+        if let Some(session) = self.sessions.last_mut() {
+            session.tests_passed = result.as_ref().ok().map(|result| result.passed);
+        }
+        self.test_run = Some(result);
+        self.gates = Vec::new();
+        self.coverage = None;
+        if !run_gates_now {
+            return;
+        }
+        if !cfg.gates.is_empty() {
+            match run_gates(&item.workdir, cfg, &item.selection, &item.test_log) {
+                Ok(gates) => {
+                    self.gates = gates;
+                    let passed = self.failed_gate().is_none();
+                    if let Some(session) = self.sessions.last_mut() {
+                        session.gates_passed = Some(passed);
+                    }
                 }
                 Err(err) => {
-                    if run_error.is_none() {
-                        run_error = Some(err);
-                    }
+                    self.fail(err);
+                    return;
                 }
             }
         }
-
-        if let Some(err) = &run_error {
-            if status.summary.is_empty() {
-                status.summary = err.message.clone();
+        let tests_passed = matches!(&self.test_run, Some(Ok(result)) if result.passed);
+        if cfg.coverage_command.is_empty()
+            || !matches!(item.selection, WorkItemSelection::Task { .. })
+            || !tests_passed
+            || self.failed_gate().is_some()
+        {
+            return;
+        }
+        match run_coverage(cfg, item) {
+            Ok(coverage) => {
+                if let Some(session) = self.sessions.last_mut() {
+                    session.coverage_passed = Some(coverage.passed);
+                }
+                self.coverage = Some(coverage);
             }
-            status.status = "ERROR".to_string();
-            status.exit_signal = false;
+            Err(err) => self.fail(err),
         }
+    }
 
-        if status_found {
-            state.missing_status_count = 0;
-        } else {
-            state.missing_status_count += 1;
-            if state.missing_status_count >= cfg.max_missing_status {
-                mark_item_failed(
-                    &mut work_status,
-                    &item_key,
-                    WorkItemState::Error,
-                    "missing LOOPR_STATUS block",
-                    &now_rfc3339()?,
-                );
-                write_work_status(&work_status_path, &work_status)?;
-                report.exit_reason = "missing_status".to_string();
-                write_loop_status(
-                    &status_path,
-                    LoopStatusPayload {
-                        state: "error".to_string(),
-                        iteration: state.iteration,
-                        updated_at: now_rfc3339()?,
-                        exit_reason: Some(report.exit_reason.clone()),
-                        last_summary: None,
-                        last_error: Some("missing LOOPR_STATUS block".to_string()),
-                    },
-                )?;
-                if let Some(progress) = &opts.progress {
-                    progress(LoopEvent {
-                        iteration: state.iteration,
-                        status: LOOP_EVENT_ERROR.to_string(),
-                        details: "missing LOOPR_STATUS block".to_string(),
-                    });
-                }
+    fn failed_gate(&self) -> Option<&GateResult> {
+        self.gates
+            .iter()
+            .find(|gate| gate.required && !gate.run.passed)
+    }
+
+    fn failed_coverage(&self) -> Option<&CoverageResult> {
+        self.coverage
+            .as_ref()
+            .filter(|coverage| coverage.required && !coverage.passed)
+    }
+}
+
+fn prepare_item(
+    lp: &PerTaskLoop,
+    work_status: &mut WorkStatusFile,
+    selection: WorkItemSelection,
+    iteration: i64,
+) -> LooprResult<PreparedItem> {
+    if let Some(progress) = &lp.opts.progress {
+        progress(LoopEvent {
+            iteration,
+            status: LOOP_EVENT_START.to_string(),
+            details: selection.describe(),
+        });
+    }
+
+    let key = selection.key().to_string();
+    let phase = selection.phase().to_string();
+    let previous = work_status
+        .items
+        .get(&key)
+        .filter(|item| item.attempts > 0 && item.last_error.is_some());
+    let previous_failure = previous
+        .and_then(|item| item.last_error.clone())
+        .unwrap_or_default();
+    let test_failure = previous
+        .and_then(|item| item.last_test.as_ref())
+        .filter(|test| !test.passed && !test.log_path.is_empty())
+        .map(|test| test_failure_excerpt(Path::new(&test.log_path)))
+        .unwrap_or_default();
+    update_item_in_progress(work_status, &key, &phase, &now_rfc3339()?);
+    let attempt = work_status
+        .items
+        .get(&key)
+        .map(|item| item.attempts)
+        .unwrap_or(1);
+
+    // With --jobs, every item works in its own git worktree; the docs index is ignored state, so
+    // it is copied in for the prompt to point at.
+    let workspace = if lp.opts.jobs > 1 {
+        let workspace = create_workspace(lp.root, &lp.workspaces_dir.join(&key), &key)?;
+        let index = lp.root.join("loopr").join("state").join("docs-index.txt");
+        if index.exists() {
+            let target = workspace
+                .path
+                .join("loopr")
+                .join("state")
+                .join("docs-index.txt");
+            ensure_dir(target.parent().unwrap_or(&workspace.path), 0o755)?;
+            std::fs::copy(&index, &target)
+                .map_err(|err| LooprError::new(format!("copy {}: {}", index.display(), err)))?;
+        }
+        Some(workspace)
+    } else {
+        None
+    };
+    let workdir = workspace
+        .as_ref()
+        .map(|workspace| workspace.path.clone())
+        .unwrap_or_else(|| lp.root.to_path_buf());
+    let stored_tree = work_status
+        .items
+        .get(&key)
+        .and_then(|item| item.base_tree.clone());
+    let base_tree = if lp.cfg.coverage_command.is_empty() {
+        None
+    } else if stored_tree.is_some() {
+        stored_tree
+    } else if let Some(workspace) = &workspace {
+        Some(workspace.base_tree().to_string())
+    } else {
+        worktree_tree(lp.root)?
+    };
+    if let Some(item) = work_status.items.get_mut(&key)
+        && item.base_tree.is_none()
+    {
+        item.base_tree = base_tree.clone();
+    }
+
+    let prompt_inputs = selection.prompt_inputs(lp.step);
+    let prompt = build_per_task_prompt(
+        lp.templates,
+        lp.step,
+        lp.handoff_path,
+        &workdir,
+        iteration,
+        &key,
+        selection.item_type(),
+        &phase,
+        &prompt_inputs,
+        selection.is_pbt(),
+        &RetryContext {
+            attempt,
+            max_attempts: lp.cfg.max_attempts_per_item,
+            previous_failure: &previous_failure,
+            test_failure: &test_failure,
+            fix_round: 0,
+            max_fix_rounds: lp.cfg.max_fix_rounds,
+        },
+    )?;
+    let test_log = lp
+        .test_logs_dir
+        .join(format!("test-{}-{}-{}.log", lp.run_id, iteration, key));
+    Ok(PreparedItem {
+        selection,
+        key,
+        iteration,
+        attempt,
+        phase,
+        prompt_inputs,
+        prompt,
+        workdir,
+        test_log,
+        workspace,
+        base_tree,
+    })
+}
+
+fn run_items(lp: &PerTaskLoop, batch: &[PreparedItem]) -> Vec<LooprResult<ItemRun>> {
+    let runner = &ItemRunner {
+        root: lp.root,
+        cfg: lp.cfg,
+        codex_args: &lp.opts.codex_args,
+        templates: lp.templates,
+        step: lp.step,
+        handoff_path: lp.handoff_path,
+    };
+    if lp.opts.jobs <= 1 {
+        return batch.iter().map(|item| run_item(runner, item)).collect();
+    }
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = batch
+            .iter()
+            .map(|item| scope.spawn(move || run_item(runner, item)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err(LooprError::new("item run panicked")))
+            })
+            .collect()
+    })
+}
+
+// Runs the agent session and, unless it failed or reported BLOCKED/ERROR, the test command in the
+// item's working directory. A task whose tests, required gates or coverage check still fail gets
+// up to MAX_FIX_ROUNDS more sessions, each prompted with the failing output and followed by
+// another test run.
+fn run_item(runner: &ItemRunner, item: &PreparedItem) -> LooprResult<ItemRun> {
+    let (root, cfg, codex_args) = (runner.root, runner.cfg, runner.codex_args);
+    let run = run_codex_for_prompt(root, &item.workdir, cfg, codex_args, item.prompt.clone())?;
+    let (status, status_found, run_error) = read_session_status(&run);
+    let mut item_run = ItemRun {
+        run,
+        run_error: None,
+        status,
+        status_found,
+        test_run: None,
+        gates: Vec::new(),
+        coverage: None,
+        sessions: Vec::new(),
+    };
+    if let Some(err) = run_error {
+        item_run.fail(err);
+        item_run.record_session(item, 0);
+        return Ok(item_run);
+    }
+    item_run.record_session(item, 0);
+    if item_run.status.status == "BLOCKED" || item_run.status.status == "ERROR" {
+        return Ok(item_run);
+    }
+    item_run.run_tests(cfg, item);
+
+    let mut round = 0;
+    while matches!(item.selection, WorkItemSelection::Task { .. })
+        && i64::from(round) < cfg.max_fix_rounds
+    {
+        if item_run.run_error.is_some() {
+            break;
+        }
+        let failed_log = match &item_run.test_run {
+            Some(Ok(result))
+                if (!result.passed && result.stability != Some(TestStability::Flaky))
+                    || item_run.failed_gate().is_some()
+                    || item_run.failed_coverage().is_some() =>
+            {
+                result.log_path.clone()
+            }
+            _ => break,
+        };
+        round += 1;
+        let test_failure = test_failure_excerpt(Path::new(&failed_log));
+        let prompt = match build_per_task_prompt(
+            runner.templates,
+            runner.step,
+            runner.handoff_path,
+            &item.workdir,
+            item.iteration,
+            &item.key,
+            item.selection.item_type(),
+            &item.phase,
+            &item.prompt_inputs,
+            item.selection.is_pbt(),
+            &RetryContext {
+                attempt: item.attempt,
+                max_attempts: cfg.max_attempts_per_item,
+                previous_failure: "",
+                test_failure: &test_failure,
+                fix_round: round,
+                max_fix_rounds: cfg.max_fix_rounds,
+            },
+        ) {
+            Ok(value) => value,
+            Err(err) => {
+                item_run.fail(err);
+                break;
+            }
+        };
+        // A fix round that cannot start fails the attempt like any other session error.
+        item_run.run = match run_codex_for_prompt(root, &item.workdir, cfg, codex_args, prompt) {
+            Ok(value) => value,
+            Err(err) => {
+                item_run.fail(err);
                 break;
             }
+        };
+        let (status, _, run_error) = read_session_status(&item_run.run);
+        item_run.status = status;
+        if let Some(err) = run_error {
+            item_run.fail(err);
+            item_run.record_session(item, round);
+            break;
+        }
+        item_run.record_session(item, round);
+        if item_run.status.status == "BLOCKED" || item_run.status.status == "ERROR" {
+            break;
         }
+        item_run.run_tests(cfg, item);
+    }
+    Ok(item_run)
+}
 
-        if let Some(err) = run_error {
+fn read_session_status(run: &CodexRun) -> (LooprStatus, bool, Option<LooprError>) {
+    let run_error = codex_error(run);
+    match parse_loopr_status_from_log(&run.session.log_path) {
+        Ok((status, found)) => (status, found, run_error),
+        Err(err) => (LooprStatus::default(), false, run_error.or(Some(err))),
+    }
+}
+
+// Runs the item's test commands. With TEST_RERUNS, an ambiguous outcome (a pass right after the
+// tests were written, a failure after implementation) is repeated and classified as stable when
+// every repeat agrees with the first run, flaky otherwise.
+fn run_item_tests(
+    workdir: &Path,
+    cfg: &LoopConfig,
+    selection: &WorkItemSelection,
+    log_path: &Path,
+) -> LooprResult<TestRunResult> {
+    let mut result = run_test_commands(workdir, cfg, selection, log_path)?;
+    let ambiguous = match selection {
+        WorkItemSelection::Test { .. } => result.passed,
+        WorkItemSelection::Task { .. } => !result.passed,
+    };
+    if cfg.test_reruns == 0 || !ambiguous || result.timed_out {
+        return Ok(result);
+    }
+    for _ in 0..cfg.test_reruns {
+        let rerun = run_test_commands(workdir, cfg, selection, log_path)?;
+        result.reruns.push(rerun.passed);
+    }
+    let stable = result.reruns.iter().all(|passed| *passed == result.passed);
+    result.stability = Some(if stable {
+        TestStability::Stable
+    } else {
+        TestStability::Flaky
+    });
+    Ok(result)
+}
+
+// Runs the item's test commands in order and stops at the first one that fails. With
+// TEST_RESULT_FORMAT every command runs (a red suite may be red for other items' cases), the
+// cases are parsed from the output or report files, and declared `cases` decide the verdict.
+fn run_test_commands(
+    workdir: &Path,
+    cfg: &LoopConfig,
+    selection: &WorkItemSelection,
+    log_path: &Path,
+) -> LooprResult<TestRunResult> {
+    let env = selection.test_env();
+    let log_start = std::fs::metadata(log_path)
+        .map(|meta| meta.len())
+        .unwrap_or(0);
+    let started = SystemTime::now();
+    let mut result: Option<TestRunResult> = None;
+    for (command, timeout_minutes) in
+        selection.test_commands(&cfg.test_command, cfg.test_timeout_minutes)
+    {
+        let outcome = run_test_command(
+            workdir,
+            &command,
+            selection.test_phase(),
+            &env,
+            log_path,
+            Duration::from_secs((timeout_minutes as u64) * 60),
+        )?;
+        let (passed, timed_out) = (outcome.passed, outcome.timed_out);
+        if result.as_ref().is_none_or(|first| first.passed) {
+            result = Some(outcome);
+        }
+        if !passed && (cfg.test_result_format.is_none() || timed_out) {
+            break;
+        }
+    }
+    let mut result = result.ok_or_else(|| LooprError::new("TEST_COMMAND is empty"))?;
+    let format = match cfg.test_result_format {
+        Some(value) if !result.timed_out => value,
+        _ => return Ok(result),
+    };
+    let data = std::fs::read(log_path)
+        .map_err(|err| LooprError::new(format!("read {}: {}", log_path.display(), err)))?;
+    let output = String::from_utf8_lossy(&data[(log_start as usize).min(data.len())..]);
+    result.cases = collect_test_cases(format, workdir, &cfg.test_result_files, &output, started)?;
+    apply_case_verdict(&mut result, &selection.test_cases());
+    Ok(result)
+}
+
+// The item's own declared cases decide `passed`: all of them ran and none failed. The suite's
+// exit code stays in `exit_code`.
+fn apply_case_verdict(result: &mut TestRunResult, patterns: &[String]) {
+    if patterns.is_empty() {
+        return;
+    }
+    let mut failed = false;
+    for pattern in patterns {
+        let matched: Vec<&TestCaseResult> = result
+            .cases
+            .iter()
+            .filter(|case| case.outcome != TestCaseOutcome::Skipped)
+            .filter(|case| case_matches(pattern, &case.name))
+            .collect();
+        if matched.is_empty() {
+            result.missing_cases.push(pattern.clone());
+        }
+        failed |= matched
+            .iter()
+            .any(|case| case.outcome == TestCaseOutcome::Failed);
+    }
+    result.passed = !failed && result.missing_cases.is_empty();
+}
+
+// Declared cases that passed, by name; used to check that the tests phase left them all red.
+fn passing_cases(result: &TestRunResult, patterns: &[String]) -> Vec<String> {
+    result
+        .cases
+        .iter()
+        .filter(|case| case.outcome == TestCaseOutcome::Passed)
+        .filter(|case| {
+            patterns
+                .iter()
+                .any(|pattern| case_matches(pattern, &case.name))
+        })
+        .map(|case| case.name.clone())
+        .collect()
+}
+
+// Runs the gates that apply to the item's phase in config order, into the item's test log. The
+// first failing required gate stops the rest.
+fn run_gates(
+    workdir: &Path,
+    cfg: &LoopConfig,
+    selection: &WorkItemSelection,
+    log_path: &Path,
+) -> LooprResult<Vec<GateResult>> {
+    let phase = selection.test_phase();
+    let env = selection.test_env();
+    let mut results = Vec::new();
+    for gate in cfg
+        .gates
+        .iter()
+        .filter(|gate| gate.phases.iter().any(|value| value == phase))
+    {
+        let timeout_minutes = gate.timeout_minutes.unwrap_or(cfg.test_timeout_minutes);
+        let run = run_test_command(
+            workdir,
+            &gate.command,
+            phase,
+            &env,
+            log_path,
+            Duration::from_secs((timeout_minutes as u64) * 60),
+        )?;
+        let stop = gate.required && !run.passed;
+        results.push(GateResult {
+            name: gate.name.clone(),
+            required: gate.required,
+            run,
+        });
+        if stop {
+            break;
+        }
+    }
+    Ok(results)
+}
+
+// Runs COVERAGE_COMMAND into the item's test log and measures the report's line coverage over the
+// files the item changed since it started (every file in the report outside git). A failed
+// command or an unreadable report fails the check; a change set without measured lines passes.
+fn run_coverage(cfg: &LoopConfig, item: &PreparedItem) -> LooprResult<CoverageResult> {
+    let report_path = item.workdir.join(&cfg.coverage_report);
+    let mut result = CoverageResult {
+        command: cfg.coverage_command.clone(),
+        report: report_path.display().to_string(),
+        ran_at: now_rfc3339()?,
+        log_path: item.test_log.display().to_string(),
+        lines_covered: 0,
+        lines_total: 0,
+        percent: None,
+        min_percent: cfg.coverage_min_percent,
+        required: cfg.coverage_required,
+        passed: false,
+        files: Vec::new(),
+        error: None,
+    };
+    let run = run_test_command(
+        &item.workdir,
+        &cfg.coverage_command,
+        "coverage",
+        &item.selection.test_env(),
+        &item.test_log,
+        Duration::from_secs((cfg.test_timeout_minutes as u64) * 60),
+    )?;
+    if !run.passed {
+        result.error = Some(format!("coverage command failed ({})", gate_outcome(&run)));
+        return Ok(result);
+    }
+    let data = match std::fs::read_to_string(&report_path) {
+        Ok(value) => value,
+        Err(err) => {
+            result.error = Some(format!("read {}: {}", report_path.display(), err));
+            return Ok(result);
+        }
+    };
+    let changed = match &item.base_tree {
+        Some(tree) => Some(changed_files_since(&item.workdir, tree)?),
+        None => None,
+    };
+    let coverage = parse_coverage_report(cfg.coverage_format, &data);
+    result.files = changed_file_coverage(&coverage, &item.workdir, changed.as_deref());
+    result.lines_covered = result.files.iter().map(|file| file.lines_covered).sum();
+    result.lines_total = result.files.iter().map(|file| file.lines_total).sum();
+    result.percent = (result.lines_covered * 1000)
+        .checked_div(result.lines_total)
+        .map(|tenths| tenths as f64 / 10.0);
+    result.passed =
+        result.lines_covered * 100 >= cfg.coverage_min_percent as u64 * result.lines_total;
+    write_coverage_summary(&item.test_log, &result)?;
+    Ok(result)
+}
+
+// Appends the measured coverage and the uncovered lines to the test log, so a fix round's prompt
+// (the log's tail) says what is missing.
+fn write_coverage_summary(log_path: &Path, result: &CoverageResult) -> LooprResult<()> {
+    let mut summary = format!(
+        "loopr: coverage of changed files: {} ({} of {} lines), minimum {}%\n",
+        coverage_percent(result),
+        result.lines_covered,
+        result.lines_total,
+        result.min_percent
+    );
+    for file in result
+        .files
+        .iter()
+        .filter(|file| !file.uncovered_lines.is_empty())
+    {
+        let lines: Vec<String> = file
+            .uncovered_lines
+            .iter()
+            .map(|number| number.to_string())
+            .collect();
+        summary.push_str(&format!(
+            "loopr: {}: {} of {} lines covered; uncovered lines {}\n",
+            file.path,
+            file.lines_covered,
+            file.lines_total,
+            lines.join(", ")
+        ));
+    }
+    let mut log = std::fs::OpenOptions::new()
+        .append(true)
+        .open(log_path)
+        .map_err(|err| LooprError::new(format!("open {}: {}", log_path.display(), err)))?;
+    std::io::Write::write_all(&mut log, summary.as_bytes())
+        .map_err(|err| LooprError::new(format!("write {}: {}", log_path.display(), err)))
+}
+
+// Puts an item that ran but was never merged (the loop stopped earlier in its batch) back in line.
+fn release_item(
+    lp: &PerTaskLoop,
+    work_status: &mut WorkStatusFile,
+    item: PreparedItem,
+) -> LooprResult<()> {
+    if let Some(workspace) = &item.workspace {
+        remove_workspace(lp.root, workspace)?;
+    }
+    if let Some(status) = work_status.items.get_mut(&item.key) {
+        status.state = WorkItemState::NotStarted;
+        status.attempts = status.attempts.saturating_sub(1);
+        status.last_summary = Some("not merged: loop stopped".to_string());
+    }
+    capture_released_snapshot(lp.root, lp.run_id, item.iteration, &item.key)?;
+    Ok(())
+}
+
+fn finish_item(
+    lp: &PerTaskLoop,
+    state: &mut LoopState,
+    report: &mut LoopReport,
+    work_status: &mut WorkStatusFile,
+    item: PreparedItem,
+    mut item_run: ItemRun,
+) -> LooprResult<ItemFlow> {
+    let (opts, cfg, status_path, work_status_path) =
+        (lp.opts, lp.cfg, lp.status_path, lp.work_status_path);
+    if let Some(workspace) = &item.workspace {
+        let merged = merge_workspace(lp.root, workspace);
+        remove_workspace(lp.root, workspace)?;
+        if let Err(err) = merged
+            && item_run.run_error.is_none()
+        {
+            item_run.fail(LooprError::new(format!("merge workspace: {}", err)));
+        }
+    }
+    let PreparedItem {
+        selection,
+        key: item_key,
+        iteration,
+        ..
+    } = item;
+    let ItemRun {
+        run,
+        run_error,
+        status,
+        status_found,
+        test_run,
+        gates,
+        coverage,
+        sessions,
+    } = item_run;
+    report.last_session = Some(run.session);
+    state.iteration = iteration;
+    record_item_sessions(work_status, &item_key, &sessions);
+    if !gates.is_empty()
+        && let Some(entry) = work_status.items.get_mut(&item_key)
+    {
+        entry.gates = gates.clone();
+    }
+    if let Some(coverage) = &coverage
+        && let Some(entry) = work_status.items.get_mut(&item_key)
+    {
+        entry.coverage = Some(coverage.clone());
+    }
+    let failed_gate = gates.iter().find(|gate| gate.required && !gate.run.passed);
+    let fix_rounds = sessions.iter().filter(|session| session.round > 0).count();
+    if let Some(progress) = &opts.progress {
+        for session in sessions.iter().filter(|session| session.round > 0) {
+            let outcome = match (session.tests_passed, session.gates_passed) {
+                (Some(true), Some(false)) => "gates failed".to_string(),
+                (Some(true), _) if session.coverage_passed == Some(false) => {
+                    "coverage below minimum".to_string()
+                }
+                (Some(true), _) => "tests passed".to_string(),
+                (Some(false), _) => "tests failed".to_string(),
+                (None, _) => format!("status {}", session.status),
+            };
+            progress(LoopEvent {
+                iteration,
+                status: LOOP_EVENT_FIX.to_string(),
+                details: format!(
+                    "{} round {}/{}: {}",
+                    item_key, session.round, cfg.max_fix_rounds, outcome
+                ),
+            });
+        }
+    }
+    if let Some(progress) = &opts.progress {
+        for gate in gates.iter().filter(|gate| !gate.run.passed) {
+            progress(LoopEvent {
+                iteration,
+                status: LOOP_EVENT_GATE.to_string(),
+                details: format!(
+                    "{} ({}) failed ({}, log {})",
+                    gate.name,
+                    if gate.required {
+                        "required"
+                    } else {
+                        "advisory"
+                    },
+                    gate_outcome(&gate.run),
+                    gate.run.log_path
+                ),
+            });
+        }
+    }
+    if let Some(progress) = &opts.progress
+        && let Some(coverage) = coverage.as_ref().filter(|coverage| !coverage.passed)
+    {
+        progress(LoopEvent {
+            iteration,
+            status: LOOP_EVENT_COVERAGE.to_string(),
+            details: format!(
+                "{} ({}): {} (log {})",
+                item_key,
+                if coverage.required {
+                    "required"
+                } else {
+                    "advisory"
+                },
+                coverage_outcome(coverage),
+                coverage.log_path
+            ),
+        });
+    }
+    capture_snapshot(lp.root, lp.run_id, state.iteration, Some(&item_key))?;
+
+    if status_found {
+        state.missing_status_count = 0;
+    } else {
+        state.missing_status_count += 1;
+        if state.missing_status_count >= cfg.max_missing_status {
             mark_item_failed(
-                &mut work_status,
+                work_status,
                 &item_key,
                 WorkItemState::Error,
-                &err.message,
+                "missing LOOPR_STATUS block",
                 &now_rfc3339()?,
             );
-            write_work_status(&work_status_path, &work_status)?;
+            write_work_status(work_status_path, work_status)?;
+            report.exit_reason = "missing_status".to_string();
             write_loop_status(
-                &status_path,
+                status_path,
                 LoopStatusPayload {
                     state: "error".to_string(),
                     iteration: state.iteration,
                     updated_at: now_rfc3339()?,
-                    exit_reason: Some("error".to_string()),
-                    last_summary: Some(err.message.clone()),
-                    last_error: Some(err.message.clone()),
+                    exit_reason: Some(report.exit_reason.clone()),
+                    last_summary: None,
+                    last_error: Some("missing LOOPR_STATUS block".to_string()),
                 },
             )?;
             if let Some(progress) = &opts.progress {
                 progress(LoopEvent {
                     iteration: state.iteration,
                     status: LOOP_EVENT_ERROR.to_string(),
-                    details: err.message.clone(),
+                    details: "missing LOOPR_STATUS block".to_string(),
                 });
             }
-            break;
+            return Ok(ItemFlow::Stop);
         }
+    }
 
-        if status.status == "BLOCKED" {
-            mark_item_failed(
-                &mut work_status,
+    if let Some(err) = run_error {
+        if retry_item(
+            work_status,
+            &item_key,
+            &err.message,
+            cfg.max_attempts_per_item,
+            &now_rfc3339()?,
+        ) {
+            report_retry(
+                opts,
+                status_path,
+                work_status_path,
+                work_status,
+                state.iteration,
                 &item_key,
-                WorkItemState::Blocked,
-                if status.summary.is_empty() {
-                    "blocked by status"
-                } else {
-                    status.summary.as_str()
-                },
+                &err.message,
+            )?;
+            return Ok(ItemFlow::Continue);
+        }
+        mark_item_failed(
+            work_status,
+            &item_key,
+            WorkItemState::Error,
+            &err.message,
+            &now_rfc3339()?,
+        );
+        write_work_status(work_status_path, work_status)?;
+        if opts.keep_going {
+            report_skip(opts, status_path, state.iteration, &item_key, &err.message)?;
+            return Ok(ItemFlow::Continue);
+        }
+        write_loop_status(
+            status_path,
+            LoopStatusPayload {
+                state: "error".to_string(),
+                iteration: state.iteration,
+                updated_at: now_rfc3339()?,
+                exit_reason: Some("error".to_string()),
+                last_summary: Some(err.message.clone()),
+                last_error: Some(err.message.clone()),
+            },
+        )?;
+        if let Some(progress) = &opts.progress {
+            progress(LoopEvent {
+                iteration: state.iteration,
+                status: LOOP_EVENT_ERROR.to_string(),
+                details: err.message.clone(),
+            });
+        }
+        return Ok(ItemFlow::Stop);
+    }
+
+    if status.status == "BLOCKED" {
+        mark_item_failed(
+            work_status,
+            &item_key,
+            WorkItemState::Blocked,
+            if status.summary.is_empty() {
+                "blocked by status"
+            } else {
+                status.summary.as_str()
+            },
+            &now_rfc3339()?,
+        );
+        write_work_status(work_status_path, work_status)?;
+        if opts.keep_going {
+            report_skip(opts, status_path, state.iteration, &item_key, "blocked")?;
+            return Ok(ItemFlow::Continue);
+        }
+        report.exit_reason = "blocked".to_string();
+        write_loop_status(
+            status_path,
+            LoopStatusPayload {
+                state: "blocked".to_string(),
+                iteration: state.iteration,
+                updated_at: now_rfc3339()?,
+                exit_reason: Some(report.exit_reason.clone()),
+                last_summary: Some(status.summary.clone()),
+                last_error: None,
+            },
+        )?;
+        if let Some(progress) = &opts.progress {
+            progress(LoopEvent {
+                iteration: state.iteration,
+                status: LOOP_EVENT_EXIT.to_string(),
+                details: "blocked".to_string(),
+            });
+        }
+        return Ok(ItemFlow::Stop);
+    }
+
+    if status.status == "ERROR" {
+        let message = if status.summary.is_empty() {
+            "error status"
+        } else {
+            status.summary.as_str()
+        };
+        if retry_item(
+            work_status,
+            &item_key,
+            message,
+            cfg.max_attempts_per_item,
+            &now_rfc3339()?,
+        ) {
+            report_retry(
+                opts,
+                status_path,
+                work_status_path,
+                work_status,
+                state.iteration,
+                &item_key,
+                message,
+            )?;
+            return Ok(ItemFlow::Continue);
+        }
+        mark_item_failed(
+            work_status,
+            &item_key,
+            WorkItemState::Error,
+            message,
+            &now_rfc3339()?,
+        );
+        write_work_status(work_status_path, work_status)?;
+        if opts.keep_going {
+            report_skip(opts, status_path, state.iteration, &item_key, message)?;
+            return Ok(ItemFlow::Continue);
+        }
+        report.exit_reason = "error".to_string();
+        write_loop_status(
+            status_path,
+            LoopStatusPayload {
+                state: "error".to_string(),
+                iteration: state.iteration,
+                updated_at: now_rfc3339()?,
+                exit_reason: Some(report.exit_reason.clone()),
+                last_summary: Some(status.summary.clone()),
+                last_error: None,
+            },
+        )?;
+        if let Some(progress) = &opts.progress {
+            progress(LoopEvent {
+                iteration: state.iteration,
+                status: LOOP_EVENT_ERROR.to_string(),
+                details: "error".to_string(),
+            });
+        }
+        return Ok(ItemFlow::Stop);
+    }
+
+    let test_run = test_run.unwrap_or_else(|| Err(LooprError::new("test command did not run")));
+    match selection {
+        WorkItemSelection::Test { test, task, .. } => {
+            let test_outcome = match test_run {
+                Ok(value) => value,
+                Err(err) => {
+                    return fail_attempt(
+                        lp,
+                        state,
+                        report,
+                        work_status,
+                        &item_key,
+                        &err.message,
+                        "error",
+                        "test command failed to run",
+                    );
+                }
+            };
+
+            update_test_result(
+                work_status,
+                &item_key,
+                test_outcome.clone(),
                 &now_rfc3339()?,
             );
-            write_work_status(&work_status_path, &work_status)?;
-            report.exit_reason = "blocked".to_string();
-            write_loop_status(
-                &status_path,
-                LoopStatusPayload {
-                    state: "blocked".to_string(),
-                    iteration: state.iteration,
-                    updated_at: now_rfc3339()?,
-                    exit_reason: Some(report.exit_reason.clone()),
-                    last_summary: Some(status.summary.clone()),
-                    last_error: None,
-                },
-            )?;
-            if let Some(progress) = &opts.progress {
-                progress(LoopEvent {
-                    iteration: state.iteration,
-                    status: LOOP_EVENT_EXIT.to_string(),
-                    details: "blocked".to_string(),
-                });
+
+            // A hung run proves nothing about the new tests, so it fails the attempt instead of
+            // counting as the expected red run.
+            if test_outcome.timed_out {
+                let message = format!(
+                    "tests timed out after {}s (log {})",
+                    test_outcome.duration_ms / 1000,
+                    test_outcome.log_path
+                );
+                return fail_attempt(
+                    lp,
+                    state,
+                    report,
+                    work_status,
+                    &item_key,
+                    &message,
+                    "tests_timed_out",
+                    "tests timed out",
+                );
+            }
+            if !test_outcome.missing_cases.is_empty() {
+                let message = format!(
+                    "declared test cases did not run: {} (log {})",
+                    test_outcome.missing_cases.join(", "),
+                    test_outcome.log_path
+                );
+                return fail_attempt(
+                    lp,
+                    state,
+                    report,
+                    work_status,
+                    &item_key,
+                    &message,
+                    "test_cases_missing",
+                    "declared test cases did not run",
+                );
+            }
+            if test_outcome.stability == Some(TestStability::Flaky) {
+                return flag_flaky(lp, state, report, work_status, &item_key, &test_outcome);
+            }
+
+            let pbt = work_status
+                .items
+                .get(&item_key)
+                .map(|item| item.pbt)
+                .unwrap_or(false);
+            // With structured results, every declared case must go red first, not just one of them.
+            let passing = if cfg.test_result_format.is_some() {
+                passing_cases(&test_outcome, &test.cases)
+            } else {
+                Vec::new()
+            };
+            let passed_first = if pbt && test_outcome.passed {
+                Some(("pbt_passed_first", "PBT tests passed on first run".to_string()))
+            } else if !passing.is_empty() {
+                Some((
+                    "tests_passed_first",
+                    format!(
+                        "declared test cases passed on first run: {}",
+                        passing.join(", ")
+                    ),
+                ))
+            } else {
+                None
+            };
+            if let Some((reason, summary)) = passed_first {
+                mark_item_failed(
+                    work_status,
+                    &item_key,
+                    WorkItemState::Blocked,
+                    &format!("{}; must fail first", summary),
+                    &now_rfc3339()?,
+                );
+                write_work_status(work_status_path, work_status)?;
+                if opts.keep_going {
+                    report_skip(opts, status_path, state.iteration, &item_key, reason)?;
+                    return Ok(ItemFlow::Continue);
+                }
+                report.exit_reason = reason.to_string();
+                write_loop_status(
+                    status_path,
+                    LoopStatusPayload {
+                        state: "blocked".to_string(),
+                        iteration: state.iteration,
+                        updated_at: now_rfc3339()?,
+                        exit_reason: Some(report.exit_reason.clone()),
+                        last_summary: Some(summary),
+                        last_error: None,
+                    },
+                )?;
+                if let Some(progress) = &opts.progress {
+                    progress(LoopEvent {
+                        iteration: state.iteration,
+                        status: LOOP_EVENT_EXIT.to_string(),
+                        details: reason.to_string(),
+                    });
+                }
+                return Ok(ItemFlow::Stop);
             }
-            break;
-        }
 
-        if status.status == "ERROR" {
-            mark_item_failed(
-                &mut work_status,
+            if let Some(gate) = failed_gate {
+                let message = format!(
+                    "gate {} failed after the tests were written ({}, log {})",
+                    gate.name,
+                    gate_outcome(&gate.run),
+                    gate.run.log_path
+                );
+                return fail_attempt(
+                    lp,
+                    state,
+                    report,
+                    work_status,
+                    &item_key,
+                    &message,
+                    "gate_failed",
+                    &format!("gate {} failed", gate.name),
+                );
+            }
+
+            mark_test_complete(
+                work_status,
                 &item_key,
-                WorkItemState::Error,
-                if status.summary.is_empty() {
-                    "error status"
-                } else {
-                    status.summary.as_str()
-                },
+                &status.summary,
                 &now_rfc3339()?,
+                !pbt || !test_outcome.passed,
             );
-            write_work_status(&work_status_path, &work_status)?;
-            report.exit_reason = "error".to_string();
+            write_work_status(work_status_path, work_status)?;
+
             write_loop_status(
-                &status_path,
+                status_path,
                 LoopStatusPayload {
-                    state: "error".to_string(),
+                    state: "running".to_string(),
                     iteration: state.iteration,
                     updated_at: now_rfc3339()?,
-                    exit_reason: Some(report.exit_reason.clone()),
-                    last_summary: Some(status.summary.clone()),
-                    last_error: None,
+                    exit_reason: None,
+                    last_summary: Some(format!(
+                        "tests written for {} (task {})",
+                        test.key, task.key
+                    )),
+                    last_error: if test_outcome.passed {
+                        None
+                    } else {
+                        Some(format!(
+                            "tests failing as expected (exit {})",
+                            test_outcome.exit_code
+                        ))
+                    },
                 },
             )?;
-            if let Some(progress) = &opts.progress {
-                progress(LoopEvent {
-                    iteration: state.iteration,
-                    status: LOOP_EVENT_ERROR.to_string(),
-                    details: "error".to_string(),
-                });
-            }
-            break;
         }
-
-        match selection {
-            WorkItemSelection::Test { test, task, .. } => {
-                let test_outcome = match run_test_command(&root, &cfg.test_command, "tests") {
-                    Ok(value) => value,
-                    Err(err) => {
-                        mark_item_failed(
-                            &mut work_status,
-                            &item_key,
-                            WorkItemState::Error,
-                            &err.message,
-                            &now_rfc3339()?,
-                        );
-                        write_work_status(&work_status_path, &work_status)?;
-                        report.exit_reason = "error".to_string();
-                        write_loop_status(
-                            &status_path,
-                            LoopStatusPayload {
-                                state: "error".to_string(),
-                                iteration: state.iteration,
-                                updated_at: now_rfc3339()?,
-                                exit_reason: Some(report.exit_reason.clone()),
-                                last_summary: Some(err.message.clone()),
-                                last_error: Some(err.message.clone()),
-                            },
-                        )?;
-                        if let Some(progress) = &opts.progress {
-                            progress(LoopEvent {
-                                iteration: state.iteration,
-                                status: LOOP_EVENT_ERROR.to_string(),
-                                details: err.message.clone(),
-                            });
-                        }
-                        break;
-                    }
-                };
-
-                update_test_result(&mut work_status, &item_key, test_outcome.clone(), &now_rfc3339()?);
-
-                let pbt = work_status
-                    .items
-                    .get(&item_key)
-                    .map(|item| item.pbt)
-                    .unwrap_or(false);
-                if pbt && test_outcome.passed {
-                    mark_item_failed(
-                        &mut work_status,
+        WorkItemSelection::Task { task, tests } => {
+            let test_outcome = match test_run {
+                Ok(value) => value,
+                Err(err) => {
+                    return fail_attempt(
+                        lp,
+                        state,
+                        report,
+                        work_status,
                         &item_key,
-                        WorkItemState::Blocked,
-                        "PBT tests passed on first run; must fail first",
-                        &now_rfc3339()?,
+                        &err.message,
+                        "error",
+                        "test command failed to run",
                     );
-                    write_work_status(&work_status_path, &work_status)?;
-                    report.exit_reason = "pbt_passed_first".to_string();
-                    write_loop_status(
-                        &status_path,
-                        LoopStatusPayload {
-                            state: "blocked".to_string(),
-                            iteration: state.iteration,
-                            updated_at: now_rfc3339()?,
-                            exit_reason: Some(report.exit_reason.clone()),
-                            last_summary: Some("PBT tests passed on first run".to_string()),
-                            last_error: None,
-                        },
-                    )?;
-                    if let Some(progress) = &opts.progress {
-                        progress(LoopEvent {
-                            iteration: state.iteration,
-                            status: LOOP_EVENT_EXIT.to_string(),
-                            details: "pbt_passed_first".to_string(),
-                        });
-                    }
-                    break;
                 }
+            };
 
-                mark_test_complete(
-                    &mut work_status,
-                    &item_key,
-                    &status.summary,
-                    &now_rfc3339()?,
-                    !pbt || !test_outcome.passed,
-                );
-                write_work_status(&work_status_path, &work_status)?;
+            update_test_result(
+                work_status,
+                &item_key,
+                test_outcome.clone(),
+                &now_rfc3339()?,
+            );
 
-                write_loop_status(
-                    &status_path,
-                    LoopStatusPayload {
-                        state: "running".to_string(),
-                        iteration: state.iteration,
-                        updated_at: now_rfc3339()?,
-                        exit_reason: None,
-                        last_summary: Some(format!(
-                            "tests written for {} (task {})",
-                            test.key, task.key
-                        )),
-                        last_error: if test_outcome.passed {
-                            None
-                        } else {
-                            Some(format!(
-                                "tests failing as expected (exit {})",
-                                test_outcome.exit_code
-                            ))
-                        },
-                    },
-                )?;
+            if test_outcome.stability == Some(TestStability::Flaky) {
+                return flag_flaky(lp, state, report, work_status, &item_key, &test_outcome);
             }
-            WorkItemSelection::Task { task, tests } => {
-                let test_outcome = match run_test_command(&root, &cfg.test_command, "validate") {
-                    Ok(value) => value,
-                    Err(err) => {
-                        mark_item_failed(
-                            &mut work_status,
-                            &item_key,
-                            WorkItemState::Error,
-                            &err.message,
-                            &now_rfc3339()?,
-                        );
-                        write_work_status(&work_status_path, &work_status)?;
-                        report.exit_reason = "error".to_string();
-                        write_loop_status(
-                            &status_path,
-                            LoopStatusPayload {
-                                state: "error".to_string(),
-                                iteration: state.iteration,
-                                updated_at: now_rfc3339()?,
-                                exit_reason: Some(report.exit_reason.clone()),
-                                last_summary: Some(err.message.clone()),
-                                last_error: Some(err.message.clone()),
-                            },
-                        )?;
-                        if let Some(progress) = &opts.progress {
-                            progress(LoopEvent {
-                                iteration: state.iteration,
-                                status: LOOP_EVENT_ERROR.to_string(),
-                                details: err.message.clone(),
-                            });
-                        }
-                        break;
+            if !test_outcome.passed {
+                // A hung run is reported apart from a red one.
+                let (outcome, result, exit_reason) = if test_outcome.timed_out {
+                    (
+                        "timed out",
+                        format!("after {}s", test_outcome.duration_ms / 1000),
+                        "tests_timed_out",
+                    )
+                } else {
+                    let mut result = format!("exit {}", test_outcome.exit_code);
+                    if !test_outcome.missing_cases.is_empty() {
+                        result.push_str(&format!(
+                            ", cases did not run: {}",
+                            test_outcome.missing_cases.join(", ")
+                        ));
                     }
+                    ("failed", result, "tests_failed")
                 };
+                let message = if fix_rounds == 0 {
+                    format!(
+                        "tests {} after implementation ({}, log {})",
+                        outcome, result, test_outcome.log_path
+                    )
+                } else {
+                    format!(
+                        "tests {} after implementation and {} fix round(s) ({}, log {})",
+                        outcome, fix_rounds, result, test_outcome.log_path
+                    )
+                };
+                return fail_attempt(
+                    lp,
+                    state,
+                    report,
+                    work_status,
+                    &item_key,
+                    &message,
+                    exit_reason,
+                    &format!("tests {} after implementation", outcome),
+                );
+            }
 
-                update_test_result(&mut work_status, &item_key, test_outcome.clone(), &now_rfc3339()?);
-
-                if !test_outcome.passed {
-                    mark_item_failed(
-                        &mut work_status,
-                        &item_key,
-                        WorkItemState::Error,
-                        "tests failed after implementation",
-                        &now_rfc3339()?,
-                    );
-                    write_work_status(&work_status_path, &work_status)?;
-                    report.exit_reason = "tests_failed".to_string();
-                    write_loop_status(
-                        &status_path,
-                        LoopStatusPayload {
-                            state: "error".to_string(),
-                            iteration: state.iteration,
-                            updated_at: now_rfc3339()?,
-                            exit_reason: Some(report.exit_reason.clone()),
-                            last_summary: Some("tests failed after implementation".to_string()),
-                            last_error: Some(format!(
-                                "tests failed (exit {})",
-                                test_outcome.exit_code
-                            )),
-                        },
-                    )?;
-                    if let Some(progress) = &opts.progress {
-                        progress(LoopEvent {
-                            iteration: state.iteration,
-                            status: LOOP_EVENT_ERROR.to_string(),
-                            details: "tests_failed".to_string(),
-                        });
-                    }
-                    break;
-                }
+            // A required gate blocks completion just like failing tests.
+            if let Some(gate) = failed_gate {
+                let message = if fix_rounds == 0 {
+                    format!(
+                        "gate {} failed after implementation ({}, log {})",
+                        gate.name,
+                        gate_outcome(&gate.run),
+                        gate.run.log_path
+                    )
+                } else {
+                    format!(
+                        "gate {} failed after implementation and {} fix round(s) ({}, log {})",
+                        gate.name,
+                        fix_rounds,
+                        gate_outcome(&gate.run),
+                        gate.run.log_path
+                    )
+                };
+                return fail_attempt(
+                    lp,
+                    state,
+                    report,
+                    work_status,
+                    &item_key,
+                    &message,
+                    "gate_failed",
+                    &format!("gate {} failed after implementation", gate.name),
+                );
+            }
 
-                mark_task_complete(&mut work_status, &task.key, &status.summary, &now_rfc3339()?);
-                for test in &tests {
-                    mark_test_validated(&mut work_status, &test.key, &now_rfc3339()?);
-                }
-                write_work_status(&work_status_path, &work_status)?;
+            // Below COVERAGE_MIN_PERCENT only blocks completion when COVERAGE_REQUIRED is set;
+            // otherwise the coverage event above is the warning.
+            if let Some(coverage) = coverage
+                .as_ref()
+                .filter(|value| value.required && !value.passed)
+            {
+                let message = if fix_rounds == 0 {
+                    format!(
+                        "coverage check failed after implementation ({}, log {})",
+                        coverage_outcome(coverage),
+                        coverage.log_path
+                    )
+                } else {
+                    format!(
+                        "coverage check failed after implementation and {} fix round(s) ({}, log {})",
+                        fix_rounds,
+                        coverage_outcome(coverage),
+                        coverage.log_path
+                    )
+                };
+                return fail_attempt(
+                    lp,
+                    state,
+                    report,
+                    work_status,
+                    &item_key,
+                    &message,
+                    "coverage_below_threshold",
+                    "coverage check failed after implementation",
+                );
+            }
 
-                write_loop_status(
-                    &status_path,
-                    LoopStatusPayload {
-                        state: "running".to_string(),
-                        iteration: state.iteration,
-                        updated_at: now_rfc3339()?,
-                        exit_reason: None,
-                        last_summary: Some(format!("implemented {}", task.key)),
-                        last_error: None,
-                    },
-                )?;
+            mark_task_complete(work_status, &task.key, &status.summary, &now_rfc3339()?);
+            for test in &tests {
+                mark_test_validated(work_status, &test.key, &now_rfc3339()?);
             }
-        }
+            write_work_status(work_status_path, work_status)?;
 
-        if let Some(progress) = &opts.progress {
-            progress(LoopEvent {
-                iteration: state.iteration,
-                status: LOOP_EVENT_DONE.to_string(),
-                details: String::new(),
-            });
+            write_loop_status(
+                status_path,
+                LoopStatusPayload {
+                    state: "running".to_string(),
+                    iteration: state.iteration,
+                    updated_at: now_rfc3339()?,
+                    exit_reason: None,
+                    last_summary: Some(format!("implemented {}", task.key)),
+                    last_error: None,
+                },
+            )?;
         }
     }
 
-    report.iterations = state.iteration;
-    Ok(report)
+    if let Some(progress) = &opts.progress {
+        progress(LoopEvent {
+            iteration: state.iteration,
+            status: LOOP_EVENT_DONE.to_string(),
+            details: String::new(),
+        });
+    }
+    Ok(ItemFlow::Continue)
 }
 
 #[derive(Clone)]
@@ -800,6 +1832,89 @@ enum WorkItemSelection {
 }
 
 impl WorkItemSelection {
+    // The item's own `command` wins. A task without one runs its tests' commands when every test
+    // declares one; anything else falls back to TEST_COMMAND. Each command is paired with its
+    // timeout: the declaring item's `timeout_minutes`, then the task's, then TEST_TIMEOUT_MINUTES.
+    fn test_commands(&self, default: &str, default_timeout: i64) -> Vec<(String, i64)> {
+        let own = |command: &Option<String>| {
+            command
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let timeout = |minutes: Option<i64>| minutes.filter(|value| *value > 0);
+        let (commands, item_timeout) = match self {
+            WorkItemSelection::Test { test, .. } => {
+                let item_timeout = timeout(test.timeout_minutes).unwrap_or(default_timeout);
+                let commands = own(&test.command)
+                    .map(|command| (command, item_timeout))
+                    .into_iter()
+                    .collect();
+                (commands, item_timeout)
+            }
+            WorkItemSelection::Task { task, tests } => {
+                let item_timeout = timeout(task.timeout_minutes).unwrap_or(default_timeout);
+                match own(&task.command) {
+                    Some(command) => (vec![(command, item_timeout)], item_timeout),
+                    None => {
+                        let mut commands: Vec<(String, i64)> = Vec::new();
+                        for test in tests {
+                            match own(&test.command) {
+                                Some(command)
+                                    if commands.iter().any(|(seen, _)| *seen == command) => {}
+                                Some(command) => commands.push((
+                                    command,
+                                    timeout(test.timeout_minutes).unwrap_or(item_timeout),
+                                )),
+                                None => return vec![(default.to_string(), item_timeout)],
+                            }
+                        }
+                        (commands, item_timeout)
+                    }
+                }
+            }
+        };
+        if commands.is_empty() {
+            vec![(default.to_string(), item_timeout)]
+        } else {
+            commands
+        }
+    }
+
+    fn test_env(&self) -> Vec<(&'static str, String)> {
+        let files: Vec<&str> = match self {
+            WorkItemSelection::Test { test, .. } => vec![test.file.as_str()],
+            WorkItemSelection::Task { tests, .. } => {
+                tests.iter().map(|test| test.file.as_str()).collect()
+            }
+        };
+        vec![
+            ("LOOPR_ITEM_KEY", self.key().to_string()),
+            ("LOOPR_ITEM_TYPE", self.item_type().to_string()),
+            ("LOOPR_PHASE", self.test_phase().to_string()),
+            ("LOOPR_TEST_FILES", files.join("\n")),
+        ]
+    }
+
+    // `cases` declared by the test, or by all of a task's tests.
+    fn test_cases(&self) -> Vec<String> {
+        match self {
+            WorkItemSelection::Test { test, .. } => test.cases.clone(),
+            WorkItemSelection::Task { tests, .. } => tests
+                .iter()
+                .flat_map(|test| test.cases.iter().cloned())
+                .collect(),
+        }
+    }
+
+    fn test_phase(&self) -> &'static str {
+        match self {
+            WorkItemSelection::Test { .. } => "tests",
+            WorkItemSelection::Task { .. } => "validate",
+        }
+    }
+
     fn key(&self) -> &str {
         match self {
             WorkItemSelection::Test { test, .. } => &test.key,
@@ -1041,6 +2156,110 @@ fn blocked_reason(status: &WorkStatusFile) -> Option<String> {
     None
 }
 
+// Blocked/errored items, then every unfinished item that depends on one of them (directly or
+// through another skipped item). Tasks wait on their tests; tests wait on their task's dependencies
+// and are only validated once their task completes.
+fn skipped_items(
+    tasks: &[TaskSpec],
+    tests: &[TestSpec],
+    task_by_id: &HashMap<i64, TaskSpec>,
+    test_by_id: &HashMap<i64, TestSpec>,
+    status: &WorkStatusFile,
+) -> Vec<SkippedItem> {
+    let mut skipped = Vec::new();
+    let mut keys: HashSet<String> = HashSet::new();
+    for key in tasks
+        .iter()
+        .map(|task| &task.key)
+        .chain(tests.iter().map(|test| &test.key))
+    {
+        let item = match status.items.get(key) {
+            Some(value) => value,
+            None => continue,
+        };
+        let state = match item.state {
+            WorkItemState::Blocked => "blocked",
+            WorkItemState::Error => "error",
+            _ => continue,
+        };
+        let reason = match &item.last_error {
+            Some(message) => format!("{}: {}", state, message),
+            None => state.to_string(),
+        };
+        keys.insert(key.clone());
+        skipped.push(SkippedItem {
+            key: key.clone(),
+            reason,
+        });
+    }
+    if skipped.is_empty() {
+        return skipped;
+    }
+
+    let task_keys = |ids: &[i64]| -> Vec<String> {
+        ids.iter()
+            .filter_map(|id| task_by_id.get(id))
+            .map(|task| task.key.clone())
+            .collect()
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let mut candidates = Vec::new();
+        for task in tasks {
+            let done = status
+                .items
+                .get(&task.key)
+                .map(|item| item.state == WorkItemState::Complete)
+                .unwrap_or(false);
+            if done || keys.contains(&task.key) {
+                continue;
+            }
+            let mut deps = task_keys(&task.depends_on);
+            deps.extend(
+                tests
+                    .iter()
+                    .filter(|test| test.task_id == task.id)
+                    .map(|test| test.key.clone()),
+            );
+            candidates.push((task.key.clone(), deps));
+        }
+        for test in tests {
+            let done = status
+                .items
+                .get(&test.key)
+                .map(|item| item.tests_validated)
+                .unwrap_or(false);
+            if done || keys.contains(&test.key) {
+                continue;
+            }
+            let mut deps = Vec::new();
+            if let Some(task) = task_by_id.get(&test.task_id) {
+                deps.push(task.key.clone());
+                deps.extend(task_keys(&task.depends_on));
+            }
+            deps.extend(
+                test.depends_on
+                    .iter()
+                    .filter_map(|id| test_by_id.get(id))
+                    .map(|dep| dep.key.clone()),
+            );
+            candidates.push((test.key.clone(), deps));
+        }
+        for (key, deps) in candidates {
+            if let Some(dep) = deps.iter().find(|dep| keys.contains(*dep)) {
+                skipped.push(SkippedItem {
+                    key: key.clone(),
+                    reason: format!("depends on {}", dep),
+                });
+                keys.insert(key);
+                changed = true;
+            }
+        }
+    }
+    skipped
+}
+
 fn all_tasks_complete(tasks: &[TaskSpec], status: &WorkStatusFile) -> bool {
     tasks.iter().all(|task| {
         status
@@ -1087,6 +2306,211 @@ fn mark_item_failed(
     status.updated_at = now.to_string();
 }
 
+// Sends an errored item back to not_started while it has attempts left. The failure stays in
+// last_error so the next prompt for the item can include it.
+fn retry_item(
+    status: &mut WorkStatusFile,
+    key: &str,
+    message: &str,
+    max_attempts: i64,
+    now: &str,
+) -> bool {
+    let item = match status.items.get_mut(key) {
+        Some(value) => value,
+        None => return false,
+    };
+    if i64::from(item.attempts) >= max_attempts {
+        return false;
+    }
+    item.state = WorkItemState::NotStarted;
+    item.last_updated = now.to_string();
+    item.last_error = Some(message.to_string());
+    status.updated_at = now.to_string();
+    true
+}
+
+// A failed attempt is retried while attempts remain. After that the item errors and the loop
+// either skips it (--keep-going) or stops with `exit_reason`.
+#[allow(clippy::too_many_arguments)]
+fn fail_attempt(
+    lp: &PerTaskLoop,
+    state: &LoopState,
+    report: &mut LoopReport,
+    work_status: &mut WorkStatusFile,
+    key: &str,
+    message: &str,
+    exit_reason: &str,
+    summary: &str,
+) -> LooprResult<ItemFlow> {
+    let (opts, status_path, work_status_path) = (lp.opts, lp.status_path, lp.work_status_path);
+    if retry_item(
+        work_status,
+        key,
+        message,
+        lp.cfg.max_attempts_per_item,
+        &now_rfc3339()?,
+    ) {
+        report_retry(
+            opts,
+            status_path,
+            work_status_path,
+            work_status,
+            state.iteration,
+            key,
+            message,
+        )?;
+        return Ok(ItemFlow::Continue);
+    }
+    mark_item_failed(
+        work_status,
+        key,
+        WorkItemState::Error,
+        message,
+        &now_rfc3339()?,
+    );
+    write_work_status(work_status_path, work_status)?;
+    if opts.keep_going {
+        report_skip(opts, status_path, state.iteration, key, message)?;
+        return Ok(ItemFlow::Continue);
+    }
+    report.exit_reason = exit_reason.to_string();
+    write_loop_status(
+        status_path,
+        LoopStatusPayload {
+            state: "error".to_string(),
+            iteration: state.iteration,
+            updated_at: now_rfc3339()?,
+            exit_reason: Some(report.exit_reason.clone()),
+            last_summary: Some(summary.to_string()),
+            last_error: Some(message.to_string()),
+        },
+    )?;
+    if let Some(progress) = &opts.progress {
+        progress(LoopEvent {
+            iteration: state.iteration,
+            status: LOOP_EVENT_ERROR.to_string(),
+            details: exit_reason.to_string(),
+        });
+    }
+    Ok(ItemFlow::Stop)
+}
+
+// A flaky result is neither accepted nor failed: the item is blocked so someone can look at the
+// suite, and the loop skips it (--keep-going) or stops with `flaky_tests`.
+fn flag_flaky(
+    lp: &PerTaskLoop,
+    state: &LoopState,
+    report: &mut LoopReport,
+    work_status: &mut WorkStatusFile,
+    key: &str,
+    outcome: &TestRunResult,
+) -> LooprResult<ItemFlow> {
+    let (opts, status_path) = (lp.opts, lp.status_path);
+    let passes = usize::from(outcome.passed) + outcome.reruns.iter().filter(|pass| **pass).count();
+    let message = format!(
+        "flaky tests: passed {} of {} runs (log {})",
+        passes,
+        outcome.reruns.len() + 1,
+        outcome.log_path
+    );
+    mark_item_failed(
+        work_status,
+        key,
+        WorkItemState::Blocked,
+        &message,
+        &now_rfc3339()?,
+    );
+    write_work_status(lp.work_status_path, work_status)?;
+    if opts.keep_going {
+        report_skip(opts, status_path, state.iteration, key, "flaky_tests")?;
+        return Ok(ItemFlow::Continue);
+    }
+    report.exit_reason = "flaky_tests".to_string();
+    write_loop_status(
+        status_path,
+        LoopStatusPayload {
+            state: "blocked".to_string(),
+            iteration: state.iteration,
+            updated_at: now_rfc3339()?,
+            exit_reason: Some(report.exit_reason.clone()),
+            last_summary: Some(message.clone()),
+            last_error: None,
+        },
+    )?;
+    if let Some(progress) = &opts.progress {
+        progress(LoopEvent {
+            iteration: state.iteration,
+            status: LOOP_EVENT_EXIT.to_string(),
+            details: message,
+        });
+    }
+    Ok(ItemFlow::Stop)
+}
+
+fn report_retry(
+    opts: &LoopOptions,
+    status_path: &Path,
+    work_status_path: &Path,
+    work_status: &WorkStatusFile,
+    iteration: i64,
+    key: &str,
+    message: &str,
+) -> LooprResult<()> {
+    write_work_status(work_status_path, work_status)?;
+    let attempts = work_status
+        .items
+        .get(key)
+        .map(|item| item.attempts)
+        .unwrap_or(0);
+    write_loop_status(
+        status_path,
+        LoopStatusPayload {
+            state: "running".to_string(),
+            iteration,
+            updated_at: now_rfc3339()?,
+            exit_reason: None,
+            last_summary: Some(format!("retrying {} after attempt {}", key, attempts)),
+            last_error: Some(message.to_string()),
+        },
+    )?;
+    if let Some(progress) = &opts.progress {
+        progress(LoopEvent {
+            iteration,
+            status: LOOP_EVENT_RETRY.to_string(),
+            details: format!("{} attempt {} failed: {}", key, attempts, message),
+        });
+    }
+    Ok(())
+}
+
+fn report_skip(
+    opts: &LoopOptions,
+    status_path: &Path,
+    iteration: i64,
+    key: &str,
+    reason: &str,
+) -> LooprResult<()> {
+    write_loop_status(
+        status_path,
+        LoopStatusPayload {
+            state: "running".to_string(),
+            iteration,
+            updated_at: now_rfc3339()?,
+            exit_reason: None,
+            last_summary: Some(format!("skipping {} and its dependents", key)),
+            last_error: Some(reason.to_string()),
+        },
+    )?;
+    if let Some(progress) = &opts.progress {
+        progress(LoopEvent {
+            iteration,
+            status: LOOP_EVENT_SKIP.to_string(),
+            details: format!("{}: {}", key, reason),
+        });
+    }
+    Ok(())
+}
+
 fn mark_test_complete(
     status: &mut WorkStatusFile,
     key: &str,
@@ -1127,6 +2551,41 @@ fn mark_task_complete(status: &mut WorkStatusFile, key: &str, summary: &str, now
     status.updated_at = now.to_string();
 }
 
+fn coverage_percent(result: &CoverageResult) -> String {
+    match result.percent {
+        Some(percent) => format!("{:.1}%", percent),
+        None => "no measured lines".to_string(),
+    }
+}
+
+// What the coverage check found, for messages and events.
+fn coverage_outcome(result: &CoverageResult) -> String {
+    match &result.error {
+        Some(error) => error.clone(),
+        None => format!(
+            "{} of changed lines ({}/{}) below {}%",
+            coverage_percent(result),
+            result.lines_covered,
+            result.lines_total,
+            result.min_percent
+        ),
+    }
+}
+
+fn gate_outcome(run: &TestRunResult) -> String {
+    if run.timed_out {
+        "timed out".to_string()
+    } else {
+        format!("exit {}", run.exit_code)
+    }
+}
+
+fn record_item_sessions(status: &mut WorkStatusFile, key: &str, sessions: &[ItemSession]) {
+    if let Some(item) = status.items.get_mut(key) {
+        item.sessions.extend(sessions.iter().cloned());
+    }
+}
+
 fn update_test_result(
     status: &mut WorkStatusFile,
     key: &str,
@@ -1142,11 +2601,12 @@ fn update_test_result(
 
 fn run_codex_for_prompt(
     root: &Path,
+    workdir: &Path,
     cfg: &LoopConfig,
     codex_args: &[String],
     prompt: String,
 ) -> LooprResult<CodexRun> {
-    let mut args = vec!["--cd".to_string(), root.display().to_string()];
+    let mut args = vec!["--cd".to_string(), workdir.display().to_string()];
     args.extend(codex_args.iter().cloned());
     args.push(prompt);
 
@@ -1170,33 +2630,11 @@ fn run_codex_for_prompt(
     }
 }
 
-fn run_test_command(root: &Path, command: &str, phase: &str) -> LooprResult<TestRunResult> {
-    let command = command.trim();
-    if command.is_empty() {
-        return Err(LooprError::new("TEST_COMMAND is empty"));
-    }
-    let output = Command::new("sh")
-        .arg("-lc")
-        .arg(command)
-        .current_dir(root)
-        .output()
-        .map_err(|err| LooprError::new(format!("run test command: {}", err)))?;
-    let exit_code = output.status.code().unwrap_or(1);
-    let passed = exit_code == 0;
-    let _ = output;
-    Ok(TestRunResult {
-        exit_code,
-        passed,
-        ran_at: now_rfc3339()?,
-        phase: phase.to_string(),
-    })
-}
-
 fn detect_pbt(test: &TestSpec, root: &Path) -> LooprResult<bool> {
-    if let Some(kind) = &test.kind {
-        if kind.eq_ignore_ascii_case("pbt") {
-            return Ok(true);
-        }
+    if let Some(kind) = &test.kind
+        && kind.eq_ignore_ascii_case("pbt")
+    {
+        return Ok(true);
     }
     let path = root.join(&test.file);
     let data = std::fs::read_to_string(&path).map_err(|err| {
@@ -1215,7 +2653,18 @@ fn detect_pbt(test: &TestSpec, root: &Path) -> LooprResult<bool> {
     Ok(keywords.iter().any(|needle| lower.contains(needle)))
 }
 
+struct RetryContext<'a> {
+    attempt: u32,
+    max_attempts: i64,
+    previous_failure: &'a str,
+    test_failure: &'a str,
+    fix_round: u32,
+    max_fix_rounds: i64,
+}
+
+#[allow(clippy::too_many_arguments)]
 fn build_per_task_prompt(
+    templates: &PromptTemplates,
     step: &RunStep,
     handoff_path: &Path,
     root: &Path,
@@ -1225,108 +2674,48 @@ fn build_per_task_prompt(
     phase: &str,
     inputs: &[String],
     pbt: bool,
-) -> String {
-    let mut lines = vec![
-        format!("Loopr loop iteration: {}", iteration),
-        format!("Item: {} ({})", item_key, item_type),
-        format!("Phase: {}", phase),
-        format!("Loopr root: {}", root.display()),
-    ];
-    let docs_index = root.join("loopr").join("state").join("docs-index.txt");
-    if docs_index.exists() {
-        lines.push(format!("Docs index: {}", docs_index.display()));
-    }
-    lines.push(format!("Handoff: {}", handoff_path.display()));
-    lines.push(String::new());
-    lines.push(format!("Prompt: {}", step.skill));
-    lines.push(String::new());
-    lines.push("Allowed inputs:".to_string());
-    let allowed_inputs = add_context_inputs(inputs);
-    for input in allowed_inputs {
-        lines.push(format!("- {}", input));
-    }
-    if step.allow_repo_read {
-        lines.push("- Repo files as needed (read-only).".to_string());
-    }
-    lines.push(String::new());
-    lines.push("Required outputs:".to_string());
-    for output in &step.outputs {
-        lines.push(format!("- {}", output));
-    }
-
-    lines.push(String::new());
-    lines.push("Rules:".to_string());
-    if step.allow_repo_read {
-        lines.push(
-            "- Read the allowed inputs and any repo files needed for implementation.".to_string(),
-        );
-        lines.push("- Avoid broad scans; open only what you need.".to_string());
-    } else {
-        lines.push("- Read only the allowed inputs.".to_string());
-        lines.push("- Do not scan the repo.".to_string());
-    }
-    lines.push(
-        "- If required inputs are missing, stop and ask to run the appropriate step.".to_string(),
-    );
-    lines.push(format!(
-        "- Append a completion note to {} (decisions, open questions, tests).",
-        handoff_path.display()
-    ));
-    match phase {
-        "tests" => {
-            lines.push("- Write tests only; do not implement production code beyond minimal scaffolding.".to_string());
-            lines.push("- Ensure tests can run (Loopr will execute the test command after this session).".to_string());
-            if pbt {
-                lines.push("- This is a PBT test: tests must fail on the first run.".to_string());
-            }
-        }
-        "implement" => {
-            lines.push("- Implement the task to satisfy the tests.".to_string());
-            lines.push("- Ensure tests pass (Loopr will execute the test command after this session).".to_string());
-        }
-        _ => {}
-    }
-    lines.push(
-        "- Only set EXIT_SIGNAL: true when all tasks are complete and tests are green.".to_string(),
-    );
-    lines.push("- Always include the status block at the end of your response.".to_string());
-    lines.push(String::new());
-    lines.push("Status block format (required):".to_string());
-    lines.push(LOOPR_STATUS_START.to_string());
-    lines.push("STATUS: IN_PROGRESS | COMPLETE | BLOCKED | ERROR".to_string());
-    lines.push("EXIT_SIGNAL: true | false".to_string());
-    lines.push(format!("ITEM_KEY: {}", item_key));
-    lines.push(format!("ITEM_TYPE: {}", item_type));
-    lines.push(format!("PHASE: {}", phase));
-    lines.push("SUMMARY: <short summary>".to_string());
-    lines.push(LOOPR_STATUS_END.to_string());
-    lines.push(String::new());
-    lines.push(format!("Run the prompt: {}", step.skill));
-    lines.join("\n")
+    retry: &RetryContext,
+) -> LooprResult<String> {
+    let mut ctx = prompt_context(step, handoff_path, root, add_context_inputs(inputs));
+    ctx.flag("requires_seed", false)
+        .flag("loop_mode", true)
+        .text("iteration", iteration.to_string())
+        .text("item_key", item_key)
+        .text("item_type", item_type)
+        .text("phase", phase)
+        .flag("phase_tests", phase == "tests")
+        .flag("phase_implement", phase == "implement")
+        .flag("pbt", pbt)
+        .text("attempt", retry.attempt.to_string())
+        .text("max_attempts", retry.max_attempts.to_string())
+        .text("previous_failure", retry.previous_failure)
+        .text("test_failure", retry.test_failure)
+        .text(
+            "fix_round",
+            if retry.fix_round > 0 {
+                retry.fix_round.to_string()
+            } else {
+                String::new()
+            },
+        )
+        .text("max_fix_rounds", retry.max_fix_rounds.to_string());
+    templates.render(TEMPLATE_PER_TASK, &ctx)
 }
 
 fn build_loop_prompt(
+    templates: &PromptTemplates,
     step: &RunStep,
     handoff_path: &Path,
     root: &Path,
     iteration: i64,
-) -> String {
-    let mut lines = vec![format!("Loopr loop iteration: {}", iteration)];
-    lines.extend(build_prompt_lines(step, "", handoff_path, root));
-    lines.push(
-        "- Only set EXIT_SIGNAL: true when all tasks are complete and tests are green.".to_string(),
-    );
-    lines.push("- Always include the status block at the end of your response.".to_string());
-    lines.push(String::new());
-    lines.push("Status block format (required):".to_string());
-    lines.push(LOOPR_STATUS_START.to_string());
-    lines.push("STATUS: IN_PROGRESS | COMPLETE | BLOCKED | ERROR".to_string());
-    lines.push("EXIT_SIGNAL: true | false".to_string());
-    lines.push("SUMMARY: <short summary>".to_string());
-    lines.push(LOOPR_STATUS_END.to_string());
-    lines.push(String::new());
-    lines.push(format!("Run the prompt: {}", step.skill));
-    lines.join("\n")
+) -> LooprResult<String> {
+    let mut inputs = step.inputs.clone();
+    inputs.push("AGENTS.md".to_string());
+    inputs.push("loopr/state/docs-index.txt".to_string());
+    let mut ctx = prompt_context(step, handoff_path, root, dedupe_inputs(inputs));
+    ctx.text("iteration", iteration.to_string())
+        .flag("loop_mode", true);
+    templates.render(TEMPLATE_LOOP, &ctx)
 }
 
 fn evaluate_loop_exit(
@@ -1,12 +1,27 @@
 pub mod codex;
+pub mod coverage;
 pub mod docs_index;
+pub mod editor;
+pub mod feature_scope;
 pub mod fs;
+pub mod glob;
 pub mod init;
 pub mod loop_config;
 pub mod loop_run;
 pub mod loop_status;
 pub mod loopr_root;
 pub mod nanoid;
+pub mod prompt_template;
+pub mod review;
 pub mod run;
+pub mod run_state;
+pub mod run_status;
+pub mod seed;
+pub mod skills;
+pub mod snapshot;
+pub mod stale;
+pub mod test_command;
+pub mod test_results;
+pub mod validate;
 pub mod work_plan;
 pub mod work_status;
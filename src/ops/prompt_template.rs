@@ -0,0 +1,450 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::ops::fs::write_file_atomic;
+use crate::{LooprError, LooprResult};
+
+pub const TEMPLATE_STEP: &str = "step";
+pub const TEMPLATE_LOOP: &str = "loop";
+pub const TEMPLATE_PER_TASK: &str = "per-task";
+pub const TEMPLATE_SEED: &str = "seed";
+
+const DEFAULT_TEMPLATES: &[(&str, &str)] = &[
+    (TEMPLATE_STEP, include_str!("../templates/step.tmpl")),
+    (TEMPLATE_LOOP, include_str!("../templates/loop.tmpl")),
+    (
+        TEMPLATE_PER_TASK,
+        include_str!("../templates/per-task.tmpl"),
+    ),
+    (TEMPLATE_SEED, include_str!("../templates/seed.tmpl")),
+    ("context", include_str!("../templates/context.tmpl")),
+    ("rules", include_str!("../templates/rules.tmpl")),
+    (
+        "status-block",
+        include_str!("../templates/status-block.tmpl"),
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateValue {
+    Text(String),
+    Flag(bool),
+    List(Vec<String>),
+}
+
+impl TemplateValue {
+    fn is_truthy(&self) -> bool {
+        match self {
+            TemplateValue::Text(value) => !value.is_empty(),
+            TemplateValue::Flag(value) => *value,
+            TemplateValue::List(values) => !values.is_empty(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TemplateContext {
+    values: BTreeMap<String, TemplateValue>,
+}
+
+impl TemplateContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(&mut self, name: &str, value: impl Into<String>) -> &mut Self {
+        self.values
+            .insert(name.to_string(), TemplateValue::Text(value.into()));
+        self
+    }
+
+    pub fn flag(&mut self, name: &str, value: bool) -> &mut Self {
+        self.values
+            .insert(name.to_string(), TemplateValue::Flag(value));
+        self
+    }
+
+    pub fn list(&mut self, name: &str, values: Vec<String>) -> &mut Self {
+        self.values
+            .insert(name.to_string(), TemplateValue::List(values));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TemplateValue> {
+        self.values.get(name)
+    }
+}
+
+pub struct PromptTemplates {
+    sources: BTreeMap<String, TemplateSource>,
+}
+
+struct TemplateSource {
+    origin: String,
+    body: String,
+}
+
+pub fn prompts_dir(root: &Path) -> PathBuf {
+    root.join("loopr").join("prompts")
+}
+
+impl PromptTemplates {
+    pub fn defaults() -> Self {
+        let mut sources = BTreeMap::new();
+        for (name, body) in DEFAULT_TEMPLATES {
+            sources.insert(
+                name.to_string(),
+                TemplateSource {
+                    origin: format!("built-in {}.tmpl", name),
+                    body: body.to_string(),
+                },
+            );
+        }
+        Self { sources }
+    }
+
+    pub fn load(root: &Path) -> LooprResult<Self> {
+        let mut templates = Self::defaults();
+        let dir = prompts_dir(root);
+        if !dir.is_dir() {
+            return Ok(templates);
+        }
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|err| LooprError::new(format!("read dir {}: {}", dir.display(), err)))?;
+        for entry in entries {
+            let entry = entry.map_err(|err| LooprError::new(format!("read dir entry: {}", err)))?;
+            let path = entry.path();
+            let name = match path.file_name().and_then(|s| s.to_str()) {
+                Some(value) => value,
+                None => continue,
+            };
+            let name = match name.strip_suffix(".tmpl") {
+                Some(value) => value.to_string(),
+                None => continue,
+            };
+            let body = std::fs::read_to_string(&path)
+                .map_err(|err| LooprError::new(format!("read {}: {}", path.display(), err)))?;
+            templates.sources.insert(
+                name,
+                TemplateSource {
+                    origin: path.display().to_string(),
+                    body,
+                },
+            );
+        }
+        Ok(templates)
+    }
+
+    pub fn render(&self, name: &str, ctx: &TemplateContext) -> LooprResult<String> {
+        let mut out = String::new();
+        self.render_into(name, ctx, &mut Vec::new(), &mut out)?;
+        let trimmed = out.trim_end_matches('\n').len();
+        out.truncate(trimmed);
+        Ok(out)
+    }
+
+    fn render_into(
+        &self,
+        name: &str,
+        ctx: &TemplateContext,
+        stack: &mut Vec<String>,
+        out: &mut String,
+    ) -> LooprResult<()> {
+        let source = self
+            .sources
+            .get(name)
+            .ok_or_else(|| LooprError::new(format!("unknown prompt template: {}", name)))?;
+        if stack.iter().any(|value| value == name) {
+            return Err(LooprError::new(format!(
+                "prompt template {}: recursive include of {}",
+                source.origin, name
+            )));
+        }
+        let nodes = parse_template(&source.body).map_err(|err| {
+            LooprError::new(format!("prompt template {}: {}", source.origin, err))
+        })?;
+        stack.push(name.to_string());
+        let result = self.render_nodes(&nodes, ctx, None, stack, out);
+        stack.pop();
+        result.map_err(|err| {
+            if err.message.starts_with("prompt template ") {
+                err
+            } else {
+                LooprError::new(format!("prompt template {}: {}", source.origin, err))
+            }
+        })
+    }
+
+    fn render_nodes(
+        &self,
+        nodes: &[Node],
+        ctx: &TemplateContext,
+        item: Option<&str>,
+        stack: &mut Vec<String>,
+        out: &mut String,
+    ) -> LooprResult<()> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Var(name) => {
+                    if name == "this" {
+                        match item {
+                            Some(value) => out.push_str(value),
+                            None => {
+                                return Err(LooprError::new("{{this}} used outside {{#each}}"));
+                            }
+                        }
+                        continue;
+                    }
+                    match lookup(ctx, name)? {
+                        TemplateValue::Text(value) => out.push_str(value),
+                        TemplateValue::Flag(value) => out.push_str(&value.to_string()),
+                        TemplateValue::List(values) => out.push_str(&values.join(", ")),
+                    }
+                }
+                Node::If {
+                    name,
+                    negate,
+                    then_nodes,
+                    else_nodes,
+                } => {
+                    let truthy = lookup(ctx, name)?.is_truthy() != *negate;
+                    let branch = if truthy { then_nodes } else { else_nodes };
+                    self.render_nodes(branch, ctx, item, stack, out)?;
+                }
+                Node::Each { name, body } => match lookup(ctx, name)? {
+                    TemplateValue::List(values) => {
+                        for value in values {
+                            self.render_nodes(body, ctx, Some(value), stack, out)?;
+                        }
+                    }
+                    _ => {
+                        return Err(LooprError::new(format!(
+                            "{{{{#each {}}}}} requires a list",
+                            name
+                        )));
+                    }
+                },
+                Node::Include(name) => self.render_into(name, ctx, stack, out)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn lookup<'a>(ctx: &'a TemplateContext, name: &str) -> LooprResult<&'a TemplateValue> {
+    ctx.get(name)
+        .ok_or_else(|| LooprError::new(format!("unknown variable: {}", name)))
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Var(String),
+    If {
+        name: String,
+        negate: bool,
+        then_nodes: Vec<Node>,
+        else_nodes: Vec<Node>,
+    },
+    Each {
+        name: String,
+        body: Vec<Node>,
+    },
+    Include(String),
+}
+
+enum Tag {
+    Var(String),
+    If(String, bool),
+    Else,
+    EndIf,
+    Each(String),
+    EndEach,
+    Include(String),
+}
+
+fn parse_template(source: &str) -> LooprResult<Vec<Node>> {
+    let tokens = tokenize(source)?;
+    let mut pos = 0;
+    let nodes = parse_nodes(&tokens, &mut pos, None)?;
+    Ok(nodes)
+}
+
+enum Token {
+    Text(String),
+    Tag(Tag, usize),
+}
+
+fn parse_nodes(tokens: &[Token], pos: &mut usize, closing: Option<&str>) -> LooprResult<Vec<Node>> {
+    let mut nodes = Vec::new();
+    while *pos < tokens.len() {
+        let token = &tokens[*pos];
+        *pos += 1;
+        match token {
+            Token::Text(text) => nodes.push(Node::Text(text.clone())),
+            Token::Tag(Tag::Var(name), _) => nodes.push(Node::Var(name.clone())),
+            Token::Tag(Tag::Include(name), _) => nodes.push(Node::Include(name.clone())),
+            Token::Tag(Tag::If(name, negate), line) => {
+                let then_nodes = parse_nodes(tokens, pos, Some("if"))?;
+                let mut else_nodes = Vec::new();
+                if matches!(tokens.get(*pos - 1), Some(Token::Tag(Tag::Else, _))) {
+                    else_nodes = parse_nodes(tokens, pos, Some("else"))?;
+                }
+                if !matches!(tokens.get(*pos - 1), Some(Token::Tag(Tag::EndIf, _))) {
+                    return Err(LooprError::new(format!(
+                        "line {}: unclosed {{{{#if {}}}}}",
+                        line, name
+                    )));
+                }
+                nodes.push(Node::If {
+                    name: name.clone(),
+                    negate: *negate,
+                    then_nodes,
+                    else_nodes,
+                });
+            }
+            Token::Tag(Tag::Each(name), line) => {
+                let body = parse_nodes(tokens, pos, Some("each"))?;
+                if !matches!(tokens.get(*pos - 1), Some(Token::Tag(Tag::EndEach, _))) {
+                    return Err(LooprError::new(format!(
+                        "line {}: unclosed {{{{#each {}}}}}",
+                        line, name
+                    )));
+                }
+                nodes.push(Node::Each {
+                    name: name.clone(),
+                    body,
+                });
+            }
+            Token::Tag(Tag::Else, line) => {
+                if closing != Some("if") {
+                    return Err(LooprError::new(format!(
+                        "line {}: {{{{else}}}} without {{{{#if}}}}",
+                        line
+                    )));
+                }
+                return Ok(nodes);
+            }
+            Token::Tag(Tag::EndIf, line) => {
+                if closing != Some("if") && closing != Some("else") {
+                    return Err(LooprError::new(format!(
+                        "line {}: {{{{/if}}}} without {{{{#if}}}}",
+                        line
+                    )));
+                }
+                return Ok(nodes);
+            }
+            Token::Tag(Tag::EndEach, line) => {
+                if closing != Some("each") {
+                    return Err(LooprError::new(format!(
+                        "line {}: {{{{/each}}}} without {{{{#each}}}}",
+                        line
+                    )));
+                }
+                return Ok(nodes);
+            }
+        }
+    }
+    if let Some(block) = closing {
+        return Err(LooprError::new(format!(
+            "unclosed {{{{#{}}}}} block",
+            if block == "else" { "if" } else { block }
+        )));
+    }
+    Ok(nodes)
+}
+
+// Block tags ({{#if}}, {{else}}, {{/if}}, {{#each}}, {{/each}}, {{> name}}) that sit alone on a
+// line drop the whole line, so templates can be laid out one tag per line.
+fn tokenize(source: &str) -> LooprResult<Vec<Token>> {
+    let mut tokens = Vec::new();
+    for (idx, line_text) in source.split_inclusive('\n').enumerate() {
+        let line = idx + 1;
+        let trimmed = line_text.trim();
+        if trimmed.starts_with("{{")
+            && trimmed.ends_with("}}")
+            && trimmed.matches("{{").count() == 1
+        {
+            let tag = parse_tag(trimmed[2..trimmed.len() - 2].trim(), line)?;
+            if !matches!(tag, Tag::Var(_)) {
+                tokens.push(Token::Tag(tag, line));
+                continue;
+            }
+        }
+        let mut rest = line_text;
+        while let Some(start) = rest.find("{{") {
+            let end = rest[start..]
+                .find("}}")
+                .map(|value| start + value)
+                .ok_or_else(|| LooprError::new(format!("line {}: unclosed {{{{", line)))?;
+            if start > 0 {
+                tokens.push(Token::Text(rest[..start].to_string()));
+            }
+            let tag = parse_tag(rest[start + 2..end].trim(), line)?;
+            tokens.push(Token::Tag(tag, line));
+            rest = &rest[end + 2..];
+        }
+        if !rest.is_empty() {
+            tokens.push(Token::Text(rest.to_string()));
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_tag(raw: &str, line: usize) -> LooprResult<Tag> {
+    let invalid = || LooprError::new(format!("line {}: invalid tag {{{{{}}}}}", line, raw));
+    if let Some(rest) = raw.strip_prefix("#if ") {
+        return Ok(Tag::If(valid_name(rest.trim()).ok_or_else(invalid)?, false));
+    }
+    if let Some(rest) = raw.strip_prefix("#unless ") {
+        return Ok(Tag::If(valid_name(rest.trim()).ok_or_else(invalid)?, true));
+    }
+    if let Some(rest) = raw.strip_prefix("#each ") {
+        return Ok(Tag::Each(valid_name(rest.trim()).ok_or_else(invalid)?));
+    }
+    if let Some(rest) = raw.strip_prefix('>') {
+        return Ok(Tag::Include(valid_name(rest.trim()).ok_or_else(invalid)?));
+    }
+    match raw {
+        "else" => Ok(Tag::Else),
+        "/if" | "/unless" => Ok(Tag::EndIf),
+        "/each" => Ok(Tag::EndEach),
+        _ => Ok(Tag::Var(valid_name(raw).ok_or_else(invalid)?)),
+    }
+}
+
+fn valid_name(raw: &str) -> Option<String> {
+    if !raw.is_empty()
+        && raw
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+    {
+        Some(raw.to_string())
+    } else {
+        None
+    }
+}
+
+pub struct EjectReport {
+    pub written: Vec<PathBuf>,
+    pub skipped: Vec<PathBuf>,
+}
+
+pub fn eject_templates(root: &Path, force: bool) -> LooprResult<EjectReport> {
+    let dir = prompts_dir(root);
+    let mut report = EjectReport {
+        written: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for (name, body) in DEFAULT_TEMPLATES {
+        let path = dir.join(format!("{}.tmpl", name));
+        if path.exists() && !force {
+            report.skipped.push(path);
+            continue;
+        }
+        write_file_atomic(&path, body.as_bytes(), 0o644)?;
+        report.written.push(path);
+    }
+    Ok(report)
+}
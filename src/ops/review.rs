@@ -0,0 +1,149 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::ops::editor::open_in_editor;
+use crate::ops::run::{RunStep, StepOutputState, changed_step_outputs};
+use crate::ops::snapshot::diff_stat_since;
+use crate::{LooprError, LooprResult};
+
+pub struct StepReview {
+    pub diffstat: Option<String>,
+    pub changed_files: Vec<String>,
+    pub new_files: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReviewDecision {
+    Approve(String),
+    Reject(String),
+    Stop,
+}
+
+pub fn collect_step_review(
+    root: &Path,
+    step: &RunStep,
+    before: &StepOutputState,
+    tree_before: Option<&str>,
+) -> LooprResult<StepReview> {
+    let diffstat = match tree_before {
+        Some(tree) => Some(diff_stat_since(root, tree)?),
+        None => None,
+    };
+    let mut changed_files = Vec::new();
+    let mut new_files = Vec::new();
+    for (rel, is_new) in changed_step_outputs(root, step, before)? {
+        if is_new {
+            new_files.push(rel);
+        } else {
+            changed_files.push(rel);
+        }
+    }
+    Ok(StepReview {
+        diffstat,
+        changed_files,
+        new_files,
+    })
+}
+
+pub fn print_step_review(step: &RunStep, review: &StepReview) {
+    println!();
+    println!("Review step {} ({})", step.name, step.skill);
+    if let Some(diffstat) = &review.diffstat {
+        if diffstat.trim().is_empty() {
+            println!("  no changes in the working tree");
+        } else {
+            for line in diffstat.lines() {
+                println!("  {}", line.trim_end());
+            }
+        }
+    }
+    if !review.new_files.is_empty() {
+        println!("New files:");
+        for file in &review.new_files {
+            println!("  {}", file);
+        }
+    }
+    if !review.changed_files.is_empty() {
+        println!("Changed files:");
+        for file in &review.changed_files {
+            println!("  {}", file);
+        }
+    }
+}
+
+pub fn prompt_review(
+    root: &Path,
+    step: &RunStep,
+    review: &StepReview,
+) -> LooprResult<ReviewDecision> {
+    loop {
+        print!("[a]pprove, [r]eject and re-run, [e]dit in $EDITOR, [q]uit? ");
+        let answer = read_answer()?.to_lowercase();
+        match answer.as_str() {
+            "a" | "approve" => {
+                print!("Comment (optional): ");
+                return Ok(ReviewDecision::Approve(read_answer()?));
+            }
+            "r" | "reject" => {
+                print!("What should change? ");
+                let comment = read_answer()?;
+                if comment.is_empty() {
+                    println!("A comment is required to re-run step {}.", step.name);
+                    continue;
+                }
+                return Ok(ReviewDecision::Reject(comment));
+            }
+            "e" | "edit" => {
+                let mut files = review.new_files.clone();
+                files.extend(review.changed_files.iter().cloned());
+                if let Err(err) = open_in_editor(root, &files) {
+                    println!("{}", err);
+                }
+            }
+            "q" | "quit" => return Ok(ReviewDecision::Stop),
+            _ => {}
+        }
+    }
+}
+
+pub fn record_review(
+    handoff_path: &Path,
+    step: &RunStep,
+    decision: &ReviewDecision,
+    now: &str,
+) -> LooprResult<()> {
+    let (verdict, comment) = match decision {
+        ReviewDecision::Approve(comment) => ("approved", comment.as_str()),
+        ReviewDecision::Reject(comment) => ("rejected", comment.as_str()),
+        ReviewDecision::Stop => ("stopped", ""),
+    };
+    let mut entry = format!("\n## Review: {} ({})\n\n", step.name, verdict);
+    entry.push_str(&format!("- Reviewed: {}\n", now));
+    if comment.is_empty() {
+        entry.push_str("- Comment: none\n");
+    } else {
+        entry.push_str(&format!("- Comment: {}\n", comment));
+    }
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(handoff_path)
+        .map_err(|err| LooprError::new(format!("open {}: {}", handoff_path.display(), err)))?;
+    file.write_all(entry.as_bytes())
+        .map_err(|err| LooprError::new(format!("write {}: {}", handoff_path.display(), err)))
+}
+
+fn read_answer() -> LooprResult<String> {
+    io::stdout()
+        .flush()
+        .map_err(|err| LooprError::new(format!("flush stdout: {}", err)))?;
+    let mut input = String::new();
+    let read = io::stdin()
+        .read_line(&mut input)
+        .map_err(|err| LooprError::new(format!("read stdin: {}", err)))?;
+    if read == 0 {
+        return Err(LooprError::new("review aborted (end of input)"));
+    }
+    Ok(input.trim().to_string())
+}
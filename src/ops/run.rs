@@ -1,25 +1,77 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 use time::format_description::well_known::Rfc3339;
 
-use crate::ops::codex::{CodexMode, CodexOptions, CodexRun, CodexSession, run_codex};
+use crate::ops::codex::{
+    CodexMode, CodexOptions, CodexRun, CodexSession, run_codex, run_codex_with_timeout,
+};
 use crate::ops::docs_index::write_docs_index;
+use crate::ops::feature_scope::{
+    SCOPED_STEPS, load_feature, merge_scoped_orders, scope_steps, snapshot_orders,
+};
 use crate::ops::fs::write_file_atomic;
+use crate::ops::glob::{expand_glob, is_glob, path_matches};
+use crate::ops::loop_config::load_loop_config;
+use crate::ops::loop_status::{LOOPR_STATUS_END, LOOPR_STATUS_START, parse_loopr_status_from_log};
 use crate::ops::loopr_root::resolve_loopr_root;
+use crate::ops::prompt_template::{PromptTemplates, TEMPLATE_STEP, TemplateContext};
+use crate::ops::review::{
+    ReviewDecision, collect_step_review, print_step_review, prompt_review, record_review,
+};
+use crate::ops::run_state::{
+    SkipDecision, evaluate_skip, hash_step_inputs, hash_step_outputs, load_run_state,
+    record_step_run, run_state_path, write_run_state,
+};
+use crate::ops::run_status::{
+    RUN_STATE_COMPLETE, RUN_STATE_FAILED, RUN_STATE_RUNNING, RunSessionRecord, RunStatusFile,
+    load_run_status, run_status_path, write_run_status,
+};
+use crate::ops::seed::{SEED_PATH, archive_seed, edit_seed_prompt, read_seed};
+use crate::ops::snapshot::{new_run_id, worktree_tree};
+use crate::ops::validate::{FEATURE_ORDER_PATH, TASK_ORDER_PATH, TEST_ORDER_PATH, validate_orders};
+use crate::ops::work_plan::FeatureSpec;
 use crate::{LooprError, LooprResult};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct RunStep {
     pub name: String,
     pub skill: String,
+    #[serde(default)]
     pub inputs: Vec<String>,
+    #[serde(default)]
     pub outputs: Vec<String>,
+    #[serde(default)]
     pub requires_seed: bool,
+    #[serde(default)]
     pub always_run: bool,
+    #[serde(default)]
     pub allow_repo_read: bool,
+    #[serde(skip)]
+    pub feature: Option<FeatureSpec>,
+}
+
+impl RunStep {
+    // Feature-scoped runs are tracked separately in run-state.json so they don't mask full runs.
+    pub fn state_key(&self) -> String {
+        match &self.feature {
+            Some(feature) => format!("{}@{}", self.name, feature.key),
+            None => self.name.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RunStepsFile {
+    version: i64,
+    steps: Vec<RunStep>,
 }
 
 pub struct RunOptions {
@@ -27,8 +79,14 @@ pub struct RunOptions {
     pub from: String,
     pub to: String,
     pub step: String,
+    pub feature: String,
     pub seed: String,
+    pub edit_seed: bool,
     pub confirm: bool,
+    pub review: bool,
+    pub show_prompts: bool,
+    pub force: bool,
+    pub resume: bool,
     pub no_prompt: bool,
     pub codex: bool,
     pub codex_args: Vec<String>,
@@ -36,12 +94,42 @@ pub struct RunOptions {
 }
 
 pub struct RunReport {
+    pub run_id: Option<String>,
     pub steps: Vec<RunStep>,
     pub executed: Vec<RunStep>,
     pub skipped: Vec<RunStep>,
+    pub decisions: HashMap<String, SkipDecision>,
+    pub plan: Vec<StepPlan>,
     pub last_session: Option<CodexSession>,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct StepPlan {
+    pub name: String,
+    pub skill: String,
+    pub action: String,
+    pub reason: String,
+    pub inputs: Vec<PlannedPath>,
+    pub outputs: Vec<PlannedPath>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedPath {
+    pub pattern: String,
+    pub files: Vec<String>,
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub produced_by: Option<String>,
+}
+
+pub const HANDOFF_PATH: &str = "loopr/state/handoff.md";
+
+pub const PATH_PRESENT: &str = "present";
+pub const PATH_PENDING: &str = "pending";
+pub const PATH_MISSING: &str = "missing";
+
 pub struct ProgressEvent {
     pub step: RunStep,
     pub index: usize,
@@ -52,6 +140,7 @@ pub struct ProgressEvent {
 pub const PROGRESS_START: &str = "start";
 pub const PROGRESS_DONE: &str = "done";
 pub const PROGRESS_ERROR: &str = "error";
+pub const PROGRESS_SKIPPED: &str = "skipped";
 
 pub fn run_workflow(opts: RunOptions) -> LooprResult<RunReport> {
     let cwd = std::env::current_dir()?;
@@ -80,9 +169,12 @@ pub fn run_workflow(opts: RunOptions) -> LooprResult<RunReport> {
         )?;
         let err = codex_error(&run);
         let report = RunReport {
+            run_id: None,
             steps: Vec::new(),
             executed: Vec::new(),
             skipped: Vec::new(),
+            decisions: HashMap::new(),
+            plan: Vec::new(),
             last_session: Some(run.session),
         };
         if let Some(err) = err {
@@ -97,84 +189,657 @@ pub fn run_workflow(opts: RunOptions) -> LooprResult<RunReport> {
         None
     };
 
-    let steps = plan_steps(&opts)?;
+    let all_steps = load_run_steps(&root)?;
+    let status_path = run_status_path(&root);
+    let resumed = if opts.resume {
+        Some(load_resumable_run(&status_path)?)
+    } else {
+        None
+    };
+    let steps = match &resumed {
+        Some(status) => resume_steps(&all_steps, status)?,
+        None => plan_steps(&all_steps, &opts)?,
+    };
+    let feature_key = match &resumed {
+        Some(status) => status.feature.clone(),
+        None => Some(opts.feature.clone()).filter(|key| !key.is_empty()),
+    };
+    let scope = match &feature_key {
+        Some(key) => Some(load_feature(&root, key)?),
+        None => None,
+    };
+    let steps = match &scope {
+        Some((feature, _)) => scope_steps(&steps, feature)?,
+        None => steps,
+    };
+    let force = resumed
+        .as_ref()
+        .map(|status| status.force)
+        .unwrap_or(opts.force);
+    // A resumed run keeps the review gates it was started with.
+    let (review, confirm) = match &resumed {
+        Some(status) => (status.review || opts.review, status.confirm || opts.confirm),
+        None => (opts.review, opts.confirm),
+    };
     let mut report = RunReport {
+        run_id: resumed.as_ref().map(|status| status.run_id.clone()),
         steps: steps.clone(),
         executed: Vec::new(),
         skipped: Vec::new(),
+        decisions: HashMap::new(),
+        plan: Vec::new(),
         last_session: None,
     };
 
-    if !opts.codex {
-        return Ok(report);
-    }
+    let state_path = run_state_path(&root);
+    let mut run_state = load_run_state(&state_path, &now_rfc3339()?)?;
 
-    let total = steps.len();
-    for step in steps {
-        let idx = report.executed.len() + report.skipped.len() + 1;
-        if append_prompt && step.requires_seed && opts.seed.trim().is_empty() {
-            return Err(LooprError::new(format!(
-                "seed prompt required for {} (use --seed-prompt)",
-                step.name
-            )));
-        }
-        if opts.confirm {
-            let ok = confirm_step(&step.name)?;
-            if !ok {
-                return Err(LooprError::new("run cancelled"));
+    if !opts.codex {
+        let templates = if opts.show_prompts {
+            Some(PromptTemplates::load(&root)?)
+        } else {
+            None
+        };
+        let handoff = root.join(HANDOFF_PATH);
+        let mut will_run: Vec<&RunStep> = Vec::new();
+        for step in &steps {
+            let mut decision = seed_decision(
+                &root,
+                step,
+                &opts.seed,
+                evaluate_skip(&root, step, &run_state, force)?,
+            )?;
+            if decision.skip
+                && let Some(upstream) = will_run.iter().find(|upstream| {
+                    step.inputs
+                        .iter()
+                        .any(|input| produces_input(upstream, input))
+                })
+            {
+                decision = SkipDecision {
+                    skip: false,
+                    reason: format!("upstream step {} will run", upstream.name),
+                };
             }
-        }
-        if let Some(progress) = &opts.progress {
-            progress(ProgressEvent {
-                step: step.clone(),
-                index: idx,
-                total,
-                status: PROGRESS_START.to_string(),
+            let inputs = plan_step_inputs(&root, step, &all_steps, &will_run)?;
+            let outputs = plan_step_outputs(&root, step)?;
+            let prompt = match &templates {
+                Some(templates) => Some(build_prompt(
+                    templates, step, &opts.seed, "", &handoff, &root,
+                )?),
+                None => None,
+            };
+            report.plan.push(StepPlan {
+                name: step.name.clone(),
+                skill: step.skill.clone(),
+                action: if decision.skip { "skip" } else { "run" }.to_string(),
+                reason: decision.reason.clone(),
+                inputs,
+                outputs,
+                prompt,
             });
+            if !decision.skip {
+                will_run.push(step);
+            }
+            report.decisions.insert(step.name.clone(), decision);
         }
+        return Ok(report);
+    }
 
-        let mut args = vec!["--cd".to_string(), root.display().to_string()];
-        args.extend(opts.codex_args.clone());
-        if append_prompt {
-            let prompt =
-                build_prompt(&step, &opts.seed, handoff_path.as_ref().unwrap(), &root);
-            args.push(prompt);
+    let templates = PromptTemplates::load(&root)?;
+    let cfg = load_loop_config(&root.join("loopr").join("config"))?;
+    let now = now_rfc3339()?;
+    let mut run_status = match resumed {
+        Some(mut status) => {
+            status.state = RUN_STATE_RUNNING.to_string();
+            status.last_error = None;
+            status.exit_reason = None;
+            status.updated_at = now;
+            status.review = review;
+            status.confirm = confirm;
+            status
         }
+        None => RunStatusFile {
+            version: 1,
+            run_id: new_run_id()?,
+            state: RUN_STATE_RUNNING.to_string(),
+            started_at: now.clone(),
+            updated_at: now,
+            planned_steps: steps.iter().map(|step| step.name.clone()).collect(),
+            completed_steps: Vec::new(),
+            skipped_steps: Vec::new(),
+            current_step: None,
+            seed: opts.seed.clone(),
+            feature: feature_key.clone(),
+            codex_args: opts.codex_args.clone(),
+            force: opts.force,
+            review,
+            confirm,
+            sessions: Vec::new(),
+            last_error: None,
+            exit_reason: None,
+        },
+    };
+    report.run_id = Some(run_status.run_id.clone());
+    write_run_status(&status_path, &run_status)?;
 
-        let run = run_codex(
-            &args,
-            &CodexOptions {
-                loopr_root: Some(root.clone()),
-                mode: CodexMode::Exec,
-            },
-        )?;
-        let err = codex_error(&run);
-        report.last_session = Some(run.session);
-        if let Some(err) = err {
+    let total = steps.len();
+    let mut seed_archived = false;
+    // Errors that the loop does not record itself (prompt build, codex spawn, I/O) still mark the
+    // run failed, so run-status.json never stays `running` after loopr exits.
+    let result = (|| -> LooprResult<()> {
+        for step in steps {
+            let idx = report.executed.len() + report.skipped.len() + 1;
+            let decision = seed_decision(
+                &root,
+                &step,
+                &run_status.seed,
+                evaluate_skip(&root, &step, &run_state, run_status.force)?,
+            )?;
+            if decision.skip {
+                if let Some(progress) = &opts.progress {
+                    progress(ProgressEvent {
+                        step: step.clone(),
+                        index: idx,
+                        total,
+                        status: PROGRESS_SKIPPED.to_string(),
+                    });
+                }
+                report.decisions.insert(step.name.clone(), decision);
+                run_status.skipped_steps.push(step.name.clone());
+                run_status.updated_at = now_rfc3339()?;
+                write_run_status(&status_path, &run_status)?;
+                report.skipped.push(step);
+                continue;
+            }
+            report.decisions.insert(step.name.clone(), decision);
+            run_status.current_step = Some(step.name.clone());
+            run_status.updated_at = now_rfc3339()?;
+            write_run_status(&status_path, &run_status)?;
+            if append_prompt && step.requires_seed && run_status.seed.trim().is_empty() {
+                if let Some(seed) = read_seed(&root)? {
+                    run_status.seed = seed;
+                    write_run_status(&status_path, &run_status)?;
+                } else if opts.edit_seed {
+                    match edit_seed_prompt(&root, &templates) {
+                        Ok(seed) => run_status.seed = seed,
+                        Err(err) => {
+                            return Err(fail_run(
+                                &status_path,
+                                &mut run_status,
+                                "missing_seed",
+                                err,
+                            ));
+                        }
+                    }
+                    write_run_status(&status_path, &run_status)?;
+                }
+                if run_status.seed.trim().is_empty() {
+                    let err = LooprError::new(format!(
+                        "seed prompt required for {} (use --seed-prompt)",
+                        step.name
+                    ));
+                    return Err(fail_run(&status_path, &mut run_status, "missing_seed", err));
+                }
+            }
+            if append_prompt && step.requires_seed && !seed_archived {
+                archive_seed(&root, &run_status.run_id, &run_status.seed, &now_rfc3339()?)?;
+                seed_archived = true;
+            }
+            if let Err(err) = check_step_inputs(&root, &step, &all_steps) {
+                return Err(fail_run(
+                    &status_path,
+                    &mut run_status,
+                    "missing_inputs",
+                    err,
+                ));
+            }
+            if confirm {
+                let ok = confirm_step(&step.name)?;
+                if !ok {
+                    let err = LooprError::new("run cancelled");
+                    return Err(fail_run(&status_path, &mut run_status, "cancelled", err));
+                }
+            }
+            let outputs_before = capture_step_outputs(&root, &step)?;
+            let tree_before = if review { worktree_tree(&root)? } else { None };
+            let input_hashes = hash_step_inputs(&root, &step)?;
+            let orders_before = match &scope {
+                Some(_) => snapshot_orders(&root, &step)?,
+                None => Vec::new(),
+            };
+            let mut feedback = String::new();
+            loop {
+                if let Some(progress) = &opts.progress {
+                    progress(ProgressEvent {
+                        step: step.clone(),
+                        index: idx,
+                        total,
+                        status: PROGRESS_START.to_string(),
+                    });
+                }
+                let mut args = vec!["--cd".to_string(), root.display().to_string()];
+                args.extend(run_status.codex_args.clone());
+                if append_prompt {
+                    let prompt = build_prompt(
+                        &templates,
+                        &step,
+                        &run_status.seed,
+                        &feedback,
+                        handoff_path.as_ref().unwrap(),
+                        &root,
+                    )?;
+                    args.push(prompt);
+                }
+
+                let timeout_minutes = cfg.codex_timeout_for_step(&step.name);
+                let run = run_codex_with_timeout(
+                    &args,
+                    &CodexOptions {
+                        loopr_root: Some(root.clone()),
+                        mode: CodexMode::Exec,
+                    },
+                    Duration::from_secs((timeout_minutes as u64) * 60),
+                )?;
+                let mut err = if run.timed_out {
+                    Some((
+                        "timeout",
+                        LooprError::new(format!(
+                            "step {}: codex timed out after {} minutes",
+                            step.name, timeout_minutes
+                        )),
+                    ))
+                } else {
+                    codex_error(&run).map(|err| ("codex_failed", err))
+                };
+                let mut session_status = None;
+                if err.is_none() {
+                    match check_step_status(&step, &run.session.log_path) {
+                        Ok(status) => session_status = Some(status),
+                        Err(failure) => err = Some(failure),
+                    }
+                }
+                run_status.sessions.push(RunSessionRecord {
+                    step: step.name.clone(),
+                    started_at: run
+                        .session
+                        .started
+                        .format(&Rfc3339)
+                        .map_err(|err| LooprError::new(format!("format time: {}", err)))?,
+                    exit_code: run.exit_code,
+                    status: session_status,
+                    log_path: run.session.log_path.display().to_string(),
+                    meta_path: run.session.meta_path.display().to_string(),
+                });
+                report.last_session = Some(run.session);
+                if err.is_none() {
+                    err = verify_step_outputs(&root, &step, &outputs_before)
+                        .err()
+                        .map(|err| ("missing_outputs", err));
+                }
+                if err.is_none()
+                    && let Some((feature, keys)) = &scope
+                {
+                    err = merge_scoped_orders(&root, &orders_before, &feature.key, keys)
+                        .err()
+                        .map(|err| ("invalid_orders", err));
+                }
+                if err.is_none() {
+                    err = validate_step_orders(&root, &step)
+                        .err()
+                        .map(|err| ("invalid_orders", err));
+                }
+                if let Some((reason, err)) = err {
+                    if let Some(progress) = &opts.progress {
+                        progress(ProgressEvent {
+                            step: step.clone(),
+                            index: idx,
+                            total,
+                            status: PROGRESS_ERROR.to_string(),
+                        });
+                    }
+                    return Err(fail_run(&status_path, &mut run_status, reason, err));
+                }
+                if !review {
+                    break;
+                }
+                let review =
+                    collect_step_review(&root, &step, &outputs_before, tree_before.as_deref())?;
+                print_step_review(&step, &review);
+                let decision = prompt_review(&root, &step, &review)?;
+                if let Some(handoff) = &handoff_path {
+                    record_review(handoff, &step, &decision, &now_rfc3339()?)?;
+                }
+                match decision {
+                    ReviewDecision::Approve(_) => break,
+                    ReviewDecision::Reject(comment) => feedback = comment,
+                    ReviewDecision::Stop => {
+                        let err =
+                            LooprError::new(format!("run stopped during review of {}", step.name));
+                        return Err(fail_run(&status_path, &mut run_status, "cancelled", err));
+                    }
+                }
+            }
+            let output_hashes = hash_step_outputs(&root, &step)?;
+            let now = now_rfc3339()?;
+            record_step_run(&mut run_state, &step, input_hashes, output_hashes, &now);
+            write_run_state(&state_path, &run_state)?;
+            run_status.completed_steps.push(step.name.clone());
+            run_status.current_step = None;
+            run_status.updated_at = now;
+            write_run_status(&status_path, &run_status)?;
             if let Some(progress) = &opts.progress {
                 progress(ProgressEvent {
                     step: step.clone(),
                     index: idx,
                     total,
-                    status: PROGRESS_ERROR.to_string(),
+                    status: PROGRESS_DONE.to_string(),
                 });
             }
-            return Err(err);
+            report.executed.push(step);
         }
-        if let Some(progress) = &opts.progress {
-            progress(ProgressEvent {
-                step: step.clone(),
-                index: idx,
-                total,
-                status: PROGRESS_DONE.to_string(),
-            });
+        Ok(())
+    })();
+    if let Err(err) = result {
+        if run_status.state == RUN_STATE_FAILED {
+            return Err(err);
         }
-        report.executed.push(step);
+        return Err(fail_run(&status_path, &mut run_status, "error", err));
     }
 
+    run_status.state = RUN_STATE_COMPLETE.to_string();
+    run_status.exit_reason = Some("completed".to_string());
+    run_status.updated_at = now_rfc3339()?;
+    write_run_status(&status_path, &run_status)?;
     Ok(report)
 }
 
+fn load_resumable_run(path: &Path) -> LooprResult<RunStatusFile> {
+    let status = load_run_status(path)?.ok_or_else(|| {
+        LooprError::new(format!("no run to resume ({} not found)", path.display()))
+    })?;
+    if status.state == RUN_STATE_COMPLETE {
+        return Err(LooprError::new(format!(
+            "run {} already completed; nothing to resume",
+            status.run_id
+        )));
+    }
+    Ok(status)
+}
+
+pub fn resume_steps(all_steps: &[RunStep], status: &RunStatusFile) -> LooprResult<Vec<RunStep>> {
+    status
+        .remaining_steps()
+        .iter()
+        .map(|name| {
+            find_step(all_steps, name).ok_or_else(|| {
+                LooprError::new(format!(
+                    "cannot resume run {}: step {} is no longer in the pipeline",
+                    status.run_id, name
+                ))
+            })
+        })
+        .collect()
+}
+
+// A step passes when its transcript ends with a status block that is not BLOCKED or ERROR.
+fn check_step_status(
+    step: &RunStep,
+    log_path: &Path,
+) -> Result<String, (&'static str, LooprError)> {
+    let (status, found) =
+        parse_loopr_status_from_log(log_path).map_err(|err| ("missing_status", err))?;
+    if !found {
+        return Err((
+            "missing_status",
+            LooprError::new(format!(
+                "step {}: no {} block in transcript {}",
+                step.name,
+                LOOPR_STATUS_START,
+                log_path.display()
+            )),
+        ));
+    }
+    let summary = if status.summary.is_empty() {
+        "no summary".to_string()
+    } else {
+        status.summary.clone()
+    };
+    match status.status.as_str() {
+        "BLOCKED" => Err((
+            "blocked",
+            LooprError::new(format!("step {} reported BLOCKED: {}", step.name, summary)),
+        )),
+        "ERROR" => Err((
+            "error",
+            LooprError::new(format!("step {} reported ERROR: {}", step.name, summary)),
+        )),
+        _ => Ok(status.status),
+    }
+}
+
+// Records the failure in run-status.json so `loopr run --resume` can pick up at the same step.
+fn fail_run(path: &Path, status: &mut RunStatusFile, reason: &str, err: LooprError) -> LooprError {
+    status.state = RUN_STATE_FAILED.to_string();
+    status.exit_reason = Some(reason.to_string());
+    status.last_error = Some(err.message.clone());
+    if let Ok(now) = now_rfc3339() {
+        status.updated_at = now;
+    }
+    match write_run_status(path, status) {
+        Ok(()) => err,
+        Err(write_err) => write_err,
+    }
+}
+
+pub struct StepOutputState {
+    files: HashMap<String, u64>,
+}
+
+pub fn missing_step_inputs(root: &Path, step: &RunStep) -> LooprResult<Vec<String>> {
+    let mut missing = Vec::new();
+    for input in &step.inputs {
+        if expand_glob(root, input)?.is_empty() {
+            missing.push(input.clone());
+        }
+    }
+    Ok(missing)
+}
+
+pub fn check_step_inputs(root: &Path, step: &RunStep, all_steps: &[RunStep]) -> LooprResult<()> {
+    let missing = missing_step_inputs(root, step)?;
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let details: Vec<String> = missing
+        .iter()
+        .map(|input| match producing_step(all_steps, input) {
+            Some(producer) => format!("{} (produced by step {})", input, producer.name),
+            None => input.clone(),
+        })
+        .collect();
+    Err(LooprError::new(format!(
+        "step {}: missing required inputs: {}",
+        step.name,
+        details.join(", ")
+    )))
+}
+
+pub fn producing_step<'a>(steps: &'a [RunStep], input: &str) -> Option<&'a RunStep> {
+    steps.iter().find(|step| produces_input(step, input))
+}
+
+// A new seed prompt only reaches specs/seed.md once its step starts, so compare it with the
+// archived one here instead of waiting for the input hashes to change.
+fn seed_decision(
+    root: &Path,
+    step: &RunStep,
+    seed: &str,
+    decision: SkipDecision,
+) -> LooprResult<SkipDecision> {
+    if decision.skip
+        && step.requires_seed
+        && !seed.trim().is_empty()
+        && read_seed(root)?.as_deref() != Some(seed.trim())
+    {
+        return Ok(SkipDecision {
+            skip: false,
+            reason: "seed prompt changed".to_string(),
+        });
+    }
+    Ok(decision)
+}
+
+pub fn produces_input(step: &RunStep, input: &str) -> bool {
+    step.outputs.iter().any(|output| {
+        output == input || (!is_glob(input) && is_glob(output) && path_matches(output, input))
+    })
+}
+
+// Inputs that do not exist yet are "pending" when an earlier step in the plan will produce them
+// (the handoff is created when the run starts, specs/seed.md when the seed prompt is archived).
+fn plan_step_inputs(
+    root: &Path,
+    step: &RunStep,
+    all_steps: &[RunStep],
+    will_run: &[&RunStep],
+) -> LooprResult<Vec<PlannedPath>> {
+    let mut planned = Vec::new();
+    for input in &step.inputs {
+        let files = expand_glob(root, input)?;
+        let pending = will_run
+            .iter()
+            .find(|upstream| produces_input(upstream, input));
+        let (status, produced_by) = if !files.is_empty() {
+            (PATH_PRESENT, None)
+        } else if input == HANDOFF_PATH || (input == SEED_PATH && step.requires_seed) {
+            (PATH_PENDING, None)
+        } else if let Some(upstream) = pending {
+            (PATH_PENDING, Some(upstream.name.clone()))
+        } else {
+            (
+                PATH_MISSING,
+                producing_step(all_steps, input).map(|producer| producer.name.clone()),
+            )
+        };
+        planned.push(PlannedPath {
+            pattern: input.clone(),
+            files,
+            status: status.to_string(),
+            produced_by,
+        });
+    }
+    Ok(planned)
+}
+
+fn plan_step_outputs(root: &Path, step: &RunStep) -> LooprResult<Vec<PlannedPath>> {
+    let mut planned = Vec::new();
+    for output in &step.outputs {
+        let files = expand_glob(root, output)?;
+        let status = if files.is_empty() {
+            PATH_MISSING
+        } else {
+            PATH_PRESENT
+        };
+        planned.push(PlannedPath {
+            pattern: output.clone(),
+            files,
+            status: status.to_string(),
+            produced_by: None,
+        });
+    }
+    Ok(planned)
+}
+
+pub fn capture_step_outputs(root: &Path, step: &RunStep) -> LooprResult<StepOutputState> {
+    let mut files = HashMap::new();
+    for output in &step.outputs {
+        for rel in expand_glob(root, output)? {
+            let fingerprint = file_fingerprint(&root.join(&rel))?;
+            files.insert(rel, fingerprint);
+        }
+    }
+    Ok(StepOutputState { files })
+}
+
+pub fn verify_step_outputs(
+    root: &Path,
+    step: &RunStep,
+    before: &StepOutputState,
+) -> LooprResult<()> {
+    let mut untouched = Vec::new();
+    for output in &step.outputs {
+        let mut changed = false;
+        for rel in expand_glob(root, output)? {
+            let fingerprint = file_fingerprint(&root.join(&rel))?;
+            if before.files.get(&rel) != Some(&fingerprint) {
+                changed = true;
+                break;
+            }
+        }
+        if !changed {
+            untouched.push(output.clone());
+        }
+    }
+    if untouched.is_empty() {
+        return Ok(());
+    }
+    Err(LooprError::new(format!(
+        "step {}: declared outputs were not created or modified: {}",
+        step.name,
+        untouched.join(", ")
+    )))
+}
+
+// Output files that differ from the capture taken before the step, flagged true when newly created.
+pub fn changed_step_outputs(
+    root: &Path,
+    step: &RunStep,
+    before: &StepOutputState,
+) -> LooprResult<Vec<(String, bool)>> {
+    let mut changed = Vec::new();
+    for output in &step.outputs {
+        for rel in expand_glob(root, output)? {
+            if changed.iter().any(|(seen, _)| seen == &rel) {
+                continue;
+            }
+            let fingerprint = file_fingerprint(&root.join(&rel))?;
+            match before.files.get(&rel) {
+                None => changed.push((rel, true)),
+                Some(previous) if *previous != fingerprint => changed.push((rel, false)),
+                Some(_) => {}
+            }
+        }
+    }
+    changed.sort();
+    Ok(changed)
+}
+
+fn validate_step_orders(root: &Path, step: &RunStep) -> LooprResult<()> {
+    let produced: Vec<&str> = [FEATURE_ORDER_PATH, TASK_ORDER_PATH, TEST_ORDER_PATH]
+        .into_iter()
+        .filter(|path| step.outputs.iter().any(|output| output == path))
+        .collect();
+    if produced.is_empty() {
+        return Ok(());
+    }
+    validate_orders(root, &produced)?.into_result()
+}
+
+fn file_fingerprint(path: &Path) -> LooprResult<u64> {
+    let metadata = std::fs::metadata(path)
+        .map_err(|err| LooprError::new(format!("metadata {}: {}", path.display(), err)))?;
+    let data = std::fs::read(path)
+        .map_err(|err| LooprError::new(format!("read {}: {}", path.display(), err)))?;
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    if let Ok(modified) = metadata.modified() {
+        modified.hash(&mut hasher);
+    }
+    Ok(hasher.finish())
+}
+
 fn resolve_plan_root(cwd: &Path, override_root: Option<&Path>) -> LooprResult<PathBuf> {
     if let Some(root) = override_root {
         let abs = if root.is_absolute() {
@@ -187,17 +852,97 @@ fn resolve_plan_root(cwd: &Path, override_root: Option<&Path>) -> LooprResult<Pa
     Ok(cwd.to_path_buf())
 }
 
-pub fn plan_steps(opts: &RunOptions) -> LooprResult<Vec<RunStep>> {
-    let steps = default_run_steps();
+pub fn plan_steps(steps: &[RunStep], opts: &RunOptions) -> LooprResult<Vec<RunStep>> {
     if !opts.step.is_empty() {
-        let step = find_step(&steps, &opts.step)
+        let step = find_step(steps, &opts.step)
             .ok_or_else(|| LooprError::new(format!("unknown step: {}", opts.step)))?;
         return Ok(vec![step]);
     }
     if !opts.from.is_empty() || !opts.to.is_empty() {
-        return select_range(&steps, &opts.from, &opts.to);
+        return select_range(steps, &opts.from, &opts.to);
+    }
+    if !opts.feature.is_empty() {
+        return select_range(steps, SCOPED_STEPS[0], "");
+    }
+    Ok(steps.to_vec())
+}
+
+pub fn run_steps_path(root: &Path) -> PathBuf {
+    root.join("loopr").join("steps.yaml")
+}
+
+pub fn load_run_steps(root: &Path) -> LooprResult<Vec<RunStep>> {
+    let path = run_steps_path(root);
+    let data = match std::fs::read_to_string(&path) {
+        Ok(value) => value,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(default_run_steps()),
+        Err(err) => return Err(LooprError::new(format!("read {}: {}", path.display(), err))),
+    };
+    parse_run_steps(&data)
+        .map_err(|err| LooprError::new(format!("invalid {}: {}", path.display(), err)))
+}
+
+pub fn parse_run_steps(data: &str) -> LooprResult<Vec<RunStep>> {
+    let file: RunStepsFile =
+        serde_yaml::from_str(data).map_err(|err| LooprError::new(format!("parse: {}", err)))?;
+    if file.version != 1 {
+        return Err(LooprError::new(format!(
+            "unsupported version {} (expected 1)",
+            file.version
+        )));
     }
-    Ok(steps)
+    validate_run_steps(&file.steps)?;
+    Ok(file.steps)
+}
+
+fn validate_run_steps(steps: &[RunStep]) -> LooprResult<()> {
+    if steps.is_empty() {
+        return Err(LooprError::new("steps must not be empty"));
+    }
+    let mut names = HashSet::new();
+    for (idx, step) in steps.iter().enumerate() {
+        let label = format!("step {}", idx + 1);
+        if step.name.trim().is_empty() {
+            return Err(LooprError::new(format!("{}: name is empty", label)));
+        }
+        if !step
+            .name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+        {
+            return Err(LooprError::new(format!(
+                "{}: invalid name \"{}\" (use letters, digits, '-' or '_')",
+                label, step.name
+            )));
+        }
+        if !names.insert(step.name.clone()) {
+            return Err(LooprError::new(format!(
+                "{}: duplicate step name \"{}\"",
+                label, step.name
+            )));
+        }
+        if step.skill.trim().is_empty() {
+            return Err(LooprError::new(format!(
+                "{} ({}): skill is empty",
+                label, step.name
+            )));
+        }
+        for path in step.inputs.iter().chain(step.outputs.iter()) {
+            if path.trim().is_empty() {
+                return Err(LooprError::new(format!(
+                    "{} ({}): empty input/output path",
+                    label, step.name
+                )));
+            }
+            if Path::new(path).is_absolute() || path.split('/').any(|part| part == "..") {
+                return Err(LooprError::new(format!(
+                    "{} ({}): path must be relative to the Loopr root: {}",
+                    label, step.name, path
+                )));
+            }
+        }
+    }
+    Ok(())
 }
 
 pub fn default_run_steps() -> Vec<RunStep> {
@@ -205,11 +950,15 @@ pub fn default_run_steps() -> Vec<RunStep> {
         RunStep {
             name: "prd".to_string(),
             skill: "loopr-prd".to_string(),
-            inputs: vec!["loopr/state/handoff.md".to_string()],
+            inputs: vec![
+                "loopr/state/handoff.md".to_string(),
+                "specs/seed.md".to_string(),
+            ],
             outputs: vec!["specs/prd.md".to_string()],
             requires_seed: true,
             always_run: false,
             allow_repo_read: false,
+            feature: None,
         },
         RunStep {
             name: "spec".to_string(),
@@ -222,6 +971,7 @@ pub fn default_run_steps() -> Vec<RunStep> {
             requires_seed: false,
             always_run: false,
             allow_repo_read: false,
+            feature: None,
         },
         RunStep {
             name: "features".to_string(),
@@ -237,6 +987,7 @@ pub fn default_run_steps() -> Vec<RunStep> {
             requires_seed: false,
             always_run: false,
             allow_repo_read: false,
+            feature: None,
         },
         RunStep {
             name: "tasks".to_string(),
@@ -253,6 +1004,7 @@ pub fn default_run_steps() -> Vec<RunStep> {
             requires_seed: false,
             always_run: false,
             allow_repo_read: false,
+            feature: None,
         },
         RunStep {
             name: "tests".to_string(),
@@ -269,6 +1021,7 @@ pub fn default_run_steps() -> Vec<RunStep> {
             requires_seed: false,
             always_run: false,
             allow_repo_read: false,
+            feature: None,
         },
         RunStep {
             name: "execute".to_string(),
@@ -284,6 +1037,7 @@ pub fn default_run_steps() -> Vec<RunStep> {
             requires_seed: false,
             always_run: true,
             allow_repo_read: true,
+            feature: None,
         },
     ]
 }
@@ -331,96 +1085,110 @@ pub fn find_step(steps: &[RunStep], name: &str) -> Option<RunStep> {
     steps.get(idx as usize).cloned()
 }
 
-pub fn build_prompt(step: &RunStep, seed: &str, handoff_path: &Path, root: &Path) -> String {
-    let mut lines = Vec::new();
-    lines.push(format!("Loopr step: {}", step.name));
-    lines.extend(build_prompt_lines(step, seed, handoff_path, root));
-    lines.push(String::new());
-    lines.push(format!("Run the prompt: {}", step.skill));
-    lines.join("\n")
-}
-
-pub fn build_prompt_lines(
+pub fn build_prompt(
+    templates: &PromptTemplates,
     step: &RunStep,
     seed: &str,
+    feedback: &str,
     handoff_path: &Path,
     root: &Path,
-) -> Vec<String> {
-    let mut lines = Vec::new();
-    lines.push(format!("Loopr root: {}", root.display()));
-    let docs_index = root.join("loopr").join("state").join("docs-index.txt");
-    if docs_index.exists() {
-        lines.push(format!("Docs index: {}", docs_index.display()));
-    }
-    lines.push(format!("Handoff: {}", handoff_path.display()));
-    lines.push(String::new());
-    lines.push(format!("Prompt: {}", step.skill));
-    lines.push(String::new());
-    lines.push("Allowed inputs:".to_string());
-
+) -> LooprResult<String> {
+    let mut inputs = step.inputs.clone();
+    inputs.push("AGENTS.md".to_string());
+    inputs.push("loopr/state/docs-index.txt".to_string());
     let mut seen = HashSet::new();
-    let mut allowed_inputs = Vec::new();
-    allowed_inputs.extend(step.inputs.iter().cloned());
-    allowed_inputs.push("AGENTS.md".to_string());
-    allowed_inputs.push("loopr/state/docs-index.txt".to_string());
-    for input in allowed_inputs {
-        if seen.insert(input.clone()) {
-            lines.push(format!("- {}", input));
-        }
-    }
-    if step.allow_repo_read {
-        lines.push("- Repo files as needed (read-only).".to_string());
-    }
-    lines.push(String::new());
-    lines.push("Required outputs:".to_string());
-    for output in &step.outputs {
-        lines.push(format!("- {}", output));
-    }
-
-    if step.requires_seed {
-        lines.push(String::new());
-        lines.push("Seed prompt:".to_string());
-        lines.push(seed.to_string());
-    }
+    inputs.retain(|input| seen.insert(input.clone()));
 
-    lines.push(String::new());
-    lines.push("Rules:".to_string());
-    if step.allow_repo_read {
-        lines.push(
-            "- Read the allowed inputs and any repo files needed for implementation.".to_string(),
-        );
-        lines.push("- Avoid broad scans; open only what you need.".to_string());
-    } else {
-        lines.push("- Read only the allowed inputs.".to_string());
-        lines.push("- Do not scan the repo.".to_string());
-    }
-    lines.push(
-        "- If required inputs are missing, stop and ask to run the appropriate step.".to_string(),
-    );
-    lines.push(format!(
-        "- Append a completion note to {} (decisions, open questions, tests).",
-        handoff_path.display()
-    ));
+    let mut ctx = prompt_context(step, handoff_path, root, inputs);
+    ctx.text("seed", seed).text("review_feedback", feedback);
+    templates.render(TEMPLATE_STEP, &ctx)
+}
 
-    lines
+pub fn prompt_context(
+    step: &RunStep,
+    handoff_path: &Path,
+    root: &Path,
+    inputs: Vec<String>,
+) -> TemplateContext {
+    let docs_index = root.join("loopr").join("state").join("docs-index.txt");
+    let mut ctx = TemplateContext::new();
+    ctx.text("step", step.name.clone())
+        .text("skill", step.skill.clone())
+        .text("root", root.display().to_string())
+        .text(
+            "docs_index",
+            if docs_index.exists() {
+                docs_index.display().to_string()
+            } else {
+                String::new()
+            },
+        )
+        .text("handoff", handoff_path.display().to_string())
+        .list("inputs", inputs)
+        .list("outputs", step.outputs.clone())
+        .flag("allow_repo_read", step.allow_repo_read)
+        .flag("requires_seed", step.requires_seed)
+        .text("seed", "")
+        .text("iteration", "")
+        .text("item_key", "")
+        .text("item_type", "")
+        .text("phase", "")
+        .flag("phase_tests", false)
+        .flag("phase_implement", false)
+        .flag("pbt", false)
+        .flag("loop_mode", false)
+        .text("review_feedback", "")
+        .text("previous_failure", "")
+        .text("test_failure", "")
+        .text("attempt", "")
+        .text("max_attempts", "")
+        .text("fix_round", "")
+        .text("max_fix_rounds", "")
+        .text(
+            "feature_key",
+            step.feature
+                .as_ref()
+                .map(|f| f.key.clone())
+                .unwrap_or_default(),
+        )
+        .text(
+            "feature_title",
+            step.feature
+                .as_ref()
+                .map(|f| f.title.clone())
+                .unwrap_or_default(),
+        )
+        .text(
+            "feature_file",
+            step.feature
+                .as_ref()
+                .map(|f| f.file.clone())
+                .unwrap_or_default(),
+        )
+        .text("status_start", LOOPR_STATUS_START)
+        .text("status_end", LOOPR_STATUS_END);
+    ctx
 }
 
 fn ensure_handoff(root: &Path) -> LooprResult<PathBuf> {
-    let path = root.join("loopr").join("state").join("handoff.md");
+    let path = root.join(HANDOFF_PATH);
     if path.exists() {
         return Ok(path);
     }
     if let Some(parent) = path.parent() {
         crate::ops::fs::ensure_dir(parent, 0o755)?;
     }
-    let ts = OffsetDateTime::now_utc()
-        .format(&Rfc3339)
-        .map_err(|err| LooprError::new(format!("format time: {}", err)))?;
-    let header = format!("# Loopr Handoff\n\nInitialized: {}\n\n", ts);
+    let header = format!("# Loopr Handoff\n\nInitialized: {}\n\n", now_rfc3339()?);
     write_file_atomic(&path, header.as_bytes(), 0o644)?;
     Ok(path)
 }
 
+fn now_rfc3339() -> LooprResult<String> {
+    OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .map_err(|err| LooprError::new(format!("format time: {}", err)))
+}
+
 fn confirm_step(name: &str) -> LooprResult<bool> {
     print!("Run step {}? [y/N]: ", name);
     io::stdout()
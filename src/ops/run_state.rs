@@ -0,0 +1,188 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::ops::glob::{expand_glob, path_matches};
+use crate::ops::run::RunStep;
+use crate::{LooprError, LooprResult};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunStateFile {
+    pub version: i32,
+    pub updated_at: String,
+    pub steps: HashMap<String, StepRunRecord>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StepRunRecord {
+    pub completed_at: String,
+    pub inputs: BTreeMap<String, String>,
+    pub outputs: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkipDecision {
+    pub skip: bool,
+    pub reason: String,
+}
+
+pub fn run_state_path(root: &Path) -> PathBuf {
+    root.join("loopr").join("state").join("run-state.json")
+}
+
+pub fn load_run_state(path: &Path, now: &str) -> LooprResult<RunStateFile> {
+    let data = match fs::read_to_string(path) {
+        Ok(value) => value,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(RunStateFile {
+                version: 1,
+                updated_at: now.to_string(),
+                steps: HashMap::new(),
+            });
+        }
+        Err(err) => return Err(LooprError::new(format!("read {}: {}", path.display(), err))),
+    };
+    serde_json::from_str(&data)
+        .map_err(|err| LooprError::new(format!("parse {}: {}", path.display(), err)))
+}
+
+pub fn write_run_state(path: &Path, state: &RunStateFile) -> LooprResult<()> {
+    let mut data = serde_json::to_vec_pretty(state)
+        .map_err(|err| LooprError::new(format!("serialize {}: {}", path.display(), err)))?;
+    data.push(b'\n');
+    crate::ops::fs::write_file_atomic(path, &data, 0o644)
+}
+
+// Runtime state (handoff log, transcripts) changes on every step, so it never counts as an input.
+pub fn is_tracked_input(pattern: &str) -> bool {
+    !pattern.starts_with("loopr/state/")
+}
+
+// Files matching the step's own outputs are left out: input globs like specs/feature-*.md also
+// match the files the step writes, which would otherwise look like changed inputs on every run.
+pub fn hash_step_inputs(root: &Path, step: &RunStep) -> LooprResult<BTreeMap<String, String>> {
+    let patterns: Vec<&String> = step
+        .inputs
+        .iter()
+        .filter(|pattern| is_tracked_input(pattern))
+        .collect();
+    let mut hashes = hash_patterns(root, &patterns)?;
+    hashes.retain(|path, _| {
+        !step
+            .outputs
+            .iter()
+            .any(|pattern| path_matches(pattern, path))
+    });
+    Ok(hashes)
+}
+
+pub fn hash_step_outputs(root: &Path, step: &RunStep) -> LooprResult<BTreeMap<String, String>> {
+    let patterns: Vec<&String> = step.outputs.iter().collect();
+    hash_patterns(root, &patterns)
+}
+
+pub fn hash_file(path: &Path) -> LooprResult<String> {
+    let data = fs::read(path)
+        .map_err(|err| LooprError::new(format!("read {}: {}", path.display(), err)))?;
+    let digest = Sha256::digest(&data);
+    Ok(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+fn hash_patterns(root: &Path, patterns: &[&String]) -> LooprResult<BTreeMap<String, String>> {
+    let mut hashes = BTreeMap::new();
+    for pattern in patterns {
+        for rel in expand_glob(root, pattern)? {
+            let hash = hash_file(&root.join(&rel))?;
+            hashes.insert(rel, hash);
+        }
+    }
+    Ok(hashes)
+}
+
+pub fn record_step_run(
+    state: &mut RunStateFile,
+    step: &RunStep,
+    inputs: BTreeMap<String, String>,
+    outputs: BTreeMap<String, String>,
+    now: &str,
+) {
+    state.steps.insert(
+        step.state_key(),
+        StepRunRecord {
+            completed_at: now.to_string(),
+            inputs,
+            outputs,
+        },
+    );
+    state.updated_at = now.to_string();
+}
+
+pub fn evaluate_skip(
+    root: &Path,
+    step: &RunStep,
+    state: &RunStateFile,
+    force: bool,
+) -> LooprResult<SkipDecision> {
+    if force {
+        return Ok(run_decision("--force"));
+    }
+    if step.always_run {
+        return Ok(run_decision("always_run"));
+    }
+    let record = match state.steps.get(&step.state_key()) {
+        Some(value) => value,
+        None => return Ok(run_decision("no previous run recorded")),
+    };
+    if step.outputs.is_empty() {
+        return Ok(run_decision("step declares no outputs"));
+    }
+
+    let inputs = hash_step_inputs(root, step)?;
+    let changed = changed_paths(&record.inputs, &inputs);
+    if !changed.is_empty() {
+        return Ok(run_decision(&format!(
+            "inputs changed: {}",
+            changed.join(", ")
+        )));
+    }
+
+    for pattern in &step.outputs {
+        if expand_glob(root, pattern)?.is_empty() {
+            return Ok(run_decision(&format!("output missing: {}", pattern)));
+        }
+    }
+
+    Ok(SkipDecision {
+        skip: true,
+        reason: format!("up to date (last run {})", record.completed_at),
+    })
+}
+
+fn run_decision(reason: &str) -> SkipDecision {
+    SkipDecision {
+        skip: false,
+        reason: reason.to_string(),
+    }
+}
+
+pub fn changed_paths(
+    recorded: &BTreeMap<String, String>,
+    current: &BTreeMap<String, String>,
+) -> Vec<String> {
+    let mut changed = Vec::new();
+    for (path, hash) in current {
+        if recorded.get(path) != Some(hash) {
+            changed.push(path.clone());
+        }
+    }
+    for path in recorded.keys() {
+        if !current.contains_key(path) {
+            changed.push(path.clone());
+        }
+    }
+    changed.sort();
+    changed
+}
@@ -0,0 +1,84 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{LooprError, LooprResult};
+
+pub const RUN_STATE_RUNNING: &str = "running";
+pub const RUN_STATE_FAILED: &str = "failed";
+pub const RUN_STATE_COMPLETE: &str = "complete";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunStatusFile {
+    pub version: i32,
+    pub run_id: String,
+    pub state: String,
+    pub started_at: String,
+    pub updated_at: String,
+    pub planned_steps: Vec<String>,
+    pub completed_steps: Vec<String>,
+    pub skipped_steps: Vec<String>,
+    pub current_step: Option<String>,
+    pub seed: String,
+    #[serde(default)]
+    pub feature: Option<String>,
+    pub codex_args: Vec<String>,
+    pub force: bool,
+    #[serde(default)]
+    pub review: bool,
+    #[serde(default)]
+    pub confirm: bool,
+    pub sessions: Vec<RunSessionRecord>,
+    pub last_error: Option<String>,
+    #[serde(default)]
+    pub exit_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunSessionRecord {
+    pub step: String,
+    pub started_at: String,
+    pub exit_code: i32,
+    #[serde(default)]
+    pub status: Option<String>,
+    pub log_path: String,
+    pub meta_path: String,
+}
+
+impl RunStatusFile {
+    pub fn is_finished(&self, step: &str) -> bool {
+        self.completed_steps.iter().any(|name| name == step)
+            || self.skipped_steps.iter().any(|name| name == step)
+    }
+
+    pub fn remaining_steps(&self) -> Vec<String> {
+        self.planned_steps
+            .iter()
+            .filter(|name| !self.is_finished(name))
+            .cloned()
+            .collect()
+    }
+}
+
+pub fn run_status_path(root: &Path) -> PathBuf {
+    root.join("loopr").join("state").join("run-status.json")
+}
+
+pub fn load_run_status(path: &Path) -> LooprResult<Option<RunStatusFile>> {
+    let data = match fs::read_to_string(path) {
+        Ok(value) => value,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(LooprError::new(format!("read {}: {}", path.display(), err))),
+    };
+    serde_json::from_str(&data)
+        .map(Some)
+        .map_err(|err| LooprError::new(format!("parse {}: {}", path.display(), err)))
+}
+
+pub fn write_run_status(path: &Path, status: &RunStatusFile) -> LooprResult<()> {
+    let mut data = serde_json::to_vec_pretty(status)
+        .map_err(|err| LooprError::new(format!("serialize {}: {}", path.display(), err)))?;
+    data.push(b'\n');
+    crate::ops::fs::write_file_atomic(path, &data, 0o644)
+}
@@ -0,0 +1,71 @@
+use std::path::{Path, PathBuf};
+
+use crate::ops::editor::open_in_editor;
+use crate::ops::fs::write_file_atomic;
+use crate::ops::prompt_template::{PromptTemplates, TEMPLATE_SEED, TemplateContext};
+use crate::{LooprError, LooprResult};
+
+pub const SEED_PATH: &str = "specs/seed.md";
+pub const SEED_SCISSORS: &str = "# ------------------------ >8 ------------------------";
+// Ends the header archive_seed writes; everything below it is the seed, exactly as given.
+pub const SEED_MARKER: &str = "<!-- loopr: seed prompt below -->";
+
+pub fn seed_history_dir(root: &Path) -> PathBuf {
+    root.join("loopr").join("state").join("seeds")
+}
+
+// Opens the seed template in $EDITOR and returns the text above the scissors line.
+pub fn edit_seed_prompt(root: &Path, templates: &PromptTemplates) -> LooprResult<String> {
+    let body = templates.render(TEMPLATE_SEED, &TemplateContext::new())?;
+    let path = root.join("loopr").join("state").join("SEED_EDITMSG.md");
+    write_file_atomic(&path, body.as_bytes(), 0o644)?;
+    let edited = open_in_editor(root, &[path.display().to_string()]).and_then(|()| {
+        std::fs::read_to_string(&path)
+            .map_err(|err| LooprError::new(format!("read {}: {}", path.display(), err)))
+    });
+    let _ = std::fs::remove_file(&path);
+    Ok(strip_seed_template(&edited?))
+}
+
+pub fn strip_seed_template(text: &str) -> String {
+    let kept: Vec<&str> = text
+        .lines()
+        .take_while(|line| line.trim_end() != SEED_SCISSORS)
+        .collect();
+    kept.join("\n").trim().to_string()
+}
+
+// Writes the seed to specs/seed.md and keeps a copy per run under loopr/state/seeds. specs/seed.md
+// is an input of the prd step, so it is left alone when it already holds the same seed.
+pub fn archive_seed(root: &Path, run_id: &str, seed: &str, now: &str) -> LooprResult<PathBuf> {
+    let body = format!(
+        "# Seed Prompt\n\n- Run: {}\n- Recorded: {}\n\n{}\n\n{}\n",
+        run_id,
+        now,
+        SEED_MARKER,
+        seed.trim()
+    );
+    if read_seed(root)?.as_deref() != Some(seed.trim()) {
+        write_file_atomic(&root.join(SEED_PATH), body.as_bytes(), 0o644)?;
+    }
+    let history = seed_history_dir(root).join(format!("{}.md", run_id));
+    write_file_atomic(&history, body.as_bytes(), 0o644)?;
+    Ok(history)
+}
+
+// The seed text in specs/seed.md: what follows the marker line, or the whole file when it has no
+// marker (written by hand).
+pub fn read_seed(root: &Path) -> LooprResult<Option<String>> {
+    let path = root.join(SEED_PATH);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(value) => value,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(LooprError::new(format!("read {}: {}", path.display(), err))),
+    };
+    let seed = match text.lines().position(|line| line.trim() == SEED_MARKER) {
+        Some(at) => text.lines().skip(at + 1).collect::<Vec<_>>().join("\n"),
+        None => text,
+    };
+    let seed = seed.trim().to_string();
+    Ok(Some(seed).filter(|seed| !seed.is_empty()))
+}
@@ -0,0 +1,317 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use crate::ops::fs::write_file_atomic;
+use crate::version::VERSION;
+use crate::{LooprError, LooprResult};
+
+pub const SKILL_FILE: &str = "SKILL.md";
+pub const SKILL_STAMP_FILE: &str = ".loopr-skill.json";
+
+const BUNDLED_SKILLS: &[(&str, &str)] = &[
+    ("loopr-prd", include_str!("../skills/loopr-prd/SKILL.md")),
+    (
+        "loopr-specify",
+        include_str!("../skills/loopr-specify/SKILL.md"),
+    ),
+    (
+        "loopr-features",
+        include_str!("../skills/loopr-features/SKILL.md"),
+    ),
+    (
+        "loopr-tasks",
+        include_str!("../skills/loopr-tasks/SKILL.md"),
+    ),
+    (
+        "loopr-tests",
+        include_str!("../skills/loopr-tests/SKILL.md"),
+    ),
+    (
+        "loopr-execute",
+        include_str!("../skills/loopr-execute/SKILL.md"),
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkillState {
+    Missing,
+    Current,
+    Outdated,
+    Modified,
+    Unmanaged,
+}
+
+impl SkillState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkillState::Missing => "missing",
+            SkillState::Current => "current",
+            SkillState::Outdated => "outdated",
+            SkillState::Modified => "modified",
+            SkillState::Unmanaged => "unmanaged",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SkillStamp {
+    pub name: String,
+    pub loopr_version: String,
+    pub sha256: String,
+    pub installed_at: String,
+}
+
+#[derive(Debug)]
+pub struct SkillStatus {
+    pub name: String,
+    pub path: PathBuf,
+    pub state: SkillState,
+    pub stamp: Option<SkillStamp>,
+}
+
+#[derive(Debug, Default)]
+pub struct SkillInstallReport {
+    pub written: Vec<SkillStatus>,
+    pub skipped: Vec<(SkillStatus, String)>,
+}
+
+pub fn bundled_skill_names() -> Vec<&'static str> {
+    BUNDLED_SKILLS.iter().map(|(name, _)| *name).collect()
+}
+
+pub fn bundled_skill(name: &str) -> Option<&'static str> {
+    BUNDLED_SKILLS
+        .iter()
+        .find(|(value, _)| *value == name)
+        .map(|(_, body)| *body)
+}
+
+pub fn repo_skills_dir(root: &Path) -> PathBuf {
+    root.join(".codex").join("skills")
+}
+
+pub fn global_skills_dir() -> LooprResult<PathBuf> {
+    if let Some(home) = std::env::var_os("CODEX_HOME")
+        && !home.is_empty()
+    {
+        return Ok(PathBuf::from(home).join("skills"));
+    }
+    let home = std::env::var_os("HOME")
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| LooprError::new("HOME is not set (set CODEX_HOME or use --repo)"))?;
+    Ok(PathBuf::from(home).join(".codex").join("skills"))
+}
+
+// Bundled skills among `skills` that are installed neither repo-locally nor globally.
+pub fn missing_skills(root: &Path, skills: &[String]) -> LooprResult<Vec<String>> {
+    let mut dirs = vec![repo_skills_dir(root)];
+    if let Ok(dir) = global_skills_dir() {
+        dirs.push(dir);
+    }
+    let mut missing = Vec::new();
+    for skill in skills {
+        if bundled_skill(skill).is_none() || missing.contains(skill) {
+            continue;
+        }
+        let mut found = false;
+        for dir in &dirs {
+            if skill_status(dir, skill)?.state != SkillState::Missing {
+                found = true;
+                break;
+            }
+        }
+        if !found {
+            missing.push(skill.clone());
+        }
+    }
+    Ok(missing)
+}
+
+pub fn skill_status(dir: &Path, name: &str) -> LooprResult<SkillStatus> {
+    let body =
+        bundled_skill(name).ok_or_else(|| LooprError::new(format!("unknown skill: {}", name)))?;
+    let skill_dir = dir.join(name);
+    let path = skill_dir.join(SKILL_FILE);
+    let installed = match fs::read(&path) {
+        Ok(value) => value,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(SkillStatus {
+                name: name.to_string(),
+                path,
+                state: SkillState::Missing,
+                stamp: None,
+            });
+        }
+        Err(err) => return Err(LooprError::new(format!("read {}: {}", path.display(), err))),
+    };
+    let stamp = load_stamp(&skill_dir.join(SKILL_STAMP_FILE))?;
+    let installed_hash = sha256_hex(&installed);
+    let state = match &stamp {
+        None => {
+            if installed_hash == sha256_hex(body.as_bytes()) {
+                SkillState::Current
+            } else {
+                SkillState::Unmanaged
+            }
+        }
+        Some(stamp) if stamp.sha256 != installed_hash => SkillState::Modified,
+        Some(_) if installed_hash != sha256_hex(body.as_bytes()) => SkillState::Outdated,
+        Some(_) => SkillState::Current,
+    };
+    Ok(SkillStatus {
+        name: name.to_string(),
+        path,
+        state,
+        stamp,
+    })
+}
+
+pub fn list_skills(dir: &Path) -> LooprResult<Vec<SkillStatus>> {
+    bundled_skill_names()
+        .into_iter()
+        .map(|name| skill_status(dir, name))
+        .collect()
+}
+
+// Install writes missing skills; update also refreshes outdated ones. Skills with local edits (or
+// installed without a stamp) are only overwritten with `force`.
+pub fn install_skills(
+    dir: &Path,
+    names: &[String],
+    update: bool,
+    force: bool,
+) -> LooprResult<SkillInstallReport> {
+    let names = select_skills(names)?;
+    let mut report = SkillInstallReport::default();
+    for name in names {
+        let status = skill_status(dir, &name)?;
+        let skip_reason = match status.state {
+            _ if force => None,
+            SkillState::Missing => None,
+            SkillState::Outdated if update => None,
+            SkillState::Outdated => Some("outdated (run loopr skills update)"),
+            SkillState::Current => Some("up to date"),
+            SkillState::Modified => Some("local changes (use --force to overwrite)"),
+            SkillState::Unmanaged => Some("not installed by loopr (use --force to overwrite)"),
+        };
+        if let Some(reason) = skip_reason {
+            report.skipped.push((status, reason.to_string()));
+            continue;
+        }
+        write_skill(dir, &name)?;
+        report.written.push(skill_status(dir, &name)?);
+    }
+    Ok(report)
+}
+
+pub fn diff_skill(dir: &Path, name: &str) -> LooprResult<Option<String>> {
+    let status = skill_status(dir, name)?;
+    if status.state == SkillState::Missing || status.state == SkillState::Current {
+        return Ok(None);
+    }
+    let installed = fs::read_to_string(&status.path)
+        .map_err(|err| LooprError::new(format!("read {}: {}", status.path.display(), err)))?;
+    let bundled = bundled_skill(name).unwrap_or_default();
+    let mut out = format!(
+        "--- {} (installed)\n+++ {} (loopr {})\n",
+        status.path.display(),
+        name,
+        VERSION
+    );
+    out.push_str(&line_diff(&installed, bundled));
+    Ok(Some(out))
+}
+
+fn select_skills(names: &[String]) -> LooprResult<Vec<String>> {
+    if names.is_empty() {
+        return Ok(bundled_skill_names()
+            .into_iter()
+            .map(|name| name.to_string())
+            .collect());
+    }
+    for name in names {
+        if bundled_skill(name).is_none() {
+            return Err(LooprError::new(format!(
+                "unknown skill: {} (bundled: {})",
+                name,
+                bundled_skill_names().join(", ")
+            )));
+        }
+    }
+    Ok(names.to_vec())
+}
+
+fn write_skill(dir: &Path, name: &str) -> LooprResult<()> {
+    let body = bundled_skill(name).unwrap_or_default();
+    let skill_dir = dir.join(name);
+    write_file_atomic(&skill_dir.join(SKILL_FILE), body.as_bytes(), 0o644)?;
+    let stamp = SkillStamp {
+        name: name.to_string(),
+        loopr_version: VERSION.to_string(),
+        sha256: sha256_hex(body.as_bytes()),
+        installed_at: OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .map_err(|err| LooprError::new(format!("format time: {}", err)))?,
+    };
+    let path = skill_dir.join(SKILL_STAMP_FILE);
+    let mut data = serde_json::to_vec_pretty(&stamp)
+        .map_err(|err| LooprError::new(format!("serialize {}: {}", path.display(), err)))?;
+    data.push(b'\n');
+    write_file_atomic(&path, &data, 0o644)
+}
+
+fn load_stamp(path: &Path) -> LooprResult<Option<SkillStamp>> {
+    let data = match fs::read_to_string(path) {
+        Ok(value) => value,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(LooprError::new(format!("read {}: {}", path.display(), err))),
+    };
+    serde_json::from_str(&data)
+        .map(Some)
+        .map_err(|err| LooprError::new(format!("parse {}: {}", path.display(), err)))
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// Line-level diff from the longest common subsequence; skill files are small enough for the
+// quadratic table.
+fn line_diff(old: &str, new: &str) -> String {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            out.push_str(&format!(" {}\n", a[i]));
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            out.push_str(&format!("+{}\n", b[j]));
+            j += 1;
+        } else {
+            out.push_str(&format!("-{}\n", a[i]));
+            i += 1;
+        }
+    }
+    out
+}
@@ -0,0 +1,540 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use time::OffsetDateTime;
+use time::macros::format_description;
+
+use crate::{LooprError, LooprResult};
+
+pub const SNAPSHOT_REF_PREFIX: &str = "refs/loopr";
+
+const SNAPSHOT_AUTHOR_NAME: &str = "loopr";
+const SNAPSHOT_AUTHOR_EMAIL: &str = "loopr@localhost";
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub run_id: String,
+    pub iteration: i64,
+    pub item_key: Option<String>,
+    pub commit: String,
+    pub reference: String,
+    // The iteration's item was handed back without being merged; the tree is unchanged from the
+    // iteration before.
+    pub released: bool,
+}
+
+pub struct Workspace {
+    pub path: PathBuf,
+    worktree: PathBuf,
+    base: String,
+}
+
+impl Workspace {
+    // The tree the workspace was created from.
+    pub fn base_tree(&self) -> &str {
+        &self.base
+    }
+}
+
+pub struct RestoreReport {
+    pub run_id: String,
+    pub iteration: i64,
+    pub backup_ref: String,
+    pub removed: Vec<String>,
+}
+
+pub fn new_run_id() -> LooprResult<String> {
+    let ts = OffsetDateTime::now_utc()
+        .format(&format_description!(
+            "[year][month][day]-[hour][minute][second]"
+        ))
+        .map_err(|err| LooprError::new(format!("format time: {}", err)))?;
+    Ok(format!("{}-{}", ts, random_hex(3)?))
+}
+
+pub fn is_git_work_tree(root: &Path) -> bool {
+    match git_output(root, &["rev-parse", "--is-inside-work-tree"], None) {
+        Ok(value) => value.trim() == "true",
+        Err(_) => false,
+    }
+}
+
+pub fn capture_snapshot(
+    root: &Path,
+    run_id: &str,
+    iteration: i64,
+    item_key: Option<&str>,
+) -> LooprResult<Option<Snapshot>> {
+    write_snapshot(root, run_id, iteration, item_key, false)
+}
+
+// Records the iteration of an item that was released without being merged, so the run's
+// iterations stay contiguous and diff/restore can say what happened to it.
+pub fn capture_released_snapshot(
+    root: &Path,
+    run_id: &str,
+    iteration: i64,
+    item_key: &str,
+) -> LooprResult<Option<Snapshot>> {
+    write_snapshot(root, run_id, iteration, Some(item_key), true)
+}
+
+fn write_snapshot(
+    root: &Path,
+    run_id: &str,
+    iteration: i64,
+    item_key: Option<&str>,
+    released: bool,
+) -> LooprResult<Option<Snapshot>> {
+    if !is_git_work_tree(root) {
+        return Ok(None);
+    }
+    let tree = write_worktree_tree(root)?;
+
+    let reference = snapshot_ref(run_id, iteration);
+    let parent = if iteration > 0 {
+        resolve_commit(root, &snapshot_ref(run_id, iteration - 1))
+    } else {
+        None
+    }
+    .or_else(|| resolve_commit(root, "HEAD"));
+
+    let mut message = format!("loopr snapshot {} iteration {}\n\n", run_id, iteration);
+    message.push_str(&format!("Loopr-Run: {}\n", run_id));
+    message.push_str(&format!("Loopr-Iteration: {}\n", iteration));
+    if let Some(key) = item_key {
+        message.push_str(&format!("Loopr-Item: {}\n", key));
+    }
+    if released {
+        message.push_str("Loopr-Released: true\n");
+    }
+    let commit = commit_tree(root, &tree, parent.as_deref(), &message)?;
+    git_output(root, &["update-ref", &reference, &commit], None)?;
+
+    Ok(Some(Snapshot {
+        run_id: run_id.to_string(),
+        iteration,
+        item_key: item_key.map(str::to_string),
+        commit,
+        reference,
+        released,
+    }))
+}
+
+pub fn list_snapshots(root: &Path, run_id: Option<&str>) -> LooprResult<Vec<Snapshot>> {
+    if !is_git_work_tree(root) {
+        return Err(LooprError::new(format!(
+            "{} is not inside a git work tree",
+            root.display()
+        )));
+    }
+    let format = "%(refname)%1f%(objectname)%1f%(contents:body)%1e";
+    let output = git_output(
+        root,
+        &[
+            "for-each-ref",
+            &format!("--format={}", format),
+            SNAPSHOT_REF_PREFIX,
+        ],
+        None,
+    )?;
+
+    let mut snapshots = Vec::new();
+    for record in output.split('\u{1e}') {
+        let record = record.trim_start_matches('\n');
+        if record.is_empty() {
+            continue;
+        }
+        let mut fields = record.split('\u{1f}');
+        let reference = fields.next().unwrap_or_default();
+        let commit = fields.next().unwrap_or_default();
+        let body = fields.next().unwrap_or_default();
+        let rest = match reference.strip_prefix(&format!("{}/", SNAPSHOT_REF_PREFIX)) {
+            Some(value) => value,
+            None => continue,
+        };
+        let (run, iteration) = match rest.rsplit_once('/') {
+            Some(value) => value,
+            None => continue,
+        };
+        let iteration: i64 = match iteration.parse() {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+        snapshots.push(Snapshot {
+            run_id: run.to_string(),
+            iteration,
+            item_key: parse_trailer(body, "Loopr-Item"),
+            commit: commit.to_string(),
+            reference: reference.to_string(),
+            released: parse_trailer(body, "Loopr-Released").as_deref() == Some("true"),
+        });
+    }
+
+    let run_id = match run_id {
+        Some(value) => value.to_string(),
+        None => match snapshots.iter().map(|snap| snap.run_id.clone()).max() {
+            Some(value) => value,
+            None => return Err(LooprError::new("no loopr snapshots found (run loopr loop)")),
+        },
+    };
+    let mut selected: Vec<Snapshot> = snapshots
+        .into_iter()
+        .filter(|snap| snap.run_id == run_id)
+        .collect();
+    if selected.is_empty() {
+        return Err(LooprError::new(format!(
+            "no snapshots found for run {}",
+            run_id
+        )));
+    }
+    selected.sort_by_key(|snap| snap.iteration);
+    Ok(selected)
+}
+
+pub fn diff_iteration(root: &Path, run_id: Option<&str>, iteration: i64) -> LooprResult<String> {
+    let snapshots = list_snapshots(root, run_id)?;
+    diff_snapshot(root, &snapshots, iteration)
+}
+
+pub fn diff_item(root: &Path, run_id: Option<&str>, item_key: &str) -> LooprResult<String> {
+    let snapshots = list_snapshots(root, run_id)?;
+    let iterations: Vec<i64> = snapshots
+        .iter()
+        .filter(|snap| snap.item_key.as_deref() == Some(item_key) && !snap.released)
+        .map(|snap| snap.iteration)
+        .collect();
+    if iterations.is_empty() {
+        return Err(LooprError::new(format!(
+            "no iterations recorded for item {} in run {}",
+            item_key, snapshots[0].run_id
+        )));
+    }
+    let mut out = String::new();
+    for iteration in iterations {
+        out.push_str(&format!("# iteration {} ({})\n", iteration, item_key));
+        out.push_str(&diff_snapshot(root, &snapshots, iteration)?);
+    }
+    Ok(out)
+}
+
+pub fn restore_iteration(
+    root: &Path,
+    run_id: Option<&str>,
+    iteration: i64,
+) -> LooprResult<RestoreReport> {
+    let snapshots = list_snapshots(root, run_id)?;
+    let target = find_snapshot(&snapshots, iteration)?;
+    check_recorded(target)?;
+    let toplevel = PathBuf::from(git_output(root, &["rev-parse", "--show-toplevel"], None)?.trim());
+
+    let current_tree = write_worktree_tree(root)?;
+    let backup_ref = format!(
+        "{}/{}/pre-restore-{}",
+        SNAPSHOT_REF_PREFIX,
+        target.run_id,
+        OffsetDateTime::now_utc().unix_timestamp()
+    );
+    let backup = commit_tree(
+        root,
+        &current_tree,
+        resolve_commit(root, "HEAD").as_deref(),
+        &format!(
+            "loopr pre-restore backup (restoring {} iteration {})\n",
+            target.run_id, iteration
+        ),
+    )?;
+    git_output(root, &["update-ref", &backup_ref, &backup], None)?;
+
+    let removed_out = git_output(
+        root,
+        &[
+            "diff-tree",
+            "-r",
+            "--name-only",
+            "--no-renames",
+            "--diff-filter=D",
+            &current_tree,
+            &target.commit,
+        ],
+        None,
+    )?;
+    let mut removed = Vec::new();
+    for rel in removed_out.lines().filter(|line| !line.is_empty()) {
+        let path = toplevel.join(rel);
+        match fs::remove_file(&path) {
+            Ok(()) => removed.push(rel.to_string()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(LooprError::new(format!(
+                    "remove {}: {}",
+                    path.display(),
+                    err
+                )));
+            }
+        }
+    }
+
+    let index = TempGitFile::new(root, "loopr-snapshot-index")?;
+    git_output(&toplevel, &["read-tree", &target.commit], Some(&index.path))?;
+    git_output(
+        &toplevel,
+        &["checkout-index", "-a", "-f"],
+        Some(&index.path),
+    )?;
+
+    Ok(RestoreReport {
+        run_id: target.run_id.clone(),
+        iteration,
+        backup_ref,
+        removed,
+    })
+}
+
+// Tree id of the current working tree (tracked and untracked, minus ignored files), or None
+// outside git. Nothing is stored under refs/.
+pub fn worktree_tree(root: &Path) -> LooprResult<Option<String>> {
+    if !is_git_work_tree(root) {
+        return Ok(None);
+    }
+    write_worktree_tree(root).map(Some)
+}
+
+pub fn diff_stat_since(root: &Path, tree: &str) -> LooprResult<String> {
+    let current = write_worktree_tree(root)?;
+    git_output(root, &["diff", "--stat", tree, &current], None)
+}
+
+// Files under `root` that the working tree added or modified since `tree`, relative to `root`.
+pub fn changed_files_since(root: &Path, tree: &str) -> LooprResult<Vec<String>> {
+    let current = write_worktree_tree(root)?;
+    let output = git_output(
+        root,
+        &[
+            "diff",
+            "--name-only",
+            "--relative",
+            "--diff-filter=AMR",
+            tree,
+            &current,
+        ],
+        None,
+    )?;
+    Ok(output
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+// A detached git worktree at `dir` holding the current working tree (uncommitted and untracked
+// files included, ignored files left out). `path` is `root`'s directory inside it.
+pub fn create_workspace(root: &Path, dir: &Path, label: &str) -> LooprResult<Workspace> {
+    if !is_git_work_tree(root) {
+        return Err(LooprError::new(format!(
+            "{} is not in a git work tree (workspaces are git worktrees)",
+            root.display()
+        )));
+    }
+    let prefix = git_output(root, &["rev-parse", "--show-prefix"], None)?;
+    let base = write_worktree_tree(root)?;
+    let commit = commit_tree(
+        root,
+        &base,
+        resolve_commit(root, "HEAD").as_deref(),
+        &format!("loopr workspace {}\n", label),
+    )?;
+    if let Some(parent) = dir.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|err| LooprError::new(format!("create {}: {}", parent.display(), err)))?;
+    }
+    let dir_arg = dir.display().to_string();
+    git_output(
+        root,
+        &["worktree", "add", "--detach", &dir_arg, &commit],
+        None,
+    )?;
+    Ok(Workspace {
+        path: dir.join(prefix.trim()),
+        worktree: dir.to_path_buf(),
+        base,
+    })
+}
+
+// Applies everything the workspace changed since it was created to the repo's working tree.
+// Fails without touching the repo when the changes do not apply cleanly.
+pub fn merge_workspace(root: &Path, workspace: &Workspace) -> LooprResult<()> {
+    let after = write_worktree_tree(&workspace.worktree)?;
+    if after == workspace.base {
+        return Ok(());
+    }
+    let patch = git_output(root, &["diff", "--binary", &workspace.base, &after], None)?;
+    let patch_file = TempGitFile::new(root, "loopr-workspace-patch")?;
+    fs::write(&patch_file.path, patch)
+        .map_err(|err| LooprError::new(format!("write {}: {}", patch_file.path.display(), err)))?;
+    let toplevel = PathBuf::from(git_output(root, &["rev-parse", "--show-toplevel"], None)?.trim());
+    let patch_arg = patch_file.path.display().to_string();
+    git_output(&toplevel, &["apply", "--binary", &patch_arg], None)?;
+    Ok(())
+}
+
+pub fn remove_workspace(root: &Path, workspace: &Workspace) -> LooprResult<()> {
+    let dir_arg = workspace.worktree.display().to_string();
+    git_output(root, &["worktree", "remove", "--force", &dir_arg], None)?;
+    if let Some(parent) = workspace.worktree.parent() {
+        let _ = fs::remove_dir(parent);
+    }
+    Ok(())
+}
+
+fn diff_snapshot(root: &Path, snapshots: &[Snapshot], iteration: i64) -> LooprResult<String> {
+    if iteration <= 0 {
+        return Err(LooprError::new(format!(
+            "invalid iteration {} (iterations start at 1)",
+            iteration
+        )));
+    }
+    let before = find_snapshot(snapshots, iteration - 1)?;
+    let after = find_snapshot(snapshots, iteration)?;
+    check_recorded(after)?;
+    git_output(root, &["diff", &before.commit, &after.commit], None)
+}
+
+fn find_snapshot(snapshots: &[Snapshot], iteration: i64) -> LooprResult<&Snapshot> {
+    snapshots
+        .iter()
+        .find(|snap| snap.iteration == iteration)
+        .ok_or_else(|| {
+            LooprError::new(format!(
+                "no snapshot for iteration {} in run {}",
+                iteration,
+                snapshots
+                    .first()
+                    .map(|snap| snap.run_id.as_str())
+                    .unwrap_or("")
+            ))
+        })
+}
+
+fn check_recorded(snapshot: &Snapshot) -> LooprResult<()> {
+    if !snapshot.released {
+        return Ok(());
+    }
+    Err(LooprError::new(format!(
+        "iteration {} of run {} was skipped: item {} was released without being merged",
+        snapshot.iteration,
+        snapshot.run_id,
+        snapshot.item_key.as_deref().unwrap_or("?")
+    )))
+}
+
+fn snapshot_ref(run_id: &str, iteration: i64) -> String {
+    format!("{}/{}/{}", SNAPSHOT_REF_PREFIX, run_id, iteration)
+}
+
+fn write_worktree_tree(root: &Path) -> LooprResult<String> {
+    let index = TempGitFile::new(root, "loopr-snapshot-index")?;
+    if resolve_commit(root, "HEAD").is_some() {
+        git_output(root, &["read-tree", "HEAD"], Some(&index.path))?;
+    }
+    git_output(root, &["add", "-A"], Some(&index.path))?;
+    Ok(git_output(root, &["write-tree"], Some(&index.path))?
+        .trim()
+        .to_string())
+}
+
+fn commit_tree(
+    root: &Path,
+    tree: &str,
+    parent: Option<&str>,
+    message: &str,
+) -> LooprResult<String> {
+    let mut args = vec!["commit-tree", tree];
+    if let Some(parent) = parent {
+        args.push("-p");
+        args.push(parent);
+    }
+    args.push("-m");
+    args.push(message);
+    Ok(git_output(root, &args, None)?.trim().to_string())
+}
+
+fn resolve_commit(root: &Path, rev: &str) -> Option<String> {
+    let spec = format!("{}^{{commit}}", rev);
+    match git_output(root, &["rev-parse", "--verify", "--quiet", &spec], None) {
+        Ok(value) if !value.trim().is_empty() => Some(value.trim().to_string()),
+        _ => None,
+    }
+}
+
+fn parse_trailer(body: &str, key: &str) -> Option<String> {
+    let prefix = format!("{}:", key);
+    body.lines()
+        .filter_map(|line| line.trim().strip_prefix(&prefix))
+        .map(|value| value.trim().to_string())
+        .find(|value| !value.is_empty())
+}
+
+fn git_output(root: &Path, args: &[&str], index: Option<&Path>) -> LooprResult<String> {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C")
+        .arg(root)
+        .args(args)
+        .stdin(Stdio::null())
+        .env("GIT_AUTHOR_NAME", SNAPSHOT_AUTHOR_NAME)
+        .env("GIT_AUTHOR_EMAIL", SNAPSHOT_AUTHOR_EMAIL)
+        .env("GIT_COMMITTER_NAME", SNAPSHOT_AUTHOR_NAME)
+        .env("GIT_COMMITTER_EMAIL", SNAPSHOT_AUTHOR_EMAIL);
+    if let Some(index) = index {
+        cmd.env("GIT_INDEX_FILE", index);
+    }
+    let output = cmd
+        .output()
+        .map_err(|err| LooprError::new(format!("run git {}: {}", args[0], err)))?;
+    if !output.status.success() {
+        return Err(LooprError::new(format!(
+            "git {}: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+struct TempGitFile {
+    path: PathBuf,
+}
+
+impl TempGitFile {
+    fn new(root: &Path, prefix: &str) -> LooprResult<Self> {
+        let git_dir = git_output(root, &["rev-parse", "--absolute-git-dir"], None)?;
+        let path = PathBuf::from(git_dir.trim()).join(format!("{}-{}", prefix, random_hex(6)?));
+        Ok(Self { path })
+    }
+}
+
+impl Drop for TempGitFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn random_hex(bytes_len: usize) -> LooprResult<String> {
+    let mut bytes = vec![0u8; bytes_len];
+    getrandom::fill(&mut bytes).map_err(|err| LooprError::new(format!("random bytes: {}", err)))?;
+    let mut out = String::with_capacity(bytes_len * 2);
+    for byte in bytes {
+        out.push(hex_digit(byte >> 4));
+        out.push(hex_digit(byte & 0x0f));
+    }
+    Ok(out)
+}
+
+fn hex_digit(value: u8) -> char {
+    match value {
+        0..=9 => (b'0' + value) as char,
+        10..=15 => (b'a' + (value - 10)) as char,
+        _ => '0',
+    }
+}
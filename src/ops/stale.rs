@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::LooprResult;
+use crate::ops::feature_scope::{load_feature, scope_step};
+use crate::ops::glob::path_matches;
+use crate::ops::run::{RunStep, load_run_steps, produces_input};
+use crate::ops::run_state::{
+    StepRunRecord, changed_paths, hash_step_inputs, hash_step_outputs, load_run_state,
+    run_state_path,
+};
+
+pub struct StaleStep {
+    pub step: String,
+    pub feature: Option<String>,
+    pub artifacts: Vec<String>,
+    pub reasons: Vec<String>,
+}
+
+pub struct EditedArtifacts {
+    pub step: String,
+    pub paths: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct StaleReport {
+    pub current: Vec<String>,
+    pub not_run: Vec<String>,
+    pub stale: Vec<StaleStep>,
+    pub edited: Vec<EditedArtifacts>,
+}
+
+impl StaleReport {
+    pub fn is_stale(&self) -> bool {
+        !self.stale.is_empty()
+    }
+
+    // One contiguous range covers every stale step; steps in between whose inputs did not change
+    // are skipped by `loopr run` anyway. Only a single feature's steps are refreshed with
+    // --feature; anything else needs the whole range.
+    pub fn fix_command(&self) -> Option<String> {
+        let first = self.stale.first()?;
+        let last = self.stale.last()?;
+        let mut command = if first.step == last.step {
+            format!("loopr run --codex --step {}", first.step)
+        } else {
+            format!("loopr run --codex --from {} --to {}", first.step, last.step)
+        };
+        if let Some(feature) = &first.feature
+            && self
+                .stale
+                .iter()
+                .all(|step| step.feature.as_ref() == Some(feature))
+        {
+            command.push_str(&format!(" --feature {}", feature));
+        }
+        Some(command)
+    }
+}
+
+// Walks the pipeline in order and compares each generated step's recorded input hashes
+// (loopr/state/run-state.json) with the files on disk, including the per-feature records that
+// `loopr run --feature` leaves. A step is stale when one of its inputs changed or when a step
+// producing one of its inputs is stale. always_run steps are not tracked.
+pub fn find_stale(root: &Path) -> LooprResult<StaleReport> {
+    let steps = load_run_steps(root)?;
+    let state = load_run_state(&run_state_path(root), "")?;
+    let mut report = StaleReport::default();
+    let mut stale_steps: Vec<RunStep> = Vec::new();
+    let tracked: Vec<&RunStep> = steps.iter().filter(|step| !step.always_run).collect();
+    for (idx, step) in tracked.iter().enumerate() {
+        let mut runs: Vec<(RunStep, &StepRunRecord)> = Vec::new();
+        if let Some(record) = state.steps.get(&step.name) {
+            runs.push(((*step).clone(), record));
+        }
+        let prefix = format!("{}@", step.name);
+        let mut scoped: Vec<(&String, &StepRunRecord)> = state
+            .steps
+            .iter()
+            .filter(|(key, _)| key.starts_with(&prefix))
+            .collect();
+        scoped.sort_by(|a, b| a.0.cmp(b.0));
+        for (key, record) in scoped {
+            // Records for features that were since dropped from feature-order.yaml are ignored.
+            if let Ok((feature, _)) = load_feature(root, &key[prefix.len()..])
+                && let Ok(run) = scope_step(step, &feature)
+            {
+                runs.push((run, record));
+            }
+        }
+        if runs.is_empty() {
+            report.not_run.push(step.name.clone());
+            continue;
+        }
+
+        let downstream = &tracked[idx + 1..];
+        for (run, record) in runs {
+            let mut reasons: Vec<String> =
+                changed_paths(&record.inputs, &hash_step_inputs(root, &run)?)
+                    .into_iter()
+                    .map(|path| format!("input changed: {}", path))
+                    .collect();
+            for upstream in &stale_steps {
+                if feeds(upstream, &run) {
+                    reasons.push(format!("upstream step {} is stale", upstream.state_key()));
+                }
+            }
+            let outputs = own_outputs(&run, step, downstream, hash_step_outputs(root, &run)?);
+            let edited = changed_paths(
+                &own_outputs(&run, step, downstream, record.outputs.clone()),
+                &outputs,
+            );
+            if !edited.is_empty() {
+                report.edited.push(EditedArtifacts {
+                    step: run.state_key(),
+                    paths: edited,
+                });
+            }
+            if reasons.is_empty() {
+                report.current.push(run.state_key());
+                continue;
+            }
+            report.stale.push(StaleStep {
+                step: run.name.clone(),
+                feature: run.feature.as_ref().map(|feature| feature.key.clone()),
+                artifacts: outputs.into_keys().collect(),
+                reasons,
+            });
+            stale_steps.push(run);
+        }
+    }
+    Ok(report)
+}
+
+// Steps scoped to different features only share the order files, which are merged per feature.
+fn feeds(upstream: &RunStep, step: &RunStep) -> bool {
+    if let (Some(a), Some(b)) = (&upstream.feature, &step.feature)
+        && a.key != b.key
+    {
+        return false;
+    }
+    step.inputs
+        .iter()
+        .any(|input| produces_input(upstream, input))
+}
+
+// The outputs a run is answerable for: broad globs like specs/feature-*.md also match the files
+// later steps write, and a feature-scoped run shares the order files with every other feature.
+fn own_outputs(
+    run: &RunStep,
+    step: &RunStep,
+    downstream: &[&RunStep],
+    mut outputs: BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    outputs.retain(|path, _| {
+        let shared = run.feature.is_some()
+            && step
+                .outputs
+                .iter()
+                .any(|pattern| pattern == path && run.outputs.contains(pattern));
+        let claimed = downstream.iter().any(|later| {
+            later
+                .outputs
+                .iter()
+                .any(|pattern| path_matches(pattern, path))
+        });
+        !shared && !claimed
+    });
+    outputs
+}
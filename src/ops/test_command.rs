@@ -0,0 +1,146 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use crate::ops::fs::ensure_dir;
+use crate::ops::work_status::TestRunResult;
+use crate::{LooprError, LooprResult};
+
+const TEST_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const TEST_EXCERPT_LINES: usize = 40;
+const TEST_EXCERPT_CHARS: usize = 4000;
+
+// Runs `command` through `sh -lc` in `root`. stdout and stderr are appended to `log_path` after a
+// `$ command` line, so several commands for one item share a log. The command gets its own process
+// group, so a run that outlives `timeout` is killed together with everything it spawned and
+// reported as `timed_out`.
+pub fn run_test_command(
+    root: &Path,
+    command: &str,
+    phase: &str,
+    env: &[(&str, String)],
+    log_path: &Path,
+    timeout: Duration,
+) -> LooprResult<TestRunResult> {
+    let command = command.trim();
+    if command.is_empty() {
+        return Err(LooprError::new("TEST_COMMAND is empty"));
+    }
+    if let Some(parent) = log_path.parent() {
+        ensure_dir(parent, 0o755)?;
+    }
+    let mut log = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)
+        .map_err(|err| LooprError::new(format!("open {}: {}", log_path.display(), err)))?;
+    writeln!(log, "$ {}", command)
+        .map_err(|err| LooprError::new(format!("write {}: {}", log_path.display(), err)))?;
+    let stdout = log
+        .try_clone()
+        .map_err(|err| LooprError::new(format!("open {}: {}", log_path.display(), err)))?;
+    let stderr = log
+        .try_clone()
+        .map_err(|err| LooprError::new(format!("open {}: {}", log_path.display(), err)))?;
+
+    let started = Instant::now();
+    let mut cmd = Command::new("sh");
+    cmd.arg("-lc")
+        .arg(command)
+        .current_dir(root)
+        .envs(env.iter().map(|(key, value)| (*key, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(stdout))
+        .stderr(Stdio::from(stderr));
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    let mut child = cmd
+        .spawn()
+        .map_err(|err| LooprError::new(format!("run test command: {}", err)))?;
+
+    let mut timed_out = false;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) => {}
+            Err(err) => {
+                return Err(LooprError::new(format!("wait for test command: {}", err)));
+            }
+        }
+        if started.elapsed() >= timeout {
+            timed_out = true;
+            break kill_process_group(&mut child);
+        }
+        std::thread::sleep(TEST_POLL_INTERVAL);
+    };
+    if timed_out {
+        writeln!(
+            log,
+            "loopr: test command timed out after {}s; killed its process group",
+            timeout.as_secs()
+        )
+        .map_err(|err| LooprError::new(format!("write {}: {}", log_path.display(), err)))?;
+    }
+
+    let exit_code = status.and_then(|value| value.code()).unwrap_or(1);
+    Ok(TestRunResult {
+        exit_code,
+        passed: exit_code == 0 && !timed_out,
+        ran_at: now_rfc3339()?,
+        phase: phase.to_string(),
+        command: command.to_string(),
+        log_path: log_path.display().to_string(),
+        duration_ms: started.elapsed().as_millis() as u64,
+        timed_out,
+        stability: None,
+        reruns: Vec::new(),
+        cases: Vec::new(),
+        missing_cases: Vec::new(),
+    })
+}
+
+#[cfg(unix)]
+fn kill_process_group(child: &mut Child) -> Option<ExitStatus> {
+    let _ = Command::new("kill")
+        .args(["-KILL", "--"])
+        .arg(format!("-{}", child.id()))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    let _ = child.kill();
+    child.wait().ok()
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(child: &mut Child) -> Option<ExitStatus> {
+    let _ = child.kill();
+    child.wait().ok()
+}
+
+fn now_rfc3339() -> LooprResult<String> {
+    OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .map_err(|err| LooprError::new(format!("format time: {}", err)))
+}
+
+// The tail of a failed test log, capped in lines and characters, for the item's next prompt.
+pub fn test_failure_excerpt(log_path: &Path) -> String {
+    let data = match std::fs::read_to_string(log_path) {
+        Ok(value) => value,
+        Err(_) => return String::new(),
+    };
+    let lines: Vec<&str> = data.trim_end().lines().collect();
+    let mut excerpt = lines[lines.len().saturating_sub(TEST_EXCERPT_LINES)..].join("\n");
+    if excerpt.chars().count() > TEST_EXCERPT_CHARS {
+        let skip = excerpt.chars().count() - TEST_EXCERPT_CHARS;
+        excerpt = excerpt.chars().skip(skip).collect();
+    }
+    excerpt
+}
@@ -0,0 +1,202 @@
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::ops::glob::{expand_glob, glob_match};
+use crate::ops::work_status::{TestCaseOutcome, TestCaseResult};
+use crate::{LooprError, LooprResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestResultFormat {
+    // `cargo test -- -Z unstable-options --format json` lines in the command output.
+    LibtestJson,
+    // JUnit XML report files (pytest --junitxml, jest-junit, cargo2junit, ...).
+    Junit,
+    // TAP lines in the command output.
+    Tap,
+}
+
+impl TestResultFormat {
+    pub fn parse(value: &str) -> Option<TestResultFormat> {
+        match value {
+            "libtest-json" => Some(TestResultFormat::LibtestJson),
+            "junit" => Some(TestResultFormat::Junit),
+            "tap" => Some(TestResultFormat::Tap),
+            _ => None,
+        }
+    }
+}
+
+// Collects the test cases of one test run: from `output` for libtest JSON and TAP, from the JUnit
+// files matching `files` (relative to `root`) written since `since` otherwise.
+pub fn collect_test_cases(
+    format: TestResultFormat,
+    root: &Path,
+    files: &str,
+    output: &str,
+    since: SystemTime,
+) -> LooprResult<Vec<TestCaseResult>> {
+    match format {
+        TestResultFormat::LibtestJson => Ok(parse_libtest_json(output)),
+        TestResultFormat::Tap => Ok(parse_tap(output)),
+        TestResultFormat::Junit => {
+            let mut cases = Vec::new();
+            for rel in expand_glob(root, files)? {
+                let path = root.join(&rel);
+                let modified = std::fs::metadata(&path)
+                    .and_then(|meta| meta.modified())
+                    .map_err(|err| LooprError::new(format!("stat {}: {}", path.display(), err)))?;
+                if modified < since {
+                    continue;
+                }
+                let data = std::fs::read_to_string(&path)
+                    .map_err(|err| LooprError::new(format!("read {}: {}", path.display(), err)))?;
+                cases.extend(parse_junit(&data));
+            }
+            Ok(cases)
+        }
+    }
+}
+
+pub fn parse_libtest_json(output: &str) -> Vec<TestCaseResult> {
+    let mut cases = Vec::new();
+    for line in output.lines() {
+        let line = line.trim();
+        if !line.starts_with('{') {
+            continue;
+        }
+        let event: serde_json::Value = match serde_json::from_str(line) {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+        if event["type"] != "test" {
+            continue;
+        }
+        // "timeout" only warns that a test is slow; its ok/failed event still follows.
+        let outcome = match event["event"].as_str() {
+            Some("ok") => TestCaseOutcome::Passed,
+            Some("failed") => TestCaseOutcome::Failed,
+            Some("ignored") => TestCaseOutcome::Skipped,
+            _ => continue,
+        };
+        if let Some(name) = event["name"].as_str() {
+            cases.push(TestCaseResult {
+                name: name.to_string(),
+                outcome,
+            });
+        }
+    }
+    cases
+}
+
+pub fn parse_tap(output: &str) -> Vec<TestCaseResult> {
+    let mut cases = Vec::new();
+    for line in output.lines() {
+        let line = line.trim();
+        let (failed, rest) = if let Some(rest) = line.strip_prefix("not ok") {
+            (true, rest)
+        } else if let Some(rest) = line.strip_prefix("ok") {
+            (false, rest)
+        } else {
+            continue;
+        };
+        if !rest.is_empty() && !rest.starts_with(' ') {
+            continue;
+        }
+        let rest = rest
+            .trim_start()
+            .trim_start_matches(|ch: char| ch.is_ascii_digit());
+        let (description, directive) = match rest.split_once('#') {
+            Some((description, directive)) => (description, directive.trim().to_uppercase()),
+            None => (rest, String::new()),
+        };
+        let name = description
+            .trim()
+            .trim_start_matches('-')
+            .trim()
+            .to_string();
+        let outcome = if directive.starts_with("SKIP") || directive.starts_with("TODO") {
+            TestCaseOutcome::Skipped
+        } else if failed {
+            TestCaseOutcome::Failed
+        } else {
+            TestCaseOutcome::Passed
+        };
+        cases.push(TestCaseResult { name, outcome });
+    }
+    cases
+}
+
+// A small scanner for <testcase> elements; names are `classname::name` when a classname is set.
+pub fn parse_junit(xml: &str) -> Vec<TestCaseResult> {
+    let mut cases = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find("<testcase") {
+        rest = &rest[start + "<testcase".len()..];
+        let tag_end = match rest.find('>') {
+            Some(value) => value,
+            None => break,
+        };
+        let attrs = &rest[..tag_end];
+        let self_closing = attrs.ends_with('/');
+        rest = &rest[tag_end + 1..];
+        let body = if self_closing {
+            ""
+        } else {
+            let end = rest.find("</testcase>").unwrap_or(rest.len());
+            let body = &rest[..end];
+            rest = &rest[end..];
+            body
+        };
+        let name = xml_attr(attrs, "name").unwrap_or_default();
+        let name = match xml_attr(attrs, "classname") {
+            Some(classname) if !classname.is_empty() => format!("{}::{}", classname, name),
+            _ => name,
+        };
+        let outcome = if body.contains("<failure") || body.contains("<error") {
+            TestCaseOutcome::Failed
+        } else if body.contains("<skipped") {
+            TestCaseOutcome::Skipped
+        } else {
+            TestCaseOutcome::Passed
+        };
+        cases.push(TestCaseResult { name, outcome });
+    }
+    cases
+}
+
+pub fn xml_attr(attrs: &str, key: &str) -> Option<String> {
+    let mut rest = attrs;
+    while let Some(idx) = rest.find(key) {
+        let before = rest[..idx].chars().last();
+        let after = rest[idx + key.len()..].trim_start();
+        rest = &rest[idx + key.len()..];
+        if before.is_some_and(|ch| !ch.is_whitespace()) {
+            continue;
+        }
+        let value = match after.strip_prefix('=') {
+            Some(value) => value.trim_start(),
+            None => continue,
+        };
+        let quote = value.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            return None;
+        }
+        let end = value[1..].find(quote)?;
+        return Some(xml_unescape(&value[1..1 + end]));
+    }
+    None
+}
+
+fn xml_unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+// A declared case matches a glob over the full name, or the name's last `::` segments.
+pub fn case_matches(pattern: &str, name: &str) -> bool {
+    glob_match(pattern, name) || name.ends_with(&format!("::{}", pattern))
+}
@@ -0,0 +1,389 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+
+use crate::ops::work_plan::{FeatureOrder, TaskOrder, TestOrder};
+use crate::{LooprError, LooprResult};
+
+pub const FEATURE_ORDER_PATH: &str = "specs/feature-order.yaml";
+pub const TASK_ORDER_PATH: &str = "specs/task-order.yaml";
+pub const TEST_ORDER_PATH: &str = "specs/test-order.yaml";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub file: String,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file, line, self.message),
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+pub struct ValidationReport {
+    pub checked: Vec<String>,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn into_result(self) -> LooprResult<()> {
+        if self.is_ok() {
+            return Ok(());
+        }
+        let mut message = format!("spec validation failed ({} issues):", self.issues.len());
+        for issue in &self.issues {
+            message.push_str(&format!("\n  {}", issue));
+        }
+        Err(LooprError::new(message))
+    }
+}
+
+struct OrderItem {
+    id: i64,
+    key: String,
+    file: String,
+    depends_on: Vec<i64>,
+    task_id: Option<i64>,
+    line: Option<usize>,
+}
+
+// Validates the order files that exist under specs/. Files listed in `required` are reported
+// as issues when missing; the rest are skipped.
+pub fn validate_orders(root: &Path, required: &[&str]) -> LooprResult<ValidationReport> {
+    let mut report = ValidationReport {
+        checked: Vec::new(),
+        issues: Vec::new(),
+    };
+
+    let features = load_items::<FeatureOrder>(root, FEATURE_ORDER_PATH, required, &mut report)?
+        .map(|(order, lines)| {
+            let items = order
+                .features
+                .into_iter()
+                .enumerate()
+                .map(|(idx, spec)| OrderItem {
+                    id: spec.id,
+                    key: spec.key,
+                    file: spec.file,
+                    depends_on: spec.depends_on,
+                    task_id: None,
+                    line: lines.get(idx).copied(),
+                })
+                .collect::<Vec<_>>();
+            (order.version, items)
+        });
+    let tasks = load_items::<TaskOrder>(root, TASK_ORDER_PATH, required, &mut report)?.map(
+        |(order, lines)| {
+            let items = order
+                .tasks
+                .into_iter()
+                .enumerate()
+                .map(|(idx, spec)| OrderItem {
+                    id: spec.id,
+                    key: spec.key,
+                    file: spec.file,
+                    depends_on: spec.depends_on,
+                    task_id: None,
+                    line: lines.get(idx).copied(),
+                })
+                .collect::<Vec<_>>();
+            (order.version, items)
+        },
+    );
+    let tests = load_items::<TestOrder>(root, TEST_ORDER_PATH, required, &mut report)?.map(
+        |(order, lines)| {
+            let items = order
+                .tests
+                .into_iter()
+                .enumerate()
+                .map(|(idx, spec)| OrderItem {
+                    id: spec.id,
+                    key: spec.key,
+                    file: spec.file,
+                    depends_on: spec.depends_on,
+                    task_id: Some(spec.task_id),
+                    line: lines.get(idx).copied(),
+                })
+                .collect::<Vec<_>>();
+            (order.version, items)
+        },
+    );
+
+    if let Some((version, items)) = &features {
+        check_items(
+            root,
+            FEATURE_ORDER_PATH,
+            "feature",
+            *version,
+            items,
+            &mut report,
+        );
+    }
+    if let Some((version, items)) = &tasks {
+        check_items(root, TASK_ORDER_PATH, "task", *version, items, &mut report);
+    }
+    if let Some((version, items)) = &tests {
+        check_items(root, TEST_ORDER_PATH, "test", *version, items, &mut report);
+        if let Some((_, task_items)) = &tasks {
+            let task_ids: HashSet<i64> = task_items.iter().map(|item| item.id).collect();
+            for item in items {
+                if let Some(task_id) = item.task_id
+                    && !task_ids.contains(&task_id)
+                {
+                    report.issues.push(ValidationIssue {
+                        file: TEST_ORDER_PATH.to_string(),
+                        line: item.line,
+                        message: format!(
+                            "test {} ({}) has task_id {} which is not in {}",
+                            item.id, item.key, task_id, TASK_ORDER_PATH
+                        ),
+                    });
+                }
+            }
+        } else if items.iter().any(|item| item.task_id.is_some())
+            && !required.contains(&TASK_ORDER_PATH)
+            && !root.join(TASK_ORDER_PATH).exists()
+        {
+            // Without the task order there is nothing to check task_id against.
+            report.issues.push(ValidationIssue {
+                file: TEST_ORDER_PATH.to_string(),
+                line: None,
+                message: format!("tests have task_ids but {} does not exist", TASK_ORDER_PATH),
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+fn load_items<T: DeserializeOwned>(
+    root: &Path,
+    rel: &str,
+    required: &[&str],
+    report: &mut ValidationReport,
+) -> LooprResult<Option<(T, Vec<usize>)>> {
+    let path = root.join(rel);
+    let data = match fs::read_to_string(&path) {
+        Ok(value) => value,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            if required.contains(&rel) {
+                report.issues.push(ValidationIssue {
+                    file: rel.to_string(),
+                    line: None,
+                    message: "file not found".to_string(),
+                });
+            }
+            return Ok(None);
+        }
+        Err(err) => return Err(LooprError::new(format!("read {}: {}", path.display(), err))),
+    };
+    report.checked.push(rel.to_string());
+    match serde_yaml::from_str::<T>(&data) {
+        Ok(order) => Ok(Some((order, entry_lines(&data)))),
+        Err(err) => {
+            report.issues.push(ValidationIssue {
+                file: rel.to_string(),
+                line: err.location().map(|loc| loc.line()),
+                message: format!("parse error: {}", err),
+            });
+            Ok(None)
+        }
+    }
+}
+
+fn check_items(
+    root: &Path,
+    rel: &str,
+    kind: &str,
+    version: i64,
+    items: &[OrderItem],
+    report: &mut ValidationReport,
+) {
+    let mut push = |line: Option<usize>, message: String| {
+        report.issues.push(ValidationIssue {
+            file: rel.to_string(),
+            line,
+            message,
+        });
+    };
+
+    if version != 1 {
+        push(
+            None,
+            format!("unsupported version {} (expected 1)", version),
+        );
+    }
+
+    let mut ids: HashMap<i64, &OrderItem> = HashMap::new();
+    let mut keys: HashMap<&str, &OrderItem> = HashMap::new();
+    for item in items {
+        if let Some(first) = ids.get(&item.id) {
+            push(
+                item.line,
+                format!(
+                    "duplicate {} id {} (first defined on line {})",
+                    kind,
+                    item.id,
+                    line_label(first.line)
+                ),
+            );
+        } else {
+            ids.insert(item.id, item);
+        }
+        if item.key.trim().is_empty() {
+            push(item.line, format!("{} {} has an empty key", kind, item.id));
+        } else if let Some(first) = keys.get(item.key.as_str()) {
+            push(
+                item.line,
+                format!(
+                    "duplicate {} key {} (first defined on line {})",
+                    kind,
+                    item.key,
+                    line_label(first.line)
+                ),
+            );
+        } else {
+            keys.insert(item.key.as_str(), item);
+        }
+        if item.file.trim().is_empty() {
+            push(item.line, format!("{} {} has an empty file", kind, item.id));
+        } else if !root.join(&item.file).is_file() {
+            push(
+                item.line,
+                format!(
+                    "{} {} ({}) file not found: {}",
+                    kind, item.id, item.key, item.file
+                ),
+            );
+        }
+        for dep in &item.depends_on {
+            if *dep == item.id {
+                push(
+                    item.line,
+                    format!("{} {} ({}) depends on itself", kind, item.id, item.key),
+                );
+            } else if !items.iter().any(|other| other.id == *dep) {
+                push(
+                    item.line,
+                    format!(
+                        "{} {} ({}) depends_on unknown {} id {}",
+                        kind, item.id, item.key, kind, dep
+                    ),
+                );
+            }
+        }
+    }
+
+    for cycle in find_cycles(items) {
+        let first = ids.get(&cycle[0]).and_then(|item| item.line);
+        let path: Vec<String> = cycle.iter().map(|id| id.to_string()).collect();
+        push(
+            first,
+            format!("{} dependency cycle: {}", kind, path.join(" -> ")),
+        );
+    }
+}
+
+fn find_cycles(items: &[OrderItem]) -> Vec<Vec<i64>> {
+    let mut deps: HashMap<i64, &Vec<i64>> = HashMap::new();
+    for item in items {
+        deps.entry(item.id).or_insert(&item.depends_on);
+    }
+    let mut state: HashMap<i64, u8> = HashMap::new();
+    let mut cycles = Vec::new();
+    let mut ids: Vec<i64> = deps.keys().copied().collect();
+    ids.sort();
+    for id in ids {
+        let mut stack = Vec::new();
+        visit(id, &deps, &mut state, &mut stack, &mut cycles);
+    }
+    cycles
+}
+
+fn visit(
+    id: i64,
+    deps: &HashMap<i64, &Vec<i64>>,
+    state: &mut HashMap<i64, u8>,
+    stack: &mut Vec<i64>,
+    cycles: &mut Vec<Vec<i64>>,
+) {
+    match state.get(&id) {
+        Some(2) => return,
+        Some(1) => {
+            if let Some(pos) = stack.iter().position(|value| *value == id) {
+                let mut cycle = stack[pos..].to_vec();
+                cycle.push(id);
+                cycles.push(cycle);
+            }
+            return;
+        }
+        _ => {}
+    }
+    state.insert(id, 1);
+    stack.push(id);
+    if let Some(children) = deps.get(&id) {
+        for dep in children.iter() {
+            if *dep != id && deps.contains_key(dep) {
+                visit(*dep, deps, state, stack, cycles);
+            }
+        }
+    }
+    stack.pop();
+    state.insert(id, 2);
+}
+
+// Line numbers (1-based) of each entry in the first top-level sequence of an order file.
+fn entry_lines(data: &str) -> Vec<usize> {
+    let mut lines = Vec::new();
+    let mut indent: Option<usize> = None;
+    let mut in_list = false;
+    for (idx, line) in data.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let current = line.len() - trimmed.len();
+        if current == 0 && !trimmed.starts_with('-') {
+            if in_list && !lines.is_empty() {
+                break;
+            }
+            in_list = trimmed.ends_with(':')
+                && matches!(
+                    trimmed.trim_end_matches(':'),
+                    "features" | "tasks" | "tests"
+                );
+            continue;
+        }
+        if !in_list || !trimmed.starts_with('-') {
+            continue;
+        }
+        match indent {
+            None => {
+                indent = Some(current);
+                lines.push(idx + 1);
+            }
+            Some(value) if value == current => lines.push(idx + 1),
+            _ => {}
+        }
+    }
+    lines
+}
+
+fn line_label(line: Option<usize>) -> String {
+    match line {
+        Some(value) => value.to_string(),
+        None => "?".to_string(),
+    }
+}
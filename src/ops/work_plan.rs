@@ -5,6 +5,22 @@ use serde::Deserialize;
 
 use crate::{LooprError, LooprResult};
 
+#[derive(Debug, Deserialize)]
+pub struct FeatureOrder {
+    pub version: i64,
+    pub features: Vec<FeatureSpec>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FeatureSpec {
+    pub id: i64,
+    pub key: String,
+    pub title: String,
+    pub file: String,
+    #[serde(default)]
+    pub depends_on: Vec<i64>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct TaskOrder {
     pub version: i64,
@@ -19,6 +35,10 @@ pub struct TaskSpec {
     pub file: String,
     #[serde(default)]
     pub depends_on: Vec<i64>,
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub timeout_minutes: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -38,6 +58,19 @@ pub struct TestSpec {
     pub depends_on: Vec<i64>,
     #[serde(default)]
     pub kind: Option<String>,
+    #[serde(default)]
+    pub cases: Vec<String>,
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub timeout_minutes: Option<i64>,
+}
+
+pub fn load_feature_order(path: &Path) -> LooprResult<FeatureOrder> {
+    let data = fs::read_to_string(path)
+        .map_err(|err| LooprError::new(format!("read {}: {}", path.display(), err)))?;
+    serde_yaml::from_str(&data)
+        .map_err(|err| LooprError::new(format!("parse {}: {}", path.display(), err)))
 }
 
 pub fn load_task_order(path: &Path) -> LooprResult<TaskOrder> {
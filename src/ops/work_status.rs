@@ -32,6 +32,18 @@ pub struct WorkItemStatus {
     pub tests_validated: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_test: Option<TestRunResult>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sessions: Vec<ItemSession>,
+    // Results of the latest gate run (GATES in loopr/config).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gates: Vec<GateResult>,
+    // Coverage of the files the task changed (COVERAGE_COMMAND in loopr/config).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<CoverageResult>,
+    // Tree the item's first attempt started from; coverage counts the files changed since, so
+    // later attempts still cover what earlier ones wrote.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_tree: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
@@ -57,6 +69,103 @@ pub struct TestRunResult {
     pub passed: bool,
     pub ran_at: String,
     pub phase: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub command: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub log_path: String,
+    #[serde(default)]
+    pub duration_ms: u64,
+    #[serde(default)]
+    pub timed_out: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stability: Option<TestStability>,
+    // Pass/fail of each TEST_RERUNS repeat of an ambiguous run.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reruns: Vec<bool>,
+    // Per-case outcomes when TEST_RESULT_FORMAT is set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cases: Vec<TestCaseResult>,
+    // Declared `cases` patterns that matched no case that ran.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing_cases: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TestCaseResult {
+    pub name: String,
+    pub outcome: TestCaseOutcome,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TestCaseOutcome {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TestStability {
+    Stable,
+    Flaky,
+}
+
+// One agent session spent on an item; round 0 is the attempt's main session, later rounds are
+// fix rounds after the validate test run failed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ItemSession {
+    pub attempt: u32,
+    pub round: u32,
+    pub phase: String,
+    pub transcript: String,
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tests_passed: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gates_passed: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coverage_passed: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GateResult {
+    pub name: String,
+    pub required: bool,
+    #[serde(flatten)]
+    pub run: TestRunResult,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CoverageResult {
+    pub command: String,
+    pub report: String,
+    pub ran_at: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub log_path: String,
+    pub lines_covered: u64,
+    pub lines_total: u64,
+    // Covered share of `lines_total`, rounded down to one decimal; absent when none of the
+    // changed files has measured lines.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percent: Option<f64>,
+    pub min_percent: i64,
+    pub required: bool,
+    pub passed: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileCoverage>,
+    // Why no number could be computed: the command failed or the report was unreadable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FileCoverage {
+    pub path: String,
+    pub lines_covered: u64,
+    pub lines_total: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub uncovered_lines: Vec<u32>,
 }
 
 pub fn load_work_status(path: &Path, now: &str) -> LooprResult<WorkStatusFile> {
@@ -108,5 +217,9 @@ pub fn ensure_item(
         tests_written: false,
         tests_validated: false,
         last_test: None,
+        sessions: Vec::new(),
+        gates: Vec::new(),
+        coverage: None,
+        base_tree: None,
     });
 }
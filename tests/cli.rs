@@ -1,9 +1,11 @@
-use std::process::Command;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use loopr::cli::{extract_codex_passthrough_flags, split_on_double_dash};
 
-fn temp_dir(name: &str) -> std::path::PathBuf {
+fn temp_dir(name: &str) -> PathBuf {
     let mut dir = std::env::temp_dir();
     let nanos = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -19,13 +21,97 @@ fn run_loopr(args: &[&str]) -> (String, i32) {
         .args(args)
         .output()
         .expect("run loopr");
-    let code = output.status.code().unwrap_or(1);
-    let mut text = String::new();
-    text.push_str(&String::from_utf8_lossy(&output.stdout));
+    joined_output(&output)
+}
+
+fn init_root(name: &str) -> PathBuf {
+    let root = temp_dir(name);
+    let (output, code) = run_loopr(&["init", "--no-agents", "--root", &root.to_string_lossy()]);
+    assert_eq!(code, 0, "{}", output);
+    root
+}
+
+fn write_file(root: &Path, relative: &str, contents: &str) {
+    let path = root.join(relative);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, contents).unwrap();
+}
+
+fn read_work_status(root: &Path) -> serde_json::Value {
+    let data =
+        std::fs::read_to_string(root.join("loopr").join("state").join("work-status.json")).unwrap();
+    serde_json::from_str(&data).unwrap()
+}
+
+// Installs `script` as an executable `codex` in `bin`, for run_loopr_with_path.
+fn fake_codex(bin: &Path, script: &str) {
+    let codex = bin.join("codex");
+    std::fs::write(&codex, script).unwrap();
+    let status = Command::new("chmod")
+        .args(["+x", &codex.to_string_lossy()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+fn loopr_with_path(args: &[&str], bin: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_loopr"));
+    command.args(args).env(
+        "PATH",
+        format!(
+            "{}:{}",
+            bin.display(),
+            std::env::var("PATH").unwrap_or_default()
+        ),
+    );
+    command
+}
+
+fn joined_output(output: &Output) -> (String, i32) {
+    let mut text = String::from_utf8_lossy(&output.stdout).to_string();
     text.push_str(&String::from_utf8_lossy(&output.stderr));
-    (text, code)
+    (text, output.status.code().unwrap_or(1))
+}
+
+// Like run_loopr, with `bin` (holding a fake codex) first on PATH.
+fn run_loopr_with_path(args: &[&str], bin: &Path, envs: &[(&str, &str)]) -> (String, i32) {
+    let output = loopr_with_path(args, bin)
+        .envs(envs.iter().copied())
+        .output()
+        .expect("run loopr");
+    joined_output(&output)
+}
+
+// Like run_loopr_with_path, answering loopr's prompts with `input`.
+fn run_loopr_with_stdin(args: &[&str], bin: &Path, input: &str) -> (String, i32) {
+    let mut child = loopr_with_path(args, bin)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run loopr");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().expect("wait loopr");
+    joined_output(&output)
 }
 
+fn git_init(root: &Path) {
+    let status = Command::new("git")
+        .args(["-C", &root.to_string_lossy(), "init", "-q"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+const ONE_TASK_ORDER: &str =
+    "version: 1\ntasks:\n  - id: 1\n    key: one\n    title: One\n    file: specs/one.md\n";
+const EMPTY_TEST_ORDER: &str = "version: 1\ntests: []\n";
+
 #[test]
 fn test_split_on_double_dash() {
     let args = vec!["--".to_string(), "--help".to_string()];
@@ -100,6 +186,55 @@ fn test_run_dry_run_prints_steps() {
     let (output, code) = run_loopr(&["run", "--dry-run", "--loopr-root", &root_str]);
     assert_eq!(code, 0);
     assert!(output.contains("Step: prd"));
+    assert!(output.contains("plan: run (no previous run recorded)"));
+    assert!(output.contains("plan: run (always_run)"));
+    assert!(output.contains("input: specs/spec.md (pending; produced by step spec)"));
+    assert!(output.contains("Plan: 6 to run, 0 to skip, 0 missing inputs"));
+}
+
+#[test]
+fn test_run_dry_run_json_and_prompts() {
+    let root = temp_dir("dry-run-json");
+    let root_str = root.to_string_lossy();
+    let (output, code) = run_loopr(&[
+        "run",
+        "--dry-run",
+        "--format",
+        "json",
+        "--step",
+        "spec",
+        "--loopr-root",
+        &root_str,
+    ]);
+    assert_eq!(code, 0, "{}", output);
+    let plan: serde_json::Value = serde_json::from_str(&output).unwrap();
+    let step = &plan["steps"][0];
+    assert_eq!(step["name"], "spec");
+    assert_eq!(step["action"], "run");
+    assert_eq!(step["inputs"][1]["pattern"], "specs/prd.md");
+    assert_eq!(step["inputs"][1]["status"], "missing");
+    assert_eq!(step["inputs"][1]["produced_by"], "prd");
+    assert!(step.get("prompt").is_none());
+
+    let (output, code) = run_loopr(&[
+        "run",
+        "--dry-run",
+        "--show-prompts",
+        "--step",
+        "prd",
+        "--seed-prompt",
+        "Build a habit tracker",
+        "--loopr-root",
+        &root_str,
+    ]);
+    assert_eq!(code, 0, "{}", output);
+    assert!(output.contains("  rendered prompt:\n    Loopr step: prd"));
+    assert!(output.contains("    Build a habit tracker"));
+    assert!(output.contains("Plan: 1 to run, 0 to skip, 0 missing inputs"));
+
+    let (output, code) = run_loopr(&["run", "--codex", "--show-prompts"]);
+    assert_ne!(code, 0);
+    assert!(output.contains("--dry-run"));
 }
 
 #[test]
@@ -135,3 +270,1087 @@ fn test_index_command_writes_docs_index() {
     assert!(output.contains("Docs index:"));
     assert!(root.join("loopr").join("state").join("docs-index.txt").exists());
 }
+
+#[test]
+fn test_validate_command_reports_issues() {
+    let root = temp_dir("validate-cmd");
+    let root_str = root.to_string_lossy();
+    let (_output, code) = run_loopr(&["init", "--no-agents", "--root", &root_str]);
+    assert_eq!(code, 0);
+
+    let (output, code) = run_loopr(&["validate", "--loopr-root", &root_str]);
+    assert_ne!(code, 0);
+    assert!(output.contains("no order files found"));
+
+    std::fs::create_dir_all(root.join("specs")).unwrap();
+    std::fs::write(
+        root.join("specs").join("task-order.yaml"),
+        "version: 1\ntasks:\n  - id: 1\n    key: one\n    title: One\n    file: specs/one.md\n",
+    )
+    .unwrap();
+    let (output, code) = run_loopr(&["validate", "--loopr-root", &root_str]);
+    assert_ne!(code, 0);
+    assert!(output.contains("specs/task-order.yaml:3: task 1 (one) file not found: specs/one.md"));
+
+    std::fs::write(root.join("specs").join("one.md"), "# One\n").unwrap();
+    let (output, code) = run_loopr(&["validate", "--loopr-root", &root_str]);
+    assert_eq!(code, 0);
+    assert!(output.contains("ok: specs/task-order.yaml"));
+}
+
+#[test]
+fn test_prompts_eject_writes_templates() {
+    let root = temp_dir("prompts-cmd");
+    let root_str = root.to_string_lossy();
+    let (_output, code) = run_loopr(&["init", "--no-agents", "--root", &root_str]);
+    assert_eq!(code, 0);
+
+    let (output, code) = run_loopr(&["prompts", "eject", "--loopr-root", &root_str]);
+    assert_eq!(code, 0);
+    assert!(output.contains("wrote:"));
+    assert!(root.join("loopr").join("prompts").join("per-task.tmpl").exists());
+
+    let (output, code) = run_loopr(&["prompts", "eject", "--loopr-root", &root_str]);
+    assert_eq!(code, 0);
+    assert!(output.contains("skipped (exists):"));
+
+    let (_output, code) = run_loopr(&["prompts", "list", "--loopr-root", &root_str]);
+    assert_ne!(code, 0);
+}
+
+#[test]
+fn test_skills_install_and_list_repo() {
+    let root = temp_dir("skills-cmd");
+    let root_str = root.to_string_lossy();
+    let (_output, code) = run_loopr(&["init", "--no-agents", "--root", &root_str]);
+    assert_eq!(code, 0);
+
+    let (output, code) = run_loopr(&["skills", "list", "--repo", "--loopr-root", &root_str]);
+    assert_eq!(code, 0);
+    assert!(output.contains("loopr-execute    missing"));
+
+    let (output, code) = run_loopr(&["skills", "install", "--repo", "--loopr-root", &root_str]);
+    assert_eq!(code, 0);
+    assert!(output.contains("installed: loopr-prd"));
+    assert!(root.join(".codex").join("skills").join("loopr-prd").join("SKILL.md").exists());
+
+    let (output, code) = run_loopr(&["skills", "list", "--repo", "--loopr-root", &root_str]);
+    assert_eq!(code, 0);
+    assert!(output.contains("loopr-execute    current"));
+
+    let (_output, code) = run_loopr(&["skills", "diff", "--repo", "--loopr-root", &root_str]);
+    assert_eq!(code, 0);
+    let (_output, code) = run_loopr(&["skills", "diff", "loopr-nope", "--repo", "--loopr-root", &root_str]);
+    assert_ne!(code, 0);
+}
+
+#[test]
+fn test_run_step_stops_on_blocked_status() {
+    let root = init_root("run-blocked");
+    let root_str = root.to_string_lossy();
+    write_file(&root, "specs/prd.md", "# PRD\n");
+
+    let bin = temp_dir("run-blocked-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nif [ \"$FAKE_STATUS\" = COMPLETE ]; then echo '# Spec' > \"$3/specs/spec.md\"; fi\nprintf -- '---LOOPR_STATUS---\\nSTATUS: %s\\nSUMMARY: prd has no goals\\n---END_LOOPR_STATUS---\\n' \"$FAKE_STATUS\"\n",
+    );
+
+    let (output, code) = run_loopr_with_path(
+        &["run", "--codex", "--loopr-root", &root_str, "--step", "spec"],
+        &bin,
+        &[("FAKE_STATUS", "BLOCKED")],
+    );
+    assert_ne!(code, 0);
+    assert!(output.contains("step spec reported BLOCKED: prd has no goals"));
+    assert!(output.contains("continue with: loopr run --codex --resume"));
+    let status =
+        std::fs::read_to_string(root.join("loopr").join("state").join("run-status.json")).unwrap();
+    assert!(status.contains("\"exit_reason\": \"blocked\""));
+
+    let (output, code) = run_loopr_with_path(
+        &["run", "--codex", "--loopr-root", &root_str, "--resume"],
+        &bin,
+        &[("FAKE_STATUS", "COMPLETE")],
+    );
+    assert_eq!(code, 0, "{}", output);
+    assert!(output.contains("Step 1/1 spec: done"));
+    let status =
+        std::fs::read_to_string(root.join("loopr").join("state").join("run-status.json")).unwrap();
+    assert!(status.contains("\"exit_reason\": \"completed\""));
+}
+
+#[test]
+fn test_run_marks_status_failed_when_codex_cannot_start() {
+    let root = init_root("run-spawn-error");
+    let root_str = root.to_string_lossy();
+    write_file(&root, "specs/prd.md", "# PRD\n");
+    let bin = temp_dir("run-spawn-error-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\necho '# Spec' > \"$3/specs/spec.md\"\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: wrote spec\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+    // Session transcripts cannot be created, so codex never starts.
+    let transcripts = root.join("loopr").join("state").join("transcripts");
+    let _ = std::fs::remove_dir_all(&transcripts);
+    std::fs::write(&transcripts, "not a directory\n").unwrap();
+
+    let (output, code) = run_loopr_with_path(
+        &[
+            "run",
+            "--codex",
+            "--loopr-root",
+            &root_str,
+            "--step",
+            "spec",
+        ],
+        &bin,
+        &[],
+    );
+    assert_ne!(code, 0, "{}", output);
+    let status =
+        std::fs::read_to_string(root.join("loopr").join("state").join("run-status.json")).unwrap();
+    assert!(status.contains("\"state\": \"failed\""), "{}", status);
+    assert!(status.contains("\"exit_reason\": \"error\""), "{}", status);
+    assert!(status.contains("\"last_error\""), "{}", status);
+
+    std::fs::remove_file(&transcripts).unwrap();
+    let (output, code) = run_loopr_with_path(
+        &["run", "--codex", "--loopr-root", &root_str, "--resume"],
+        &bin,
+        &[],
+    );
+    assert_eq!(code, 0, "{}", output);
+    assert!(output.contains("Step 1/1 spec: done"), "{}", output);
+}
+
+#[test]
+fn test_run_skips_tasks_step_when_inputs_are_unchanged() {
+    let root = init_root("run-skip-tasks");
+    let root_str = root.to_string_lossy();
+    write_file(
+        &root,
+        "specs/feature-order.yaml",
+        "version: 1\nfeatures:\n  - id: 1\n    key: a\n    title: A\n    file: specs/feature-a.md\n",
+    );
+    write_file(&root, "specs/feature-a.md", "# Feature A\n");
+
+    // The task file it writes also matches the step's specs/feature-*.md input glob.
+    let bin = temp_dir("run-skip-tasks-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\necho '# Task 1' > \"$3/specs/feature-a-task-1.md\"\nprintf 'version: 1\\ntasks:\\n  - id: 1\\n    key: a-1\\n    title: Task 1\\n    file: specs/feature-a-task-1.md\\n' > \"$3/specs/task-order.yaml\"\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: wrote tasks\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+    let args = ["run", "--codex", "--step", "tasks", "--loopr-root", &root_str];
+    let (output, code) = run_loopr_with_path(&args, &bin, &[]);
+    assert_eq!(code, 0, "{}", output);
+    assert!(output.contains("Step 1/1 tasks: done"), "{}", output);
+
+    let (output, code) = run_loopr_with_path(&args, &bin, &[]);
+    assert_eq!(code, 0, "{}", output);
+    assert!(output.contains("Step 1/1 tasks: skipped"), "{}", output);
+
+    write_file(&root, "specs/feature-a.md", "# Feature A v2\n");
+    let (output, code) = run_loopr_with_path(&args, &bin, &[]);
+    assert_eq!(code, 0, "{}", output);
+    assert!(output.contains("Step 1/1 tasks: done"), "{}", output);
+}
+
+#[test]
+fn test_run_review_reruns_rejected_step() {
+    let root = init_root("run-review");
+    let root_str = root.to_string_lossy();
+    write_file(&root, "specs/prd.md", "# PRD\n");
+
+    let bin = temp_dir("run-review-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\necho \"# Spec $$\" > \"$3/specs/spec.md\"\neval \"last=\\${$#}\"\nprintf '%s\\n' \"$last\" > \"$3/last-prompt.txt\"\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: wrote spec\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+
+    let (text, code) = run_loopr_with_stdin(
+        &["run", "--codex", "--review", "--step", "spec", "--loopr-root", &root_str],
+        &bin,
+        "r\ncover error handling\na\nlooks good\n",
+    );
+    assert_eq!(code, 0, "{}", text);
+    assert!(text.contains("Review step spec (loopr-specify)"));
+    assert!(text.contains("New files:\n  specs/spec.md"));
+    assert_eq!(text.matches("Step 1/1 spec: start").count(), 2);
+
+    let prompt = std::fs::read_to_string(root.join("last-prompt.txt")).unwrap();
+    assert!(prompt.contains("Reviewer feedback on the previous attempt"));
+    assert!(prompt.contains("cover error handling"));
+    let handoff =
+        std::fs::read_to_string(root.join("loopr").join("state").join("handoff.md")).unwrap();
+    assert!(handoff.contains("## Review: spec (rejected)"));
+    assert!(handoff.contains("- Comment: cover error handling"));
+    assert!(handoff.contains("## Review: spec (approved)"));
+    assert!(handoff.contains("- Comment: looks good"));
+
+    // Quitting the review stops the run; --resume brings the review gate back.
+    let (text, code) = run_loopr_with_stdin(
+        &[
+            "run",
+            "--codex",
+            "--review",
+            "--force",
+            "--step",
+            "spec",
+            "--loopr-root",
+            &root_str,
+        ],
+        &bin,
+        "q\n",
+    );
+    assert_ne!(code, 0, "{}", text);
+    assert!(text.contains("run stopped during review of spec"), "{}", text);
+    let (text, code) = run_loopr_with_stdin(
+        &["run", "--codex", "--resume", "--loopr-root", &root_str],
+        &bin,
+        "a\nresumed\n",
+    );
+    assert_eq!(code, 0, "{}", text);
+    assert!(text.contains("Review step spec (loopr-specify)"), "{}", text);
+    let handoff =
+        std::fs::read_to_string(root.join("loopr").join("state").join("handoff.md")).unwrap();
+    assert!(handoff.contains("- Comment: resumed"));
+}
+
+#[test]
+fn test_run_reads_seed_from_stdin_and_archives_it() {
+    let root = init_root("run-seed-stdin");
+    let root_str = root.to_string_lossy();
+
+    let bin = temp_dir("run-seed-stdin-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nmkdir -p \"$3/specs\"\necho '# PRD' > \"$3/specs/prd.md\"\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: wrote prd\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+
+    let args = ["run", "--codex", "--step", "prd", "--seed-prompt", "@-", "--loopr-root", &root_str];
+    let (text, code) = run_loopr_with_stdin(&args, &bin, "Build a habit tracker\n");
+    assert_eq!(code, 0, "{}", text);
+
+    let seed = std::fs::read_to_string(root.join("specs").join("seed.md")).unwrap();
+    assert!(seed.contains("Build a habit tracker"));
+    let history: Vec<_> = std::fs::read_dir(root.join("loopr").join("state").join("seeds"))
+        .unwrap()
+        .collect();
+    assert_eq!(history.len(), 1);
+
+    // specs/seed.md is an input of prd: the same seed skips it, an edited seed.md re-runs it.
+    let (text, code) = run_loopr_with_stdin(&args, &bin, "Build a habit tracker\n");
+    assert_eq!(code, 0, "{}", text);
+    assert!(text.contains("Step 1/1 prd: skipped"), "{}", text);
+    write_file(&root, "specs/seed.md", "Build a budget app\n");
+    let (text, code) = run_loopr_with_path(
+        &["run", "--codex", "--step", "prd", "--loopr-root", &root_str],
+        &bin,
+        &[],
+    );
+    assert_eq!(code, 0, "{}", text);
+    assert!(text.contains("Step 1/1 prd: done"), "{}", text);
+
+    let (output, code) = run_loopr(&[
+        "run",
+        "--codex",
+        "--confirm",
+        "--seed-prompt",
+        "@-",
+        "--loopr-root",
+        &root_str,
+    ]);
+    assert_ne!(code, 0);
+    assert!(output.contains("cannot be combined with --confirm or --review"));
+}
+
+#[test]
+fn test_stale_is_clean_after_a_run_and_walks_the_spec_chain() {
+    let root = init_root("stale-chain");
+    let root_str = root.to_string_lossy();
+
+    let bin = temp_dir("stale-chain-bin");
+    fake_codex(
+        &bin,
+        r#"#!/bin/sh
+for last; do :; done
+cd "$3"
+mkdir -p specs
+case "$last" in
+*'Loopr step: prd'*) echo '# PRD' > specs/prd.md ;;
+*'Loopr step: spec'*) echo '# Spec' > specs/spec.md ;;
+*'Loopr step: features'*)
+  printf 'version: 1\nfeatures:\n  - id: 1\n    key: a\n    title: A\n    file: specs/feature-a.md\n' > specs/feature-order.yaml
+  echo '# A' > specs/feature-a.md ;;
+*'Loopr step: tasks'*)
+  printf 'version: 1\ntasks:\n  - id: 1\n    key: a-1\n    title: A 1\n    file: specs/feature-a-task-1.md\n' > specs/task-order.yaml
+  echo '# Task' > specs/feature-a-task-1.md ;;
+*'Loopr step: tests'*)
+  printf 'version: 1\ntests:\n  - id: 1\n    key: a-1-test\n    title: A 1 test\n    task_id: 1\n    file: specs/feature-a-task-1-test-1.md\n' > specs/test-order.yaml
+  echo '# Test' > specs/feature-a-task-1-test-1.md ;;
+esac
+printf -- '---LOOPR_STATUS---\nSTATUS: COMPLETE\nSUMMARY: done\n---END_LOOPR_STATUS---\n'
+"#,
+    );
+    let (output, code) = run_loopr_with_path(
+        &[
+            "run",
+            "--codex",
+            "--to",
+            "tests",
+            "--seed-prompt",
+            "Build a habit tracker",
+            "--loopr-root",
+            &root_str,
+        ],
+        &bin,
+        &[],
+    );
+    assert_eq!(code, 0, "{}", output);
+    let (output, code) = run_loopr_with_path(
+        &[
+            "run",
+            "--codex",
+            "--step",
+            "tasks",
+            "--feature",
+            "a",
+            "--force",
+            "--loopr-root",
+            &root_str,
+        ],
+        &bin,
+        &[],
+    );
+    assert_eq!(code, 0, "{}", output);
+
+    // The generated task and test files match the broader globs of earlier steps; neither they
+    // nor the feature-scoped tasks record make anything look stale or edited.
+    let (output, code) = run_loopr(&["stale", "--check", "--loopr-root", &root_str]);
+    assert_eq!(code, 0, "{}", output);
+    assert!(!output.contains("edited:"), "{}", output);
+    assert!(
+        output.contains("up to date: prd, spec, features, tasks, tasks@a, tests\n"),
+        "{}",
+        output
+    );
+    assert!(output.contains("No stale artifacts."), "{}", output);
+
+    write_file(&root, "specs/feature-a.md", "# A\n\nEdited by hand.\n");
+    let (output, code) = run_loopr(&["stale", "--check", "--loopr-root", &root_str]);
+    assert_eq!(code, 1, "{}", output);
+    assert!(
+        output.contains("stale: tasks\n  artifact: specs/feature-a-task-1.md\n"),
+        "{}",
+        output
+    );
+    assert!(output.contains("stale: tasks (feature a)\n"), "{}", output);
+    assert!(
+        output.contains("  input changed: specs/feature-a.md\n"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("  upstream step tasks is stale\n"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("edited: specs/feature-a.md (changed after step features generated it)"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("up to date: prd, spec, features\n"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("Fix: loopr run --codex --from tasks --to tests\n"),
+        "{}",
+        output
+    );
+
+    // The archived seed is an input of prd, so editing it makes the whole chain stale.
+    write_file(&root, "specs/seed.md", "Build a budget app\n");
+    let (output, _code) = run_loopr(&["stale", "--command", "--loopr-root", &root_str]);
+    assert_eq!(output, "loopr run --codex --from prd --to tests\n");
+}
+
+#[test]
+fn test_run_dry_run_scopes_feature() {
+    let root = temp_dir("dry-run-feature");
+    let root_str = root.to_string_lossy();
+    std::fs::create_dir_all(root.join("specs")).unwrap();
+    std::fs::write(
+        root.join("specs").join("feature-order.yaml"),
+        "version: 1\nfeatures:\n  - id: 1\n    key: auth\n    title: Auth\n    file: specs/feature-auth.md\n  - id: 2\n    key: billing\n    title: Billing\n    file: specs/feature-billing.md\n    depends_on: [1]\n",
+    )
+    .unwrap();
+
+    let (output, code) = run_loopr(&[
+        "run",
+        "--dry-run",
+        "--show-prompts",
+        "--feature",
+        "billing",
+        "--loopr-root",
+        &root_str,
+    ]);
+    assert_eq!(code, 0, "{}", output);
+    assert!(!output.contains("Step: spec"));
+    assert!(output.contains("Step: tasks"));
+    assert!(output.contains("input: specs/feature-billing.md (missing; produced by step features)"));
+    assert!(output.contains("output: specs/feature-billing-task-*.md"));
+    assert!(output.contains("Feature scope: billing (Billing, specs/feature-billing.md)"));
+
+    let (output, code) = run_loopr(&[
+        "run",
+        "--dry-run",
+        "--feature",
+        "search",
+        "--loopr-root",
+        &root_str,
+    ]);
+    assert_ne!(code, 0);
+    assert!(output.contains("unknown feature: search (features: auth, billing)"));
+}
+
+#[test]
+fn test_loop_per_task_retries_errored_item() {
+    let root = init_root("loop-retry");
+    let root_str = root.to_string_lossy();
+    write_file(&root, "specs/one.md", "# One\n");
+    write_file(&root, "specs/task-order.yaml", ONE_TASK_ORDER);
+    write_file(&root, "specs/test-order.yaml", EMPTY_TEST_ORDER);
+    write_file(&root, "loopr/config", "TEST_COMMAND=true\nMAX_ATTEMPTS_PER_ITEM=2\n");
+
+    let bin = temp_dir("loop-retry-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nfor last; do :; done\nprintf '%s\\n' \"$last\" >> \"$FAKE_LOG\"\nif [ -e \"$FAKE_LOG.seen\" ]; then status=COMPLETE; else status=ERROR; touch \"$FAKE_LOG.seen\"; fi\nprintf -- '---LOOPR_STATUS---\\nSTATUS: %s\\nSUMMARY: missing fixture\\n---END_LOOPR_STATUS---\\n' \"$status\"\n",
+    );
+    let log = bin.join("prompts.log");
+    let (text, code) = run_loopr_with_path(
+        &["loop", "--per-task", "--loopr-root", &root_str],
+        &bin,
+        &[("FAKE_LOG", &log.to_string_lossy())],
+    );
+    assert_eq!(code, 0, "{}", text);
+    assert!(text.contains("Loop 1 retry: one attempt 1 failed: missing fixture"));
+    assert!(text.contains("Exit reason: completed"));
+
+    let prompts = std::fs::read_to_string(&log).unwrap();
+    assert!(prompts.contains("Attempt 2 of 2. The previous attempt failed:\nmissing fixture"));
+    let work_status = read_work_status(&root);
+    assert_eq!(work_status["items"]["one"]["attempts"], 2);
+    assert_eq!(work_status["items"]["one"]["state"], "complete");
+}
+
+#[test]
+fn test_loop_per_task_retries_item_when_test_command_errors() {
+    let root = init_root("loop-retry-test-error");
+    let root_str = root.to_string_lossy();
+    write_file(&root, "specs/one.md", "# One\n");
+    write_file(&root, "specs/task-order.yaml", ONE_TASK_ORDER);
+    write_file(&root, "specs/test-order.yaml", EMPTY_TEST_ORDER);
+    // The first run writes a JUnit report that is not UTF-8, so its results cannot be read; the
+    // second run writes none.
+    write_file(
+        &root,
+        "loopr/config",
+        "TEST_COMMAND=if [ -e .again ]; then rm -rf reports; else mkdir -p reports; printf '\\377' > reports/one.xml; touch .again; fi\nTEST_RESULT_FORMAT=junit\nTEST_RESULT_FILES=reports/*.xml\nMAX_ATTEMPTS_PER_ITEM=2\n",
+    );
+
+    let bin = temp_dir("loop-retry-test-error-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: done\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+    let (text, code) =
+        run_loopr_with_path(&["loop", "--per-task", "--loopr-root", &root_str], &bin, &[]);
+    assert_eq!(code, 0, "{}", text);
+    assert!(
+        text.contains("Loop 1 retry: one attempt 1 failed: read "),
+        "{}",
+        text
+    );
+    assert!(text.contains("Exit reason: completed"), "{}", text);
+
+    let work_status = read_work_status(&root);
+    assert_eq!(work_status["items"]["one"]["attempts"], 2);
+    assert_eq!(work_status["items"]["one"]["state"], "complete");
+}
+
+#[test]
+fn test_loop_keep_going_skips_blocked_item_and_dependents() {
+    let root = init_root("loop-keep-going");
+    let root_str = root.to_string_lossy();
+    for name in ["one", "two", "three"] {
+        write_file(&root, &format!("specs/{}.md", name), "# Task\n");
+    }
+    write_file(
+        &root,
+        "specs/task-order.yaml",
+        "version: 1\ntasks:\n  - id: 1\n    key: one\n    title: One\n    file: specs/one.md\n  - id: 2\n    key: two\n    title: Two\n    file: specs/two.md\n    depends_on: [1]\n  - id: 3\n    key: three\n    title: Three\n    file: specs/three.md\n",
+    );
+    write_file(&root, "specs/test-order.yaml", EMPTY_TEST_ORDER);
+    write_file(&root, "loopr/config", "TEST_COMMAND=true\n");
+
+    let bin = temp_dir("loop-keep-going-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nfor last; do :; done\ncase \"$last\" in *'Item: one '*) status=BLOCKED ;; *) status=COMPLETE ;; esac\nprintf -- '---LOOPR_STATUS---\\nSTATUS: %s\\nSUMMARY: needs credentials\\n---END_LOOPR_STATUS---\\n' \"$status\"\n",
+    );
+    let (text, code) = run_loopr_with_path(
+        &["loop", "--per-task", "--keep-going", "--loopr-root", &root_str],
+        &bin,
+        &[],
+    );
+    assert_eq!(code, 0, "{}", text);
+    assert!(text.contains("Loop 1 skip: one: blocked"), "{}", text);
+    assert!(text.contains("Loop 2 start: task three"), "{}", text);
+    assert!(text.contains("Exit reason: blocked"));
+    assert!(text.contains("Skipped:\n  one (blocked: needs credentials)\n  two (depends on one)\n"));
+
+    let work_status = read_work_status(&root);
+    assert_eq!(work_status["items"]["three"]["state"], "complete");
+    assert_eq!(work_status["items"]["two"]["state"], "not_started");
+}
+
+#[test]
+fn test_loop_keep_going_skips_item_whose_test_command_errors() {
+    let root = init_root("loop-keep-going-test-error");
+    let root_str = root.to_string_lossy();
+    for name in ["one", "two", "three"] {
+        write_file(&root, &format!("specs/{}.md", name), "# Task\n");
+    }
+    write_file(
+        &root,
+        "specs/task-order.yaml",
+        "version: 1\ntasks:\n  - id: 1\n    key: one\n    title: One\n    file: specs/one.md\n  - id: 2\n    key: two\n    title: Two\n    file: specs/two.md\n    depends_on: [1]\n  - id: 3\n    key: three\n    title: Three\n    file: specs/three.md\n",
+    );
+    write_file(&root, "specs/test-order.yaml", EMPTY_TEST_ORDER);
+    // Only one's run leaves a JUnit report that cannot be read.
+    write_file(
+        &root,
+        "loopr/config",
+        "TEST_COMMAND=rm -rf reports; mkdir -p reports; if [ \"$LOOPR_ITEM_KEY\" = one ]; then printf '\\377' > reports/one.xml; fi\nTEST_RESULT_FORMAT=junit\nTEST_RESULT_FILES=reports/*.xml\nMAX_ATTEMPTS_PER_ITEM=1\n",
+    );
+
+    let bin = temp_dir("loop-keep-going-test-error-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: done\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+    let (text, code) = run_loopr_with_path(
+        &["loop", "--per-task", "--keep-going", "--loopr-root", &root_str],
+        &bin,
+        &[],
+    );
+    assert_eq!(code, 0, "{}", text);
+    assert!(text.contains("Loop 1 skip: one: read "), "{}", text);
+    assert!(text.contains("Loop 2 start: task three"), "{}", text);
+    assert!(text.contains("Exit reason: error"), "{}", text);
+    assert!(text.contains("  two (depends on one)\n"), "{}", text);
+
+    let work_status = read_work_status(&root);
+    assert_eq!(work_status["items"]["one"]["state"], "error");
+    assert_eq!(work_status["items"]["two"]["state"], "not_started");
+    assert_eq!(work_status["items"]["three"]["state"], "complete");
+}
+
+#[test]
+fn test_loop_jobs_runs_items_in_workspaces() {
+    let root = init_root("loop-jobs");
+    let root_str = root.to_string_lossy();
+    git_init(&root);
+    for name in ["one", "two"] {
+        write_file(&root, &format!("specs/{}.md", name), "# Task\n");
+    }
+    write_file(
+        &root,
+        "specs/task-order.yaml",
+        "version: 1\ntasks:\n  - id: 1\n    key: one\n    title: One\n    file: specs/one.md\n  - id: 2\n    key: two\n    title: Two\n    file: specs/two.md\n",
+    );
+    write_file(&root, "specs/test-order.yaml", EMPTY_TEST_ORDER);
+    // The test command only passes inside the item's own workspace (named after its key).
+    write_file(&root, "loopr/config", "TEST_COMMAND=test -f done-$(basename $PWD)\n");
+
+    // Both items write shared.txt, so the second one to merge conflicts and is retried.
+    let bin = temp_dir("loop-jobs-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nfor last; do :; done\nkey=$(printf '%s\\n' \"$last\" | sed -n 's/^Item: \\([a-z]*\\) .*/\\1/p')\ncd \"$3\"\necho \"$key\" > shared.txt\ntouch \"done-$key\"\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: done\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+    let (text, code) = run_loopr_with_path(
+        &["loop", "--per-task", "--jobs", "2", "--loopr-root", &root_str],
+        &bin,
+        &[],
+    );
+    assert_eq!(code, 0, "{}", text);
+    assert!(text.contains("Loop 1 start: task one"), "{}", text);
+    assert!(text.contains("Loop 2 start: task two"), "{}", text);
+    assert!(
+        text.contains("Loop 2 retry: two attempt 1 failed: merge workspace"),
+        "{}",
+        text
+    );
+    assert!(text.contains("Exit reason: completed"), "{}", text);
+
+    assert_eq!(std::fs::read_to_string(root.join("shared.txt")).unwrap(), "two\n");
+    assert!(root.join("done-one").exists());
+    let work_status = read_work_status(&root);
+    assert_eq!(work_status["items"]["one"]["attempts"], 1);
+    assert_eq!(work_status["items"]["two"]["attempts"], 2);
+    let worktrees = Command::new("git")
+        .args(["-C", &root_str, "worktree", "list"])
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&worktrees.stdout).lines().count(), 1);
+}
+
+#[test]
+fn test_loop_jobs_releases_items_when_codex_cannot_start() {
+    let root = init_root("loop-jobs-error");
+    let root_str = root.to_string_lossy();
+    git_init(&root);
+    for name in ["one", "two"] {
+        write_file(&root, &format!("specs/{}.md", name), "# Task\n");
+    }
+    write_file(
+        &root,
+        "specs/task-order.yaml",
+        "version: 1\ntasks:\n  - id: 1\n    key: one\n    title: One\n    file: specs/one.md\n  - id: 2\n    key: two\n    title: Two\n    file: specs/two.md\n",
+    );
+    write_file(&root, "specs/test-order.yaml", EMPTY_TEST_ORDER);
+    write_file(&root, "loopr/config", "TEST_COMMAND=true\n");
+    // Session transcripts cannot be created, so no item's agent starts.
+    let transcripts = root.join("loopr").join("state").join("transcripts");
+    let _ = std::fs::remove_dir_all(&transcripts);
+    std::fs::write(&transcripts, "not a directory\n").unwrap();
+
+    let bin = temp_dir("loop-jobs-error-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: done\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+    let (text, code) = run_loopr_with_path(
+        &["loop", "--per-task", "--jobs", "2", "--loopr-root", &root_str],
+        &bin,
+        &[],
+    );
+    assert_ne!(code, 0, "{}", text);
+
+    let work_status = read_work_status(&root);
+    for key in ["one", "two"] {
+        assert_eq!(work_status["items"][key]["state"], "not_started", "{}", key);
+        assert_eq!(work_status["items"][key]["attempts"], 0, "{}", key);
+    }
+    let worktrees = Command::new("git")
+        .args(["-C", &root_str, "worktree", "list"])
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&worktrees.stdout).lines().count(), 1);
+
+    // The released items' iterations are recorded as skipped rather than left out.
+    let (text, code) = run_loopr(&["diff", "--iteration", "2", "--loopr-root", &root_str]);
+    assert_ne!(code, 0);
+    assert!(
+        text.contains("was skipped: item two was released without being merged"),
+        "{}",
+        text
+    );
+}
+
+#[test]
+fn test_loop_runs_item_test_commands_with_item_env() {
+    let root = init_root("loop-item-command");
+    let root_str = root.to_string_lossy();
+    write_file(&root, "specs/one.md", "# Task\n");
+    write_file(&root, "specs/one-test.md", "# Test\n");
+    write_file(&root, "specs/task-order.yaml", ONE_TASK_ORDER);
+    write_file(
+        &root,
+        "specs/test-order.yaml",
+        "version: 1\ntests:\n  - id: 1\n    key: one-test\n    title: One test\n    task_id: 1\n    file: specs/one-test.md\n    command: echo \"$LOOPR_ITEM_KEY $LOOPR_ITEM_TYPE $LOOPR_PHASE $LOOPR_TEST_FILES\" >> env.log\n",
+    );
+    write_file(&root, "loopr/config", "TEST_COMMAND=false\n");
+
+    let bin = temp_dir("loop-item-command-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: done\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+    let (text, code) =
+        run_loopr_with_path(&["loop", "--per-task", "--loopr-root", &root_str], &bin, &[]);
+    assert_eq!(code, 0, "{}", text);
+    assert!(text.contains("Exit reason: completed"), "{}", text);
+    assert_eq!(
+        std::fs::read_to_string(root.join("env.log")).unwrap(),
+        "one-test test tests specs/one-test.md\none task validate specs/one-test.md\n"
+    );
+    let work_status = read_work_status(&root);
+    assert_eq!(work_status["items"]["one"]["last_test"]["phase"], "validate");
+    assert!(
+        work_status["items"]["one"]["last_test"]["command"]
+            .as_str()
+            .unwrap()
+            .starts_with("echo ")
+    );
+}
+
+#[test]
+fn test_loop_feeds_test_output_into_retry_prompt() {
+    let root = init_root("loop-test-output");
+    let root_str = root.to_string_lossy();
+    write_file(&root, "specs/one.md", "# One\n");
+    write_file(&root, "specs/task-order.yaml", ONE_TASK_ORDER);
+    write_file(&root, "specs/test-order.yaml", EMPTY_TEST_ORDER);
+    write_file(
+        &root,
+        "loopr/config",
+        "MAX_FIX_ROUNDS=0\nTEST_COMMAND=if [ -e .second ]; then echo ok; else touch .second; echo 'assertion failed: parser'; exit 1; fi\n",
+    );
+
+    let bin = temp_dir("loop-test-output-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nfor last; do :; done\nprintf '%s\\n' \"$last\" >> \"$FAKE_LOG\"\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: done\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+    let log = bin.join("prompts.log");
+    let (text, code) = run_loopr_with_path(
+        &["loop", "--per-task", "--loopr-root", &root_str],
+        &bin,
+        &[("FAKE_LOG", &log.to_string_lossy())],
+    );
+    assert_eq!(code, 0, "{}", text);
+    assert!(text.contains("Exit reason: completed"), "{}", text);
+
+    let prompts = std::fs::read_to_string(&log).unwrap();
+    assert!(prompts.contains("The previous attempt failed:\ntests failed after implementation (exit 1, log "));
+    assert!(prompts.contains("Test output (tail):\n$ if [ -e .second ]"));
+    assert!(prompts.contains("assertion failed: parser\nFix the cause"));
+
+    let work_status = read_work_status(&root);
+    let last_test = &work_status["items"]["one"]["last_test"];
+    assert_eq!(last_test["passed"], true);
+    assert!(last_test["duration_ms"].is_u64());
+    let test_log = std::fs::read_to_string(last_test["log_path"].as_str().unwrap()).unwrap();
+    assert!(test_log.ends_with("\nok\n"), "{}", test_log);
+}
+
+#[test]
+fn test_loop_runs_fix_rounds_until_tests_pass() {
+    let root = init_root("loop-fix-rounds");
+    let root_str = root.to_string_lossy();
+    write_file(&root, "specs/one.md", "# One\n");
+    write_file(&root, "specs/task-order.yaml", ONE_TASK_ORDER);
+    write_file(&root, "specs/test-order.yaml", EMPTY_TEST_ORDER);
+    write_file(
+        &root,
+        "loopr/config",
+        "TEST_COMMAND=if [ -e .fixed ]; then echo ok; else echo 'assertion failed: fixer'; exit 1; fi\n",
+    );
+
+    // The main session leaves the tests red; only a fix-round prompt makes them pass.
+    let bin = temp_dir("loop-fix-rounds-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nfor last; do :; done\nprintf '%s\\n' \"$last\" >> \"$FAKE_LOG\"\ncase \"$last\" in *'Fix round 1 of 2'*) touch \"$3/.fixed\" ;; esac\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: done\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+    let log = bin.join("prompts.log");
+    let (text, code) = run_loopr_with_path(
+        &["loop", "--per-task", "--loopr-root", &root_str],
+        &bin,
+        &[("FAKE_LOG", &log.to_string_lossy())],
+    );
+    assert_eq!(code, 0, "{}", text);
+    assert!(text.contains("Loop 1 fix: one round 1/2: tests passed"), "{}", text);
+    assert!(text.contains("Exit reason: completed"), "{}", text);
+
+    let prompts = std::fs::read_to_string(&log).unwrap();
+    assert!(prompts.contains("still fail after your changes:\n$ if [ -e .fixed ]"));
+    assert!(prompts.contains("assertion failed: fixer\nFix the implementation until they pass"));
+
+    let work_status = read_work_status(&root);
+    let item = &work_status["items"]["one"];
+    assert_eq!(item["state"], "complete");
+    assert_eq!(item["attempts"], 1);
+    let sessions = item["sessions"].as_array().unwrap();
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[0]["round"], 0);
+    assert_eq!(sessions[0]["tests_passed"], false);
+    assert_eq!(sessions[1]["round"], 1);
+    assert_eq!(sessions[1]["tests_passed"], true);
+    assert_ne!(sessions[0]["transcript"], sessions[1]["transcript"]);
+}
+
+#[test]
+fn test_loop_blocks_item_with_flaky_validation() {
+    let root = init_root("loop-flaky");
+    let root_str = root.to_string_lossy();
+    write_file(&root, "specs/one.md", "# One\n");
+    write_file(&root, "specs/task-order.yaml", ONE_TASK_ORDER);
+    write_file(&root, "specs/test-order.yaml", EMPTY_TEST_ORDER);
+    // Fails only on its very first run, so the reruns disagree with it.
+    write_file(
+        &root,
+        "loopr/config",
+        "MAX_FIX_ROUNDS=0\nTEST_RERUNS=2\nTEST_COMMAND=if [ -e .ran ]; then echo ok; else touch .ran; echo flake; exit 1; fi\n",
+    );
+
+    let bin = temp_dir("loop-flaky-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: done\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+    let (text, _code) =
+        run_loopr_with_path(&["loop", "--per-task", "--loopr-root", &root_str], &bin, &[]);
+    assert!(text.contains("Loop 1 exit: flaky tests: passed 2 of 3 runs"), "{}", text);
+    assert!(text.contains("Exit reason: flaky_tests"), "{}", text);
+
+    let work_status = read_work_status(&root);
+    let item = &work_status["items"]["one"];
+    assert_eq!(item["state"], "blocked");
+    assert_eq!(item["attempts"], 1);
+    assert_eq!(item["last_test"]["passed"], false);
+    assert_eq!(item["last_test"]["stability"], "flaky");
+    assert_eq!(item["last_test"]["reruns"], serde_json::json!([true, true]));
+}
+
+#[test]
+fn test_loop_checks_declared_cases_go_red_then_green() {
+    let root = init_root("loop-cases");
+    let root_str = root.to_string_lossy();
+    write_file(&root, "specs/one.md", "# One\n");
+    write_file(&root, "specs/one-test.md", "# One test\n");
+    write_file(&root, "specs/task-order.yaml", ONE_TASK_ORDER);
+    write_file(
+        &root,
+        "specs/test-order.yaml",
+        "version: 1\ntests:\n  - id: 1\n    key: one-test\n    title: One test\n    task_id: 1\n    file: specs/one-test.md\n    cases: [parses input]\n",
+    );
+    // An unrelated case stays red throughout, so the suite never exits 0.
+    write_file(
+        &root,
+        "loopr/config",
+        "TEST_RESULT_FORMAT=tap\nTEST_COMMAND=if [ -e .impl ]; then echo 'ok 1 - parses input'; else echo 'not ok 1 - parses input'; fi; echo 'not ok 2 - other feature'; exit 1\n",
+    );
+
+    let bin = temp_dir("loop-cases-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nfor last; do :; done\ncase \"$last\" in *'Phase: implement'*) touch \"$3/.impl\" ;; esac\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: done\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+    let (text, code) =
+        run_loopr_with_path(&["loop", "--per-task", "--loopr-root", &root_str], &bin, &[]);
+    assert_eq!(code, 0, "{}", text);
+    assert!(text.contains("Exit reason: completed"), "{}", text);
+
+    let work_status = read_work_status(&root);
+    let red = &work_status["items"]["one-test"]["last_test"];
+    assert_eq!(red["passed"], false);
+    assert_eq!(red["cases"][0]["name"], "parses input");
+    assert_eq!(red["cases"][0]["outcome"], "failed");
+    let green = &work_status["items"]["one"]["last_test"];
+    assert_eq!(green["exit_code"], 1);
+    assert_eq!(green["passed"], true);
+    assert_eq!(green["cases"][0]["outcome"], "passed");
+    assert_eq!(green["cases"][1]["outcome"], "failed");
+    assert_eq!(work_status["items"]["one"]["state"], "complete");
+}
+
+#[test]
+fn test_loop_blocks_tests_phase_when_a_declared_case_passes() {
+    let root = init_root("loop-cases-partial");
+    let root_str = root.to_string_lossy();
+    write_file(&root, "specs/one.md", "# One\n");
+    write_file(&root, "specs/one-test.md", "# One test\n");
+    write_file(&root, "specs/task-order.yaml", ONE_TASK_ORDER);
+    write_file(
+        &root,
+        "specs/test-order.yaml",
+        "version: 1\ntests:\n  - id: 1\n    key: one-test\n    title: One test\n    task_id: 1\n    file: specs/one-test.md\n    cases: [parses input, rejects empty]\n",
+    );
+    // Only one of the two declared cases is red before the implementation exists.
+    write_file(
+        &root,
+        "loopr/config",
+        "TEST_RESULT_FORMAT=tap\nTEST_COMMAND=echo 'not ok 1 - parses input'; echo 'ok 2 - rejects empty'; exit 1\n",
+    );
+
+    let bin = temp_dir("loop-cases-partial-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: done\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+    let (text, _code) =
+        run_loopr_with_path(&["loop", "--per-task", "--loopr-root", &root_str], &bin, &[]);
+    assert!(text.contains("Exit reason: tests_passed_first"), "{}", text);
+
+    let work_status = read_work_status(&root);
+    let item = &work_status["items"]["one-test"];
+    assert_eq!(item["state"], "blocked");
+    assert_eq!(
+        item["last_error"],
+        "declared test cases passed on first run: rejects empty; must fail first"
+    );
+}
+
+#[test]
+fn test_loop_runs_gates_after_implementation() {
+    let root = init_root("loop-gates");
+    let root_str = root.to_string_lossy();
+    write_file(&root, "specs/one.md", "# One\n");
+    write_file(&root, "specs/task-order.yaml", ONE_TASK_ORDER);
+    write_file(&root, "specs/test-order.yaml", EMPTY_TEST_ORDER);
+    // The required fmt gate only passes once a fix round has run; lint is advisory and always red.
+    write_file(
+        &root,
+        "loopr/config",
+        "TEST_COMMAND=true\nGATES=fmt,lint\nGATE_FMT_COMMAND=test -e .formatted || { echo 'needs fmt'; exit 1; }\nGATE_LINT_COMMAND=echo \"lint $LOOPR_PHASE\"; exit 3\nGATE_LINT_REQUIRED=false\n",
+    );
+
+    let bin = temp_dir("loop-gates-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nfor last; do :; done\ncase \"$last\" in *'Fix round 1'*) touch \"$3/.formatted\" ;; esac\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: done\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+    let (text, code) =
+        run_loopr_with_path(&["loop", "--per-task", "--loopr-root", &root_str], &bin, &[]);
+    assert_eq!(code, 0, "{}", text);
+    assert!(text.contains("Loop 1 fix: one round 1/2: tests passed"), "{}", text);
+    assert!(text.contains("Loop 1 gate: lint (advisory) failed (exit 3, log "), "{}", text);
+    assert!(text.contains("Exit reason: completed"), "{}", text);
+
+    let work_status = read_work_status(&root);
+    let item = &work_status["items"]["one"];
+    assert_eq!(item["state"], "complete");
+    assert_eq!(item["sessions"][0]["gates_passed"], false);
+    assert_eq!(item["sessions"][1]["gates_passed"], true);
+    let gates = item["gates"].as_array().unwrap();
+    assert_eq!(gates.len(), 2);
+    assert_eq!(gates[0]["name"], "fmt");
+    assert_eq!(gates[0]["passed"], true);
+    assert_eq!(gates[1]["name"], "lint");
+    assert_eq!(gates[1]["required"], false);
+    assert_eq!(gates[1]["exit_code"], 3);
+    let log = std::fs::read_to_string(gates[1]["log_path"].as_str().unwrap()).unwrap();
+    assert!(log.contains("needs fmt\n"), "{}", log);
+    assert!(log.contains("lint validate\n"), "{}", log);
+}
+
+#[test]
+fn test_loop_checks_coverage_of_changed_files() {
+    let root = init_root("loop-coverage");
+    let root_str = root.to_string_lossy();
+    git_init(&root);
+    write_file(&root, "specs/one.md", "# One\n");
+    write_file(&root, "specs/task-order.yaml", ONE_TASK_ORDER);
+    write_file(&root, "specs/test-order.yaml", EMPTY_TEST_ORDER);
+    // old.py predates the task, so its uncovered line does not count; app.py's second line is
+    // only covered once a fix round has added a test for it.
+    write_file(&root, "src/old.py", "pass\n");
+    write_file(
+        &root,
+        "loopr/config",
+        "TEST_COMMAND=true\nCOVERAGE_COMMAND=hits=0; test -e .tested && hits=1; printf 'SF:%s/src/app.py\\nDA:1,1\\nDA:2,%s\\nend_of_record\\nSF:src/old.py\\nDA:1,0\\nend_of_record\\n' \"$PWD\" $hits > lcov.info\nCOVERAGE_REPORT=lcov.info\nCOVERAGE_MIN_PERCENT=75\n",
+    );
+
+    let bin = temp_dir("loop-coverage-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nfor last; do :; done\nprintf 'a = 1\\nb = 2\\n' > \"$3/src/app.py\"\ncase \"$last\" in *'src/app.py: 1 of 2 lines covered; uncovered lines 2'*) touch \"$3/.tested\" ;; esac\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: done\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+    let (text, code) =
+        run_loopr_with_path(&["loop", "--per-task", "--loopr-root", &root_str], &bin, &[]);
+    assert_eq!(code, 0, "{}", text);
+    assert!(text.contains("Loop 1 fix: one round 1/2: tests passed"), "{}", text);
+    assert!(text.contains("Exit reason: completed"), "{}", text);
+
+    let work_status = read_work_status(&root);
+    let item = &work_status["items"]["one"];
+    assert_eq!(item["state"], "complete");
+    assert_eq!(item["sessions"][0]["coverage_passed"], false);
+    assert_eq!(item["sessions"][1]["coverage_passed"], true);
+    let coverage = &item["coverage"];
+    assert_eq!(coverage["passed"], true);
+    assert_eq!(coverage["percent"], 100.0);
+    assert_eq!(coverage["lines_covered"], 2);
+    assert_eq!(coverage["lines_total"], 2);
+    assert_eq!(coverage["min_percent"], 75);
+    let files = coverage["files"].as_array().unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0]["path"], "src/app.py");
+    let log = std::fs::read_to_string(coverage["log_path"].as_str().unwrap()).unwrap();
+    assert!(
+        log.contains("loopr: coverage of changed files: 50.0% (1 of 2 lines), minimum 75%\n"),
+        "{}",
+        log
+    );
+
+    // Advisory coverage only warns: the task completes below the minimum.
+    write_file(
+        &root,
+        "loopr/config",
+        "TEST_COMMAND=true\nCOVERAGE_COMMAND=printf 'SF:src/app.py\\nDA:1,0\\nend_of_record\\n' > lcov.info\nCOVERAGE_REPORT=lcov.info\nCOVERAGE_REQUIRED=false\n",
+    );
+    std::fs::remove_file(root.join("loopr").join("state").join("work-status.json")).unwrap();
+    std::fs::remove_file(root.join("src").join("app.py")).unwrap();
+    let (text, code) =
+        run_loopr_with_path(&["loop", "--per-task", "--loopr-root", &root_str], &bin, &[]);
+    assert_eq!(code, 0, "{}", text);
+    assert!(
+        text.contains("Loop 1 coverage: one (advisory): 0.0% of changed lines (0/1) below 80% (log "),
+        "{}",
+        text
+    );
+    assert!(text.contains("Exit reason: completed"), "{}", text);
+}
+
+#[test]
+fn test_loop_coverage_counts_files_changed_by_earlier_attempts() {
+    let root = init_root("loop-coverage-retry");
+    let root_str = root.to_string_lossy();
+    git_init(&root);
+    write_file(&root, "specs/one.md", "# One\n");
+    write_file(&root, "specs/task-order.yaml", ONE_TASK_ORDER);
+    write_file(&root, "specs/test-order.yaml", EMPTY_TEST_ORDER);
+    write_file(
+        &root,
+        "loopr/config",
+        "TEST_COMMAND=true\nCOVERAGE_COMMAND=printf 'SF:src/app.py\\nDA:1,1\\nDA:2,0\\nend_of_record\\n' > lcov.info\nCOVERAGE_REPORT=lcov.info\nCOVERAGE_MIN_PERCENT=75\nMAX_FIX_ROUNDS=0\nMAX_ATTEMPTS_PER_ITEM=2\n",
+    );
+
+    // The second attempt rewrites app.py unchanged; it still counts as changed by the task.
+    let bin = temp_dir("loop-coverage-retry-bin");
+    fake_codex(
+        &bin,
+        "#!/bin/sh\nmkdir -p \"$3/src\"\nprintf 'a = 1\\nb = 2\\n' > \"$3/src/app.py\"\nprintf -- '---LOOPR_STATUS---\\nSTATUS: COMPLETE\\nSUMMARY: done\\n---END_LOOPR_STATUS---\\n'\n",
+    );
+    let (text, _) =
+        run_loopr_with_path(&["loop", "--per-task", "--loopr-root", &root_str], &bin, &[]);
+    assert!(
+        text.contains("Loop 2 coverage: one (required): 50.0% of changed lines (1/2)"),
+        "{}",
+        text
+    );
+    assert!(text.contains("Exit reason: coverage_below_threshold"), "{}", text);
+
+    let work_status = read_work_status(&root);
+    let item = &work_status["items"]["one"];
+    assert_eq!(item["attempts"], 2);
+    assert!(item["base_tree"].is_string(), "{}", item);
+    let sessions = item["sessions"].as_array().unwrap();
+    assert_eq!(sessions.len(), 2, "{}", item);
+    assert!(
+        sessions
+            .iter()
+            .all(|session| session["coverage_passed"] == false),
+        "{}",
+        item
+    );
+    assert_eq!(item["coverage"]["files"][0]["path"], "src/app.py");
+}
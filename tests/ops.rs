@@ -1,16 +1,51 @@
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use loopr::ops::coverage::{CoverageFormat, changed_file_coverage, parse_cobertura, parse_lcov};
+use loopr::ops::feature_scope::{OrderSnapshot, merge_order, merge_scoped_orders, scope_step};
+use loopr::ops::glob::{expand_glob, glob_match};
 use loopr::ops::init::{InitOptions, init};
-use loopr::ops::loop_config::{default_loop_config, load_loop_config};
+use loopr::ops::loop_config::{GateConfig, default_loop_config, load_loop_config};
 use loopr::ops::loop_status::parse_loopr_status;
 use loopr::ops::loopr_root::resolve_loopr_root;
 use loopr::ops::nanoid::{RandomSource, generate_nanoid, repo_id_alphabet, repo_id_length};
-use loopr::ops::run::{RunOptions, plan_steps, run_workflow};
-use loopr::ops::work_plan::{load_task_order, load_test_order};
-use loopr::ops::work_status::{WorkItemState, WorkItemType, ensure_item, load_work_status};
+use loopr::ops::prompt_template::{
+    PromptTemplates, TEMPLATE_SEED, TEMPLATE_STEP, TemplateContext, eject_templates,
+};
+use loopr::ops::run::{
+    PATH_MISSING, PATH_PENDING, PATH_PRESENT, RunOptions, build_prompt, capture_step_outputs,
+    check_step_inputs, default_run_steps, find_step, load_run_steps, parse_run_steps, plan_steps,
+    prompt_context, resume_steps, run_workflow, verify_step_outputs,
+};
+use loopr::ops::run_state::{
+    evaluate_skip, hash_file, hash_step_inputs, hash_step_outputs, load_run_state, record_step_run,
+    run_state_path, write_run_state,
+};
+use loopr::ops::run_status::{
+    RUN_STATE_COMPLETE, RUN_STATE_FAILED, RunStatusFile, load_run_status, run_status_path,
+    write_run_status,
+};
+use loopr::ops::seed::{SEED_PATH, archive_seed, read_seed, seed_history_dir, strip_seed_template};
+use loopr::ops::skills::{
+    SkillState, bundled_skill, diff_skill, install_skills, list_skills, missing_skills,
+    repo_skills_dir, skill_status,
+};
+use loopr::ops::snapshot::{
+    capture_released_snapshot, capture_snapshot, create_workspace, diff_item, diff_iteration,
+    list_snapshots, merge_workspace, remove_workspace, restore_iteration,
+};
+use loopr::ops::test_command::run_test_command;
+use loopr::ops::test_results::{
+    TestResultFormat, case_matches, parse_junit, parse_libtest_json, parse_tap,
+};
+use loopr::ops::validate::{TASK_ORDER_PATH, TEST_ORDER_PATH, validate_orders};
+use loopr::ops::work_plan::{FeatureSpec, load_task_order, load_test_order};
+use loopr::ops::work_status::{
+    TestCaseOutcome, WorkItemState, WorkItemType, ensure_item, load_work_status,
+};
 use loopr::{LooprError, LooprResult};
 
 struct FixedRandom {
@@ -95,13 +130,20 @@ fn test_load_loop_config_overrides() {
     let path = dir.join("config");
     fs::write(
         &path,
-        "CODEX_TIMEOUT_MINUTES=10\nMAX_ITERATIONS=5\nMAX_MISSING_STATUS=4\nTEST_COMMAND=just test --all\n",
+        "CODEX_TIMEOUT_MINUTES=10\nCODEX_TIMEOUT_MINUTES_EXECUTE=30\nCODEX_TIMEOUT_MINUTES_MY_STEP=5\nMAX_ITERATIONS=5\nMAX_MISSING_STATUS=4\nMAX_ATTEMPTS_PER_ITEM=5\nMAX_FIX_ROUNDS=0\nTEST_TIMEOUT_MINUTES=7\nTEST_RERUNS=2\nTEST_COMMAND=just test --all\n",
     )
     .unwrap();
     let cfg = load_loop_config(&path).unwrap();
     assert_eq!(cfg.codex_timeout_minutes, 10);
+    assert_eq!(cfg.codex_timeout_for_step("execute"), 30);
+    assert_eq!(cfg.codex_timeout_for_step("my-step"), 5);
+    assert_eq!(cfg.codex_timeout_for_step("spec"), 10);
     assert_eq!(cfg.max_iterations, 5);
     assert_eq!(cfg.max_missing_status, 4);
+    assert_eq!(cfg.max_attempts_per_item, 5);
+    assert_eq!(cfg.max_fix_rounds, 0);
+    assert_eq!(cfg.test_timeout_minutes, 7);
+    assert_eq!(cfg.test_reruns, 2);
     assert_eq!(cfg.test_command, "just test --all");
 }
 
@@ -116,42 +158,414 @@ fn test_load_loop_config_unknown_key() {
 
 #[test]
 fn test_plan_steps_defaults() {
-    let steps = plan_steps(&RunOptions {
-        loopr_root: None,
-        from: String::new(),
-        to: String::new(),
-        step: String::new(),
-        seed: String::new(),
-        confirm: false,
-        no_prompt: false,
-        codex: false,
-        codex_args: Vec::new(),
-        progress: None,
-    })
+    let steps = plan_steps(
+        &default_run_steps(),
+        &RunOptions {
+            loopr_root: None,
+            from: String::new(),
+            to: String::new(),
+            step: String::new(),
+            feature: String::new(),
+            seed: String::new(),
+            edit_seed: false,
+            confirm: false,
+            review: false,
+            show_prompts: false,
+            force: false,
+            resume: false,
+            no_prompt: false,
+            codex: false,
+            codex_args: Vec::new(),
+            progress: None,
+        },
+    )
     .unwrap();
     assert!(!steps.is_empty());
 }
 
 #[test]
 fn test_plan_steps_range() {
-    let steps = plan_steps(&RunOptions {
-        loopr_root: None,
-        from: "tasks".to_string(),
-        to: "tests".to_string(),
-        step: String::new(),
-        seed: String::new(),
-        confirm: false,
-        no_prompt: false,
-        codex: false,
-        codex_args: Vec::new(),
-        progress: None,
-    })
+    let steps = plan_steps(
+        &default_run_steps(),
+        &RunOptions {
+            loopr_root: None,
+            from: "tasks".to_string(),
+            to: "tests".to_string(),
+            step: String::new(),
+            feature: String::new(),
+            seed: String::new(),
+            edit_seed: false,
+            confirm: false,
+            review: false,
+            show_prompts: false,
+            force: false,
+            resume: false,
+            no_prompt: false,
+            codex: false,
+            codex_args: Vec::new(),
+            progress: None,
+        },
+    )
     .unwrap();
     assert_eq!(steps.len(), 2);
     assert_eq!(steps[0].name, "tasks");
     assert_eq!(steps[1].name, "tests");
 }
 
+#[test]
+fn test_load_run_steps_defaults_without_file() {
+    let root = temp_dir("steps-default");
+    let steps = load_run_steps(&root).unwrap();
+    let names: Vec<_> = steps.iter().map(|step| step.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["prd", "spec", "features", "tasks", "tests", "execute"]
+    );
+}
+
+#[test]
+fn test_load_run_steps_custom_pipeline() {
+    let root = temp_dir("steps-custom");
+    fs::create_dir_all(root.join("loopr")).unwrap();
+    fs::write(
+        root.join("loopr").join("steps.yaml"),
+        "version: 1\nsteps:\n  - name: spec\n    skill: team-spec\n    outputs: [specs/spec.md]\n  - name: architecture\n    skill: team-architecture\n    inputs: [specs/spec.md]\n    outputs: [specs/architecture.md]\n  - name: execute\n    skill: team-execute\n    always_run: true\n    allow_repo_read: true\n",
+    )
+    .unwrap();
+
+    let steps = load_run_steps(&root).unwrap();
+    assert_eq!(steps.len(), 3);
+    assert_eq!(steps[1].skill, "team-architecture");
+    assert!(steps[2].allow_repo_read);
+
+    let planned = plan_steps(
+        &steps,
+        &RunOptions {
+            loopr_root: None,
+            from: String::new(),
+            to: String::new(),
+            step: "architecture".to_string(),
+            feature: String::new(),
+            seed: String::new(),
+            edit_seed: false,
+            confirm: false,
+            review: false,
+            show_prompts: false,
+            force: false,
+            resume: false,
+            no_prompt: false,
+            codex: false,
+            codex_args: Vec::new(),
+            progress: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        planned[0].outputs,
+        vec!["specs/architecture.md".to_string()]
+    );
+}
+
+#[test]
+fn test_parse_run_steps_rejects_invalid() {
+    let err = parse_run_steps(
+        "version: 1\nsteps:\n  - name: spec\n    skill: a\n  - name: spec\n    skill: b\n",
+    )
+    .unwrap_err();
+    assert!(err.message.contains("duplicate step name"));
+
+    let err = parse_run_steps("version: 1\nsteps:\n  - name: spec\n    skill: \"\"\n").unwrap_err();
+    assert!(err.message.contains("skill is empty"));
+
+    let err = parse_run_steps("version: 1\nsteps:\n  - name: spec\n    skil: a\n").unwrap_err();
+    assert!(err.message.contains("parse"));
+
+    let err = parse_run_steps(
+        "version: 1\nsteps:\n  - name: spec\n    skill: a\n    outputs: [../out.md]\n",
+    )
+    .unwrap_err();
+    assert!(err.message.contains("relative"));
+}
+
+#[test]
+fn test_expand_glob_matches_spec_files() {
+    let root = temp_dir("glob");
+    let specs = root.join("specs");
+    fs::create_dir_all(&specs).unwrap();
+    fs::write(specs.join("feature-001.md"), "").unwrap();
+    fs::write(specs.join("feature-001-task-001.md"), "").unwrap();
+    fs::write(specs.join("spec.md"), "").unwrap();
+
+    let tasks = expand_glob(&root, "specs/feature-*-task-*.md").unwrap();
+    assert_eq!(tasks, vec!["specs/feature-001-task-001.md".to_string()]);
+    let literal = expand_glob(&root, "specs/spec.md").unwrap();
+    assert_eq!(literal, vec!["specs/spec.md".to_string()]);
+    assert!(expand_glob(&root, "specs/prd.md").unwrap().is_empty());
+
+    assert!(glob_match("feature-*.md", "feature-001.md"));
+    assert!(!glob_match("feature-*.md", "spec.md"));
+}
+
+#[test]
+fn test_check_step_inputs_names_producer() {
+    let root = temp_dir("preflight");
+    let steps = default_run_steps();
+    let tasks = find_step(&steps, "tasks").unwrap();
+    fs::create_dir_all(root.join("loopr").join("state")).unwrap();
+    fs::write(root.join("loopr").join("state").join("handoff.md"), "").unwrap();
+
+    let err = check_step_inputs(&root, &tasks, &steps).unwrap_err();
+    assert!(err.message.contains("step tasks"));
+    assert!(
+        err.message
+            .contains("specs/feature-order.yaml (produced by step features)")
+    );
+}
+
+#[test]
+fn test_verify_step_outputs_requires_changes() {
+    let root = temp_dir("outputs");
+    let steps = default_run_steps();
+    let spec = find_step(&steps, "spec").unwrap();
+    fs::create_dir_all(root.join("specs")).unwrap();
+
+    let before = capture_step_outputs(&root, &spec).unwrap();
+    let err = verify_step_outputs(&root, &spec, &before).unwrap_err();
+    assert!(err.message.contains("specs/spec.md"));
+
+    fs::write(root.join("specs").join("spec.md"), "# Spec\n").unwrap();
+    verify_step_outputs(&root, &spec, &before).unwrap();
+
+    let before = capture_step_outputs(&root, &spec).unwrap();
+    assert!(verify_step_outputs(&root, &spec, &before).is_err());
+}
+
+#[test]
+fn test_evaluate_skip_tracks_input_hashes() {
+    let root = temp_dir("run-state");
+    let steps = default_run_steps();
+    let spec = find_step(&steps, "spec").unwrap();
+    fs::create_dir_all(root.join("specs")).unwrap();
+    fs::write(root.join("specs").join("prd.md"), "# PRD\n").unwrap();
+    fs::write(root.join("specs").join("spec.md"), "# Spec\n").unwrap();
+
+    let mut state = load_run_state(&root.join("run-state.json"), "now").unwrap();
+    let decision = evaluate_skip(&root, &spec, &state, false).unwrap();
+    assert!(!decision.skip);
+    assert_eq!(decision.reason, "no previous run recorded");
+
+    let inputs = hash_step_inputs(&root, &spec).unwrap();
+    assert!(!inputs.contains_key("loopr/state/handoff.md"));
+    let outputs = hash_step_outputs(&root, &spec).unwrap();
+    record_step_run(&mut state, &spec, inputs, outputs, "now");
+    assert!(evaluate_skip(&root, &spec, &state, false).unwrap().skip);
+    assert!(!evaluate_skip(&root, &spec, &state, true).unwrap().skip);
+
+    fs::write(root.join("specs").join("prd.md"), "# PRD v2\n").unwrap();
+    let decision = evaluate_skip(&root, &spec, &state, false).unwrap();
+    assert!(!decision.skip);
+    assert!(decision.reason.contains("specs/prd.md"));
+
+    let execute = find_step(&steps, "execute").unwrap();
+    record_step_run(
+        &mut state,
+        &execute,
+        Default::default(),
+        Default::default(),
+        "now",
+    );
+    assert_eq!(
+        evaluate_skip(&root, &execute, &state, false)
+            .unwrap()
+            .reason,
+        "always_run"
+    );
+}
+
+#[test]
+fn test_hash_step_inputs_skips_own_outputs() {
+    let root = temp_dir("run-state-own-outputs");
+    let steps = default_run_steps();
+    let tasks = find_step(&steps, "tasks").unwrap();
+    fs::create_dir_all(root.join("specs")).unwrap();
+    fs::write(
+        root.join("specs").join("feature-order.yaml"),
+        "features: []\n",
+    )
+    .unwrap();
+    fs::write(root.join("specs").join("feature-a.md"), "# A\n").unwrap();
+    fs::write(root.join("specs").join("feature-a-task-1.md"), "# Task\n").unwrap();
+    fs::write(
+        root.join("specs").join("feature-a-task-1-test-1.md"),
+        "# Test\n",
+    )
+    .unwrap();
+
+    let inputs = hash_step_inputs(&root, &tasks).unwrap();
+    let paths: Vec<&str> = inputs.keys().map(|path| path.as_str()).collect();
+    assert_eq!(
+        paths,
+        vec!["specs/feature-a.md", "specs/feature-order.yaml"]
+    );
+}
+
+#[test]
+fn test_run_workflow_dry_run_reruns_prd_for_new_seed() {
+    let root = temp_dir("dry-run-seed");
+    let steps = default_run_steps();
+    let prd = find_step(&steps, "prd").unwrap();
+    fs::create_dir_all(root.join("specs")).unwrap();
+    fs::write(root.join("specs").join("prd.md"), "# PRD\n").unwrap();
+    archive_seed(&root, "run-1", "Build a habit tracker", "now").unwrap();
+    let mut state = load_run_state(&run_state_path(&root), "now").unwrap();
+    let inputs = hash_step_inputs(&root, &prd).unwrap();
+    let outputs = hash_step_outputs(&root, &prd).unwrap();
+    record_step_run(&mut state, &prd, inputs, outputs, "now");
+    write_run_state(&run_state_path(&root), &state).unwrap();
+
+    let dry_run = |seed: &str| {
+        run_workflow(RunOptions {
+            loopr_root: Some(root.clone()),
+            from: String::new(),
+            to: String::new(),
+            step: "prd".to_string(),
+            feature: String::new(),
+            seed: seed.to_string(),
+            edit_seed: false,
+            confirm: false,
+            review: false,
+            show_prompts: false,
+            force: false,
+            resume: false,
+            no_prompt: false,
+            codex: false,
+            codex_args: Vec::new(),
+            progress: None,
+        })
+        .unwrap()
+    };
+    assert!(dry_run("").decisions["prd"].skip);
+    assert!(dry_run("Build a habit tracker\n").decisions["prd"].skip);
+    let report = dry_run("Build a budget app");
+    assert!(!report.decisions["prd"].skip);
+    assert_eq!(report.decisions["prd"].reason, "seed prompt changed");
+
+    fs::write(root.join(SEED_PATH), "Build a budget app\n").unwrap();
+    let report = dry_run("");
+    assert_eq!(
+        report.decisions["prd"].reason,
+        "inputs changed: specs/seed.md"
+    );
+}
+
+#[test]
+fn test_build_prompt_default_template() {
+    let root = PathBuf::from("/repo");
+    let handoff = root.join("loopr").join("state").join("handoff.md");
+    let steps = default_run_steps();
+    let templates = PromptTemplates::defaults();
+
+    let spec = find_step(&steps, "spec").unwrap();
+    let prompt = build_prompt(&templates, &spec, "", "", &handoff, &root).unwrap();
+    let expected = [
+        "Loopr step: spec",
+        "Loopr root: /repo",
+        "Handoff: /repo/loopr/state/handoff.md",
+        "",
+        "Prompt: loopr-specify",
+        "",
+        "Allowed inputs:",
+        "- loopr/state/handoff.md",
+        "- specs/prd.md",
+        "- AGENTS.md",
+        "- loopr/state/docs-index.txt",
+        "",
+        "Required outputs:",
+        "- specs/spec.md",
+        "",
+        "Rules:",
+        "- Read only the allowed inputs.",
+        "- Do not scan the repo.",
+        "- If required inputs are missing, stop and ask to run the appropriate step.",
+        "- Append a completion note to /repo/loopr/state/handoff.md (decisions, open questions, tests).",
+        "- If you cannot produce the required outputs, report STATUS: BLOCKED (or ERROR) and explain why in SUMMARY.",
+        "- Always include the status block at the end of your response.",
+        "",
+        "Status block format (required):",
+        "---LOOPR_STATUS---",
+        "STATUS: IN_PROGRESS | COMPLETE | BLOCKED | ERROR",
+        "SUMMARY: <short summary>",
+        "---END_LOOPR_STATUS---",
+        "",
+        "Run the prompt: loopr-specify",
+    ]
+    .join("\n");
+    assert_eq!(prompt, expected);
+
+    let prd = find_step(&steps, "prd").unwrap();
+    let prompt = build_prompt(&templates, &prd, "Build a CLI", "", &handoff, &root).unwrap();
+    assert!(prompt.contains("- specs/prd.md\n\nSeed prompt:\nBuild a CLI\n\nRules:"));
+}
+
+#[test]
+fn test_prompt_templates_load_overrides() {
+    let root = temp_dir("prompt-templates");
+    let prompts = root.join("loopr").join("prompts");
+    fs::create_dir_all(&prompts).unwrap();
+    fs::write(
+        prompts.join("step.tmpl"),
+        "Step {{step}} ({{skill}})\n{{#each outputs}}\nwrite {{this}}\n{{/each}}\n{{#unless requires_seed}}\nno seed\n{{/unless}}\n{{> rules}}\n",
+    )
+    .unwrap();
+    fs::write(prompts.join("rules.tmpl"), "Be brief.\n").unwrap();
+
+    let steps = default_run_steps();
+    let spec = find_step(&steps, "spec").unwrap();
+    let templates = PromptTemplates::load(&root).unwrap();
+    let handoff = root.join("handoff.md");
+    let prompt = build_prompt(&templates, &spec, "", "", &handoff, &root).unwrap();
+    assert_eq!(
+        prompt,
+        "Step spec (loopr-specify)\nwrite specs/spec.md\nno seed\nBe brief."
+    );
+
+    fs::write(prompts.join("step.tmpl"), "Step {{stp}}\n").unwrap();
+    let templates = PromptTemplates::load(&root).unwrap();
+    let err = build_prompt(&templates, &spec, "", "", &handoff, &root).unwrap_err();
+    assert!(err.to_string().contains("step.tmpl"));
+    assert!(err.to_string().contains("unknown variable: stp"));
+
+    fs::write(prompts.join("step.tmpl"), "{{#if pbt}}\nopen\n").unwrap();
+    let templates = PromptTemplates::load(&root).unwrap();
+    let ctx = prompt_context(&spec, &handoff, &root, Vec::new());
+    let err = templates.render(TEMPLATE_STEP, &ctx).unwrap_err();
+    assert!(err.to_string().contains("unclosed {{#if}} block"));
+}
+
+#[test]
+fn test_eject_templates_skips_existing() {
+    let root = temp_dir("prompt-eject");
+    let prompts = root.join("loopr").join("prompts");
+    fs::create_dir_all(&prompts).unwrap();
+    fs::write(prompts.join("step.tmpl"), "custom\n").unwrap();
+
+    let report = eject_templates(&root, false).unwrap();
+    assert_eq!(report.skipped, vec![prompts.join("step.tmpl")]);
+    assert!(report.written.contains(&prompts.join("per-task.tmpl")));
+    assert_eq!(
+        fs::read_to_string(prompts.join("step.tmpl")).unwrap(),
+        "custom\n"
+    );
+
+    let report = eject_templates(&root, true).unwrap();
+    assert!(report.skipped.is_empty());
+    assert!(
+        fs::read_to_string(prompts.join("step.tmpl"))
+            .unwrap()
+            .starts_with("Loopr step: {{step}}")
+    );
+}
+
 #[test]
 fn test_run_workflow_dry_run_shows_all_steps() {
     let root = temp_dir("dry-run");
@@ -162,8 +576,14 @@ fn test_run_workflow_dry_run_shows_all_steps() {
         from: String::new(),
         to: String::new(),
         step: String::new(),
+        feature: String::new(),
         seed: String::new(),
+        edit_seed: false,
         confirm: false,
+        review: false,
+        show_prompts: false,
+        force: false,
+        resume: false,
         no_prompt: false,
         codex: false,
         codex_args: Vec::new(),
@@ -184,8 +604,14 @@ fn test_run_workflow_dry_run_does_not_require_repo_id() {
         from: String::new(),
         to: String::new(),
         step: String::new(),
+        feature: String::new(),
         seed: String::new(),
+        edit_seed: false,
         confirm: false,
+        review: false,
+        show_prompts: false,
+        force: false,
+        resume: false,
         no_prompt: false,
         codex: false,
         codex_args: Vec::new(),
@@ -198,6 +624,152 @@ fn test_run_workflow_dry_run_does_not_require_repo_id() {
     assert!(!root.join("loopr").join("state").join("handoff.md").exists());
 }
 
+#[test]
+fn test_run_workflow_dry_run_plans_inputs_and_prompts() {
+    let root = temp_dir("dry-run-plan");
+    fs::create_dir_all(root.join("specs")).unwrap();
+    fs::write(
+        root.join("specs").join("feature-order.yaml"),
+        "features: []\n",
+    )
+    .unwrap();
+    fs::write(root.join("specs").join("feature-a.md"), "# A\n").unwrap();
+    fs::write(root.join("specs").join("feature-b.md"), "# B\n").unwrap();
+
+    let report = run_workflow(RunOptions {
+        loopr_root: Some(root.clone()),
+        from: "spec".to_string(),
+        to: "tasks".to_string(),
+        step: String::new(),
+        feature: String::new(),
+        seed: String::new(),
+        edit_seed: false,
+        confirm: false,
+        review: false,
+        show_prompts: true,
+        force: false,
+        resume: false,
+        no_prompt: false,
+        codex: false,
+        codex_args: Vec::new(),
+        progress: None,
+    })
+    .unwrap();
+
+    let names: Vec<&str> = report.plan.iter().map(|step| step.name.as_str()).collect();
+    assert_eq!(names, vec!["spec", "features", "tasks"]);
+    let spec = &report.plan[0];
+    let prd = spec
+        .inputs
+        .iter()
+        .find(|input| input.pattern == "specs/prd.md")
+        .unwrap();
+    assert_eq!(prd.status, PATH_MISSING);
+    assert_eq!(prd.produced_by.as_deref(), Some("prd"));
+    assert!(
+        spec.prompt
+            .as_ref()
+            .unwrap()
+            .starts_with("Loopr step: spec")
+    );
+
+    let features = &report.plan[1];
+    let spec_input = features
+        .inputs
+        .iter()
+        .find(|input| input.pattern == "specs/spec.md")
+        .unwrap();
+    assert_eq!(spec_input.status, PATH_PENDING);
+    assert_eq!(spec_input.produced_by.as_deref(), Some("spec"));
+
+    let tasks = &report.plan[2];
+    let glob = tasks
+        .inputs
+        .iter()
+        .find(|input| input.pattern == "specs/feature-*.md")
+        .unwrap();
+    assert_eq!(glob.status, PATH_PRESENT);
+    assert_eq!(
+        glob.files,
+        vec![
+            "specs/feature-a.md".to_string(),
+            "specs/feature-b.md".to_string()
+        ]
+    );
+}
+
+#[test]
+fn test_run_workflow_resume_plans_remaining_steps() {
+    let root = temp_dir("run-resume");
+    let resume_opts = |root: &Path| RunOptions {
+        loopr_root: Some(root.to_path_buf()),
+        from: String::new(),
+        to: String::new(),
+        step: String::new(),
+        feature: String::new(),
+        seed: String::new(),
+        edit_seed: false,
+        confirm: false,
+        review: false,
+        show_prompts: false,
+        force: false,
+        resume: true,
+        no_prompt: false,
+        codex: false,
+        codex_args: Vec::new(),
+        progress: None,
+    };
+    let err = run_workflow(resume_opts(&root)).err().unwrap();
+    assert!(err.to_string().contains("no run to resume"));
+
+    let status_path = run_status_path(&root);
+    let mut status = RunStatusFile {
+        version: 1,
+        run_id: "20260101-000000-abcdef".to_string(),
+        state: RUN_STATE_FAILED.to_string(),
+        started_at: "now".to_string(),
+        updated_at: "now".to_string(),
+        planned_steps: vec![
+            "spec".to_string(),
+            "features".to_string(),
+            "tasks".to_string(),
+        ],
+        completed_steps: vec!["spec".to_string()],
+        skipped_steps: Vec::new(),
+        current_step: Some("features".to_string()),
+        seed: "seed".to_string(),
+        feature: None,
+        codex_args: vec!["--model".to_string(), "m".to_string()],
+        force: false,
+        review: false,
+        confirm: false,
+        sessions: Vec::new(),
+        last_error: Some("codex exited with code 1".to_string()),
+        exit_reason: Some("codex_failed".to_string()),
+    };
+    write_run_status(&status_path, &status).unwrap();
+    let loaded = load_run_status(&status_path).unwrap().unwrap();
+    assert_eq!(loaded.codex_args, status.codex_args);
+    assert_eq!(loaded.remaining_steps(), vec!["features", "tasks"]);
+
+    let report = run_workflow(resume_opts(&root)).unwrap();
+    let names: Vec<_> = report.steps.iter().map(|step| step.name.as_str()).collect();
+    assert_eq!(names, vec!["features", "tasks"]);
+    assert_eq!(report.run_id.as_deref(), Some("20260101-000000-abcdef"));
+
+    status.planned_steps.push("review".to_string());
+    let err = resume_steps(&default_run_steps(), &status).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("step review is no longer in the pipeline")
+    );
+
+    status.state = RUN_STATE_COMPLETE.to_string();
+    write_run_status(&status_path, &status).unwrap();
+    let err = run_workflow(resume_opts(&root)).err().unwrap();
+    assert!(err.to_string().contains("already completed"));
+}
+
 #[test]
 fn test_init_greenfield_creates_repo_id() {
     let root = temp_dir("init-greenfield");
@@ -379,6 +951,92 @@ fn test_load_test_order_parses_yaml() {
     assert_eq!(order.tests[0].kind.as_deref(), Some("pbt"));
 }
 
+#[test]
+fn test_validate_orders_reports_problems_with_lines() {
+    let root = temp_dir("validate");
+    let specs = root.join("specs");
+    fs::create_dir_all(&specs).unwrap();
+    fs::write(specs.join("feature-001-task-001.md"), "").unwrap();
+    fs::write(
+        specs.join("task-order.yaml"),
+        "version: 1\ntasks:\n  - id: 1\n    key: one\n    title: One\n    file: specs/feature-001-task-001.md\n    depends_on: [2]\n  - id: 2\n    key: two\n    title: Two\n    file: specs/missing.md\n    depends_on: [1, 9]\n  - id: 2\n    key: one\n    title: Dup\n    file: specs/feature-001-task-001.md\n",
+    )
+    .unwrap();
+    fs::write(
+        specs.join("test-order.yaml"),
+        "version: 1\ntests:\n  - id: 1\n    key: t-one\n    title: T\n    task_id: 7\n    file: specs/feature-001-task-001.md\n",
+    )
+    .unwrap();
+
+    let report = validate_orders(&root, &[TASK_ORDER_PATH, TEST_ORDER_PATH]).unwrap();
+    let lines: Vec<String> = report
+        .issues
+        .iter()
+        .map(|issue| issue.to_string())
+        .collect();
+    let has = |needle: &str| lines.iter().any(|line| line.contains(needle));
+    assert!(has(
+        "specs/task-order.yaml:8: task 2 (two) file not found: specs/missing.md"
+    ));
+    assert!(has(
+        "specs/task-order.yaml:8: task 2 (two) depends_on unknown task id 9"
+    ));
+    assert!(has(
+        "specs/task-order.yaml:13: duplicate task id 2 (first defined on line 8)"
+    ));
+    assert!(has("duplicate task key one (first defined on line 3)"));
+    assert!(has("task dependency cycle: 1 -> 2 -> 1"));
+    assert!(has("specs/test-order.yaml:3: test 1 (t-one) has task_id 7"));
+    assert!(!report.is_ok());
+
+    let err = report.into_result().unwrap_err();
+    assert!(err.message.starts_with("spec validation failed"));
+}
+
+#[test]
+fn test_validate_orders_missing_required_and_parse_errors() {
+    let root = temp_dir("validate-missing");
+    fs::create_dir_all(root.join("specs")).unwrap();
+    fs::write(
+        root.join("specs").join("test-order.yaml"),
+        "version: 1\ntests:\n  - id: one\n",
+    )
+    .unwrap();
+    let report = validate_orders(&root, &[TASK_ORDER_PATH]).unwrap();
+    let lines: Vec<String> = report
+        .issues
+        .iter()
+        .map(|issue| issue.to_string())
+        .collect();
+    assert!(lines.contains(&"specs/task-order.yaml: file not found".to_string()));
+    assert!(
+        lines
+            .iter()
+            .any(|line| line.starts_with("specs/test-order.yaml:3: parse error"))
+    );
+
+    // Tests point at task ids, so a missing task order is reported even when it is not required.
+    fs::write(
+        root.join("specs").join("test-order.yaml"),
+        "version: 1\ntests:\n  - id: 1\n    key: one-test\n    title: One test\n    file: specs/one-test.md\n    task_id: 7\n",
+    )
+    .unwrap();
+    fs::write(root.join("specs").join("one-test.md"), "# Test\n").unwrap();
+    let report = validate_orders(&root, &[]).unwrap();
+    let lines: Vec<String> = report
+        .issues
+        .iter()
+        .map(|issue| issue.to_string())
+        .collect();
+    assert_eq!(
+        lines,
+        vec![
+            "specs/test-order.yaml: tests have task_ids but specs/task-order.yaml does not exist"
+                .to_string()
+        ]
+    );
+}
+
 #[test]
 fn test_work_status_ensure_item() {
     let dir = temp_dir("work-status");
@@ -390,8 +1048,791 @@ fn test_work_status_ensure_item() {
     assert_eq!(item.state, WorkItemState::NotStarted);
 }
 
+#[test]
+fn test_skills_install_detects_drift() {
+    let root = temp_dir("skills");
+    let dir = repo_skills_dir(&root);
+    let statuses = list_skills(&dir).unwrap();
+    assert_eq!(statuses.len(), 6);
+    assert!(
+        statuses
+            .iter()
+            .all(|status| status.state == SkillState::Missing)
+    );
+    let missing = missing_skills(&root, &["custom".to_string()]).unwrap();
+    assert!(missing.is_empty());
+
+    let report = install_skills(&dir, &[], false, false).unwrap();
+    assert_eq!(report.written.len(), 6);
+    let prd = dir.join("loopr-prd").join("SKILL.md");
+    assert_eq!(
+        fs::read_to_string(&prd).unwrap(),
+        bundled_skill("loopr-prd").unwrap()
+    );
+    assert!(dir.join("loopr-prd").join(".loopr-skill.json").exists());
+    assert!(
+        missing_skills(&root, &["loopr-prd".to_string()])
+            .unwrap()
+            .is_empty()
+    );
+
+    fs::write(&prd, "# my prd skill\n").unwrap();
+    assert_eq!(
+        skill_status(&dir, "loopr-prd").unwrap().state,
+        SkillState::Modified
+    );
+    let diff = diff_skill(&dir, "loopr-prd").unwrap().unwrap();
+    assert!(diff.contains("-# my prd skill"));
+    assert!(diff.contains("+name: loopr-prd"));
+    assert!(diff_skill(&dir, "loopr-tests").unwrap().is_none());
+
+    let report = install_skills(&dir, &["loopr-prd".to_string()], true, false).unwrap();
+    assert!(report.written.is_empty());
+    assert!(report.skipped[0].1.contains("local changes"));
+    let report = install_skills(&dir, &["loopr-prd".to_string()], true, true).unwrap();
+    assert_eq!(report.written[0].state, SkillState::Current);
+
+    let stamp_path = dir.join("loopr-tasks").join(".loopr-skill.json");
+    let old_body = "# old loopr-tasks\n";
+    let stamp = fs::read_to_string(&stamp_path).unwrap();
+    let stamp: serde_json::Value = serde_json::from_str(&stamp).unwrap();
+    let sha = stamp["sha256"].as_str().unwrap().to_string();
+    fs::write(dir.join("loopr-tasks").join("SKILL.md"), old_body).unwrap();
+    let old_sha = hash_file(&dir.join("loopr-tasks").join("SKILL.md")).unwrap();
+    fs::write(
+        &stamp_path,
+        fs::read_to_string(&stamp_path)
+            .unwrap()
+            .replace(&sha, &old_sha),
+    )
+    .unwrap();
+    assert_eq!(
+        skill_status(&dir, "loopr-tasks").unwrap().state,
+        SkillState::Outdated
+    );
+    let report = install_skills(&dir, &[], false, false).unwrap();
+    assert!(report.written.is_empty());
+    let report = install_skills(&dir, &[], true, false).unwrap();
+    assert_eq!(report.written.len(), 1);
+    assert_eq!(report.written[0].name, "loopr-tasks");
+
+    assert!(install_skills(&dir, &["loopr-nope".to_string()], false, false).is_err());
+}
+
+#[test]
+fn test_snapshots_diff_and_restore() {
+    let root = temp_dir("snapshots");
+    git(&root, &["init", "-q"]);
+    fs::write(root.join("a.txt"), "one\n").unwrap();
+
+    capture_snapshot(&root, "run-1", 0, None).unwrap().unwrap();
+    fs::write(root.join("a.txt"), "two\n").unwrap();
+    fs::write(root.join("b.txt"), "new\n").unwrap();
+    capture_snapshot(&root, "run-1", 1, Some("task-one"))
+        .unwrap()
+        .unwrap();
+
+    let snapshots = list_snapshots(&root, None).unwrap();
+    assert_eq!(snapshots.len(), 2);
+    assert_eq!(snapshots[1].item_key.as_deref(), Some("task-one"));
+
+    let diff = diff_iteration(&root, Some("run-1"), 1).unwrap();
+    assert!(diff.contains("+two"));
+    assert!(diff.contains("b.txt"));
+    let item_diff = diff_item(&root, None, "task-one").unwrap();
+    assert!(item_diff.contains("# iteration 1 (task-one)"));
+
+    let report = restore_iteration(&root, None, 0).unwrap();
+    assert_eq!(report.removed, vec!["b.txt".to_string()]);
+    assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "one\n");
+    assert!(!root.join("b.txt").exists());
+    assert!(
+        report
+            .backup_ref
+            .starts_with("refs/loopr/run-1/pre-restore-")
+    );
+}
+
+#[test]
+fn test_snapshots_report_released_iterations() {
+    let root = temp_dir("snapshots-released");
+    git(&root, &["init", "-q"]);
+    fs::write(root.join("a.txt"), "one\n").unwrap();
+
+    capture_snapshot(&root, "run-1", 0, None).unwrap().unwrap();
+    let released = capture_released_snapshot(&root, "run-1", 1, "task-one")
+        .unwrap()
+        .unwrap();
+    assert!(released.released);
+    fs::write(root.join("a.txt"), "two\n").unwrap();
+    capture_snapshot(&root, "run-1", 2, Some("task-two"))
+        .unwrap()
+        .unwrap();
+
+    let iterations: Vec<(i64, bool)> = list_snapshots(&root, None)
+        .unwrap()
+        .iter()
+        .map(|snap| (snap.iteration, snap.released))
+        .collect();
+    assert_eq!(iterations, vec![(0, false), (1, true), (2, false)]);
+
+    let err = diff_iteration(&root, None, 1).unwrap_err();
+    assert!(
+        err.message
+            .contains("iteration 1 of run run-1 was skipped: item task-one was released"),
+        "{}",
+        err.message
+    );
+    assert!(restore_iteration(&root, None, 1).is_err());
+    assert!(diff_item(&root, None, "task-one").is_err());
+    let diff = diff_iteration(&root, None, 2).unwrap();
+    assert!(diff.contains("+two"));
+}
+
+#[test]
+fn test_capture_snapshot_skips_outside_git() {
+    let root = temp_dir("snapshots-nogit");
+    let snapshot = capture_snapshot(&root, "run-1", 0, None).unwrap();
+    assert!(snapshot.is_none());
+}
+
+#[test]
+fn test_workspace_merges_changes_back() {
+    let root = temp_dir("workspace");
+    git(&root, &["init", "-q"]);
+    fs::write(root.join("a.txt"), "one\n").unwrap();
+    fs::write(root.join("shared.txt"), "base\n").unwrap();
+
+    let dir = root.join("ws");
+    let first = create_workspace(&root, &dir.join("first"), "first").unwrap();
+    let second = create_workspace(&root, &dir.join("second"), "second").unwrap();
+    assert_eq!(
+        fs::read_to_string(first.path.join("a.txt")).unwrap(),
+        "one\n"
+    );
+    fs::write(first.path.join("a.txt"), "two\n").unwrap();
+    fs::write(first.path.join("b.txt"), "new\n").unwrap();
+    fs::write(first.path.join("shared.txt"), "first\n").unwrap();
+    fs::write(second.path.join("shared.txt"), "second\n").unwrap();
+
+    merge_workspace(&root, &first).unwrap();
+    assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "two\n");
+    assert_eq!(fs::read_to_string(root.join("b.txt")).unwrap(), "new\n");
+    assert!(merge_workspace(&root, &second).is_err());
+    assert_eq!(
+        fs::read_to_string(root.join("shared.txt")).unwrap(),
+        "first\n"
+    );
+
+    remove_workspace(&root, &first).unwrap();
+    remove_workspace(&root, &second).unwrap();
+    assert!(!dir.exists());
+}
+
+#[test]
+fn test_run_test_command_kills_process_group_on_timeout() {
+    let root = temp_dir("test-command-timeout");
+    let log_path = root.join("logs").join("test.log");
+    let env = vec![("LOOPR_ITEM_KEY", "one".to_string())];
+
+    let result = run_test_command(
+        &root,
+        "echo \"$LOOPR_ITEM_KEY\"",
+        "validate",
+        &env,
+        &log_path,
+        std::time::Duration::from_secs(60),
+    )
+    .unwrap();
+    assert!(result.passed);
+    assert!(!result.timed_out);
+
+    // The background child shares the process group, so it dies with the shell.
+    let result = run_test_command(
+        &root,
+        "(sleep 1; touch late) & sleep 30",
+        "validate",
+        &env,
+        &log_path,
+        std::time::Duration::from_millis(300),
+    )
+    .unwrap();
+    assert!(result.timed_out);
+    assert!(!result.passed);
+    assert!(result.duration_ms < 10_000);
+    std::thread::sleep(std::time::Duration::from_millis(1500));
+    assert!(!root.join("late").exists());
+
+    let log = fs::read_to_string(&log_path).unwrap();
+    assert!(
+        log.starts_with("$ echo \"$LOOPR_ITEM_KEY\"\none\n$ (sleep 1; touch late) & sleep 30\n")
+    );
+    assert!(log.contains("loopr: test command timed out"));
+}
+
+#[test]
+fn test_parse_structured_test_results() {
+    let libtest = "{ \"type\": \"suite\", \"event\": \"started\", \"test_count\": 3 }\n\
+{ \"type\": \"test\", \"event\": \"started\", \"name\": \"parser::roundtrip\" }\n\
+{ \"type\": \"test\", \"event\": \"timeout\", \"name\": \"parser::roundtrip\" }\n\
+{ \"type\": \"test\", \"name\": \"parser::roundtrip\", \"event\": \"ok\" }\n\
+{ \"type\": \"test\", \"name\": \"parser::empty\", \"event\": \"failed\", \"stdout\": \"boom\" }\n\
+{ \"type\": \"test\", \"name\": \"parser::slow\", \"event\": \"ignored\" }\n\
+running 3 tests\n";
+    let cases: Vec<(String, TestCaseOutcome)> = parse_libtest_json(libtest)
+        .into_iter()
+        .map(|case| (case.name, case.outcome))
+        .collect();
+    assert_eq!(
+        cases,
+        vec![
+            ("parser::roundtrip".to_string(), TestCaseOutcome::Passed),
+            ("parser::empty".to_string(), TestCaseOutcome::Failed),
+            ("parser::slow".to_string(), TestCaseOutcome::Skipped),
+        ]
+    );
+
+    let tap = "TAP version 13\n1..4\nok 1 - parses input\nnot ok 2 - rejects empty\n  ---\n  ...\nok 3 slow # SKIP no network\nnot ok 4 - later # TODO\nokay\n";
+    let cases: Vec<(String, TestCaseOutcome)> = parse_tap(tap)
+        .into_iter()
+        .map(|case| (case.name, case.outcome))
+        .collect();
+    assert_eq!(
+        cases,
+        vec![
+            ("parses input".to_string(), TestCaseOutcome::Passed),
+            ("rejects empty".to_string(), TestCaseOutcome::Failed),
+            ("slow".to_string(), TestCaseOutcome::Skipped),
+            ("later".to_string(), TestCaseOutcome::Skipped),
+        ]
+    );
+
+    // pytest --junitxml and jest-junit shapes.
+    let junit = r#"<?xml version="1.0" encoding="utf-8"?>
+<testsuites><testsuite name="pytest" tests="3">
+<testcase classname="tests.test_parser" name="test_roundtrip" time="0.01" />
+<testcase classname="tests.test_parser" name="test_empty" time="0.02"><failure message="assert 1 == 2">trace</failure></testcase>
+<testcase classname="tests.test_parser" name="test_slow"><skipped message="slow" /></testcase>
+</testsuite>
+<testsuite name="jest"><testcase classname="parser handles &amp; escapes" name="parser handles &amp; escapes" time="0.003"></testcase>
+<testcase name="crashes"><error message="TypeError" /></testcase></testsuite></testsuites>"#;
+    let cases: Vec<(String, TestCaseOutcome)> = parse_junit(junit)
+        .into_iter()
+        .map(|case| (case.name, case.outcome))
+        .collect();
+    assert_eq!(
+        cases,
+        vec![
+            (
+                "tests.test_parser::test_roundtrip".to_string(),
+                TestCaseOutcome::Passed
+            ),
+            (
+                "tests.test_parser::test_empty".to_string(),
+                TestCaseOutcome::Failed
+            ),
+            (
+                "tests.test_parser::test_slow".to_string(),
+                TestCaseOutcome::Skipped
+            ),
+            (
+                "parser handles & escapes::parser handles & escapes".to_string(),
+                TestCaseOutcome::Passed
+            ),
+            ("crashes".to_string(), TestCaseOutcome::Failed),
+        ]
+    );
+
+    assert!(case_matches(
+        "test_roundtrip",
+        "tests.test_parser::test_roundtrip"
+    ));
+    assert!(case_matches("parser::*", "parser::empty"));
+    assert!(!case_matches("roundtrip", "parser::roundtrip_v2"));
+    assert_eq!(
+        TestResultFormat::parse("libtest-json"),
+        Some(TestResultFormat::LibtestJson)
+    );
+    assert_eq!(TestResultFormat::parse("xml"), None);
+}
+
+#[test]
+fn test_load_loop_config_gates() {
+    let dir = temp_dir("config-gates");
+    let path = dir.join("config");
+    fs::write(
+        &path,
+        "GATE_LINT_COMMAND=cargo clippy -- -D warnings\nGATES=fmt, lint\nGATE_FMT_COMMAND=cargo fmt --check\nGATE_FMT_PHASES=tests,validate\nGATE_LINT_REQUIRED=false\nGATE_LINT_TIMEOUT_MINUTES=5\n",
+    )
+    .unwrap();
+    let cfg = load_loop_config(&path).unwrap();
+    assert_eq!(
+        cfg.gates,
+        vec![
+            GateConfig {
+                name: "fmt".to_string(),
+                command: "cargo fmt --check".to_string(),
+                phases: vec!["tests".to_string(), "validate".to_string()],
+                required: true,
+                timeout_minutes: None,
+            },
+            GateConfig {
+                name: "lint".to_string(),
+                command: "cargo clippy -- -D warnings".to_string(),
+                phases: vec!["validate".to_string()],
+                required: false,
+                timeout_minutes: Some(5),
+            },
+        ]
+    );
+
+    fs::write(&path, "GATES=build\n").unwrap();
+    let err = load_loop_config(&path).unwrap_err();
+    assert_eq!(
+        err.message,
+        "GATES lists build but GATE_BUILD_COMMAND is not set"
+    );
+    fs::write(
+        &path,
+        "GATES=build\nGATE_BUILD_COMMAND=make\nGATE_BUILD_PHASES=deploy\n",
+    )
+    .unwrap();
+    let err = load_loop_config(&path).unwrap_err();
+    assert!(
+        err.message
+            .contains("invalid phase for GATE_BUILD_PHASES on line 3")
+    );
+}
+
+#[test]
+fn test_load_loop_config_result_format() {
+    let dir = temp_dir("config-result-format");
+    let path = dir.join("config");
+    fs::write(
+        &path,
+        "TEST_RESULT_FORMAT=junit\nTEST_RESULT_FILES=reports/*.xml\n",
+    )
+    .unwrap();
+    let cfg = load_loop_config(&path).unwrap();
+    assert_eq!(cfg.test_result_format, Some(TestResultFormat::Junit));
+    assert_eq!(cfg.test_result_files, "reports/*.xml");
+
+    fs::write(&path, "TEST_RESULT_FORMAT=junit\n").unwrap();
+    let err = load_loop_config(&path).unwrap_err();
+    assert!(err.message.contains("TEST_RESULT_FILES"), "{}", err.message);
+    fs::write(&path, "TEST_RESULT_FORMAT=xml\n").unwrap();
+    let err = load_loop_config(&path).unwrap_err();
+    assert!(err.message.contains("invalid TEST_RESULT_FORMAT on line 1"));
+}
+
+#[test]
+fn test_parse_coverage_reports() {
+    let lcov = "TN:\nSF:/work/repo/src/lib.rs\nDA:1,4\nDA:2,0\nDA:3,1\nDA:4,0\nend_of_record\n\
+SF:/work/repo/src/untouched.rs\nDA:1,0\nend_of_record\n";
+    let coverage = parse_lcov(lcov);
+    assert_eq!(coverage.len(), 2);
+    let changed = vec!["src/lib.rs".to_string(), "README.md".to_string()];
+    let files = changed_file_coverage(&coverage, Path::new("/work/repo"), Some(&changed));
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, "src/lib.rs");
+    assert_eq!((files[0].lines_covered, files[0].lines_total), (2, 4));
+    assert_eq!(files[0].uncovered_lines, vec![2, 4]);
+    let all = changed_file_coverage(&coverage, Path::new("/work/repo"), None);
+    assert_eq!(all.len(), 2);
+
+    // The full relative path wins over a suffix; a suffix alone must point at a single file.
+    let lcov = "SF:/work/repo/src/lib.rs\nDA:1,1\nend_of_record\n\
+SF:lib.rs\nDA:1,0\nDA:2,0\nend_of_record\nSF:util.rs\nDA:1,1\nDA:2,0\nend_of_record\n";
+    let changed = vec![
+        "src/lib.rs".to_string(),
+        "crates/core/src/lib.rs".to_string(),
+        "src/util.rs".to_string(),
+    ];
+    let files = changed_file_coverage(&parse_lcov(lcov), Path::new("/work/repo"), Some(&changed));
+    let summary: Vec<(&str, u64, u64)> = files
+        .iter()
+        .map(|file| (file.path.as_str(), file.lines_covered, file.lines_total))
+        .collect();
+    assert_eq!(summary, vec![("src/lib.rs", 1, 1), ("src/util.rs", 1, 2)]);
+
+    let cobertura = r#"<?xml version="1.0" ?>
+<coverage line-rate="0.5">
+  <sources><source>/work/repo</source></sources>
+  <packages><package name="pkg"><classes>
+    <class name="app" filename="pkg/app.py" line-rate="0.5">
+      <methods><method name="run"><lines><line number="2" hits="3"/></lines></method></methods>
+      <lines>
+        <line number="1" hits="1"/>
+        <line number="2" hits="0"/>
+        <line number="3" hits="0" branch="true"/>
+      </lines>
+    </class>
+    <class name="empty" filename="pkg/empty.py"/>
+  </classes></package></packages>
+</coverage>
+"#;
+    let coverage = parse_cobertura(cobertura);
+    let changed = vec!["python/pkg/app.py".to_string()];
+    let files = changed_file_coverage(&coverage, Path::new("/work/repo"), Some(&changed));
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, "python/pkg/app.py");
+    assert_eq!((files[0].lines_covered, files[0].lines_total), (2, 3));
+    assert_eq!(files[0].uncovered_lines, vec![3]);
+}
+
+#[test]
+fn test_load_loop_config_coverage() {
+    let dir = temp_dir("config-coverage");
+    let path = dir.join("config");
+    let cfg = load_loop_config(&path).unwrap();
+    assert!(cfg.coverage_command.is_empty());
+    assert_eq!(cfg.coverage_min_percent, 80);
+    assert!(cfg.coverage_required);
+
+    fs::write(
+        &path,
+        "COVERAGE_COMMAND=coverage run -m pytest && coverage xml\nCOVERAGE_REPORT=coverage.xml\nCOVERAGE_FORMAT=cobertura\nCOVERAGE_MIN_PERCENT=90\nCOVERAGE_REQUIRED=false\n",
+    )
+    .unwrap();
+    let cfg = load_loop_config(&path).unwrap();
+    assert_eq!(
+        cfg.coverage_command,
+        "coverage run -m pytest && coverage xml"
+    );
+    assert_eq!(cfg.coverage_report, "coverage.xml");
+    assert_eq!(cfg.coverage_format, CoverageFormat::Cobertura);
+    assert_eq!(cfg.coverage_min_percent, 90);
+    assert!(!cfg.coverage_required);
+
+    fs::write(&path, "COVERAGE_COMMAND=make coverage\n").unwrap();
+    let err = load_loop_config(&path).unwrap_err();
+    assert!(err.message.contains("COVERAGE_REPORT"), "{}", err.message);
+    fs::write(&path, "COVERAGE_MIN_PERCENT=120\n").unwrap();
+    let err = load_loop_config(&path).unwrap_err();
+    assert!(
+        err.message
+            .contains("invalid COVERAGE_MIN_PERCENT on line 1")
+    );
+    fs::write(&path, "COVERAGE_FORMAT=jacoco\n").unwrap();
+    let err = load_loop_config(&path).unwrap_err();
+    assert!(err.message.contains("invalid COVERAGE_FORMAT on line 1"));
+}
+
+fn git(root: &Path, args: &[&str]) {
+    let status = std::process::Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
 fn write_repo_id(root: &Path, repo_id: &str) {
     let path = root.join("loopr");
     fs::create_dir_all(&path).unwrap();
     fs::write(path.join("repo-id"), repo_id).unwrap();
 }
+
+#[test]
+fn test_strip_seed_template_drops_instructions() {
+    let templates = PromptTemplates::defaults();
+    let template = templates
+        .render(TEMPLATE_SEED, &TemplateContext::new())
+        .unwrap();
+    assert_eq!(strip_seed_template(&template), "");
+    let edited = format!("# Todo app\n\nTrack tasks offline.\n{}", template);
+    assert_eq!(
+        strip_seed_template(&edited),
+        "# Todo app\n\nTrack tasks offline."
+    );
+}
+
+#[test]
+fn test_archive_seed_keeps_history() {
+    let root = temp_dir("seed-archive");
+    archive_seed(&root, "run-1", "first idea\n", "2026-01-01T00:00:00Z").unwrap();
+    archive_seed(&root, "run-2", "second idea", "2026-01-02T00:00:00Z").unwrap();
+
+    let current = fs::read_to_string(root.join(SEED_PATH)).unwrap();
+    assert!(current.contains("- Run: run-2"));
+    assert!(current.ends_with("\n<!-- loopr: seed prompt below -->\n\nsecond idea\n"));
+    let first = fs::read_to_string(seed_history_dir(&root).join("run-1.md")).unwrap();
+    assert!(first.contains("first idea"));
+    assert!(seed_history_dir(&root).join("run-2.md").exists());
+    assert_eq!(read_seed(&root).unwrap().as_deref(), Some("second idea"));
+
+    // The same seed again keeps specs/seed.md as it was, so prd's input hash does not change.
+    archive_seed(&root, "run-3", "second idea\n", "2026-01-03T00:00:00Z").unwrap();
+    assert_eq!(fs::read_to_string(root.join(SEED_PATH)).unwrap(), current);
+    assert!(seed_history_dir(&root).join("run-3.md").exists());
+
+    // Seed lines that look like the header are kept.
+    let seed = "- Run: the import first\n- Recorded: calls go to the audit log";
+    archive_seed(&root, "run-4", seed, "2026-01-04T00:00:00Z").unwrap();
+    assert_eq!(read_seed(&root).unwrap().as_deref(), Some(seed));
+    fs::write(root.join(SEED_PATH), "# Seed Prompt\n\nBuild a CLI.\n").unwrap();
+    assert_eq!(
+        read_seed(&root).unwrap().as_deref(),
+        Some("# Seed Prompt\n\nBuild a CLI.")
+    );
+}
+
+#[test]
+fn test_scope_step_narrows_feature_globs() {
+    let feature = FeatureSpec {
+        id: 2,
+        key: "billing".to_string(),
+        title: "Billing".to_string(),
+        file: "specs/feature-billing.md".to_string(),
+        depends_on: vec![1],
+    };
+    let steps = default_run_steps();
+    let tasks = scope_step(&find_step(&steps, "tasks").unwrap(), &feature).unwrap();
+    assert_eq!(
+        tasks.inputs,
+        vec![
+            "loopr/state/handoff.md".to_string(),
+            "specs/feature-order.yaml".to_string(),
+            "specs/feature-billing.md".to_string(),
+        ]
+    );
+    assert_eq!(
+        tasks.outputs,
+        vec![
+            "specs/task-order.yaml".to_string(),
+            "specs/feature-billing-task-*.md".to_string(),
+        ]
+    );
+    assert_eq!(tasks.state_key(), "tasks@billing");
+
+    let err = scope_step(&find_step(&steps, "spec").unwrap(), &feature).unwrap_err();
+    assert!(err.to_string().contains("--feature only applies to"));
+}
+
+#[test]
+fn test_merge_order_keeps_other_features() {
+    let before = "version: 1
+tasks:
+  - id: 1
+    key: auth-login
+    title: Login
+    file: specs/feature-auth-task-login.md
+  - id: 2
+    key: billing-invoices
+    title: Invoices
+    file: specs/feature-billing-task-invoices.md
+    depends_on: [1]
+  # Roles wait for invoices to settle.
+  - id: 3
+    key: auth-admin-roles
+    title: Roles
+    file: specs/feature-auth-admin-task-roles.md
+    depends_on: [1, 2] # see invoices
+";
+    let after = "version: 1
+tasks:
+- id: 4
+  key: billing-invoices
+  title: Invoices v2
+  file: specs/feature-billing-task-invoices.md
+  depends_on: [1]
+- id: 5
+  key: billing-refunds
+  title: Refunds
+  file: specs/feature-billing-task-refunds.md
+  depends_on: [4]
+";
+    let merged = merge_order(
+        before,
+        after,
+        "tasks",
+        "billing",
+        &merge_feature_keys(),
+        &HashMap::new(),
+    )
+    .unwrap();
+    assert_eq!(merged.renumbered, HashMap::from([(2, 4)]));
+    assert_eq!(
+        merged.text,
+        "version: 1
+tasks:
+- id: 1
+  key: auth-login
+  title: Login
+  file: specs/feature-auth-task-login.md
+- id: 4
+  key: billing-invoices
+  title: Invoices v2
+  file: specs/feature-billing-task-invoices.md
+  depends_on: [1]
+- id: 5
+  key: billing-refunds
+  title: Refunds
+  file: specs/feature-billing-task-refunds.md
+  depends_on: [4]
+# Roles wait for invoices to settle.
+- id: 3
+  key: auth-admin-roles
+  title: Roles
+  file: specs/feature-auth-admin-task-roles.md
+  depends_on: [1, 4] # see invoices
+"
+    );
+    let root = temp_dir("merge-order");
+    fs::create_dir_all(root.join("specs")).unwrap();
+    fs::write(root.join(TASK_ORDER_PATH), &merged.text).unwrap();
+    let order = load_task_order(&root.join(TASK_ORDER_PATH)).unwrap();
+    let summary: Vec<(i64, &str, Vec<i64>)> = order
+        .tasks
+        .iter()
+        .map(|task| (task.id, task.key.as_str(), task.depends_on.clone()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (1, "auth-login", vec![]),
+            (4, "billing-invoices", vec![1]),
+            (5, "billing-refunds", vec![4]),
+            (3, "auth-admin-roles", vec![1, 4]),
+        ]
+    );
+}
+
+#[test]
+fn test_merge_order_rejects_ids_used_by_other_features() {
+    let before = "version: 1
+tasks:
+  - id: 1
+    key: auth-login
+    title: Login
+    file: specs/feature-auth-task-login.md
+  - id: 2
+    key: billing-invoices
+    title: Invoices
+    file: specs/feature-billing-task-invoices.md
+";
+    let after = "version: 1
+tasks:
+  - id: 1
+    key: billing-invoices
+    title: Invoices
+    file: specs/feature-billing-task-invoices.md
+";
+    let err = merge_order(
+        before,
+        after,
+        "tasks",
+        "billing",
+        &merge_feature_keys(),
+        &HashMap::new(),
+    )
+    .err()
+    .unwrap();
+    assert!(
+        err.to_string()
+            .contains("id 1 of billing-invoices is already used by auth-login"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_merge_scoped_orders_remaps_test_task_ids() {
+    let root = temp_dir("merge-scoped-orders");
+    fs::create_dir_all(root.join("specs")).unwrap();
+    let before = "version: 1
+tasks:
+  - id: 1
+    key: auth-login
+    title: Login
+    file: specs/feature-auth-task-login.md
+  - id: 2
+    key: billing-invoices
+    title: Invoices
+    file: specs/feature-billing-task-invoices.md
+";
+    fs::write(
+        root.join(TASK_ORDER_PATH),
+        "version: 1
+tasks:
+  - id: 1
+    key: auth-login
+    title: Login
+    file: specs/feature-auth-task-login.md
+  - id: 7
+    key: billing-invoices
+    title: Invoices
+    file: specs/feature-billing-task-invoices.md
+",
+    )
+    .unwrap();
+    let tests = "version: 1
+tests:
+  # Login first.
+  - id: 1
+    key: auth-login-ok
+    title: Login works
+    file: specs/feature-auth-task-login-test-ok.md
+    task_id: 1
+  - id: 2
+    key: billing-invoices-total
+    title: Invoice totals
+    file: specs/feature-billing-task-invoices-test-total.md
+    task_id: 2 # invoices
+";
+    fs::write(root.join(TEST_ORDER_PATH), tests).unwrap();
+    let snapshots = vec![OrderSnapshot {
+        path: TASK_ORDER_PATH.to_string(),
+        list_key: "tasks",
+        before: Some(before.to_string()),
+    }];
+    merge_scoped_orders(&root, &snapshots, "billing", &merge_feature_keys()).unwrap();
+    assert_eq!(
+        fs::read_to_string(root.join(TEST_ORDER_PATH)).unwrap(),
+        tests.replace("task_id: 2 #", "task_id: 7 #")
+    );
+
+    // A scoped tests step remaps the task ids of the tests it keeps, not the ones it wrote.
+    let after = "version: 1
+tests:
+  - id: 1
+    key: auth-login-ok
+    title: Login works
+    file: specs/feature-auth-task-login-test-ok.md
+    task_id: 1
+  - id: 3
+    key: billing-invoices-total
+    title: Invoice totals
+    file: specs/feature-billing-task-invoices-test-total.md
+    task_id: 1
+";
+    let before = tests.replace("task_id: 1\n", "task_id: 2\n");
+    let merged = merge_order(
+        &before,
+        after,
+        "tests",
+        "billing",
+        &merge_feature_keys(),
+        &HashMap::from([(2, 7)]),
+    )
+    .unwrap();
+    let doc: serde_yaml::Value = serde_yaml::from_str(&merged.text).unwrap();
+    let task_ids: Vec<i64> = doc["tests"]
+        .as_sequence()
+        .unwrap()
+        .iter()
+        .map(|test| test["task_id"].as_i64().unwrap())
+        .collect();
+    assert_eq!(task_ids, vec![7, 1]);
+    assert!(merged.text.contains("  # Login first.\n  - id: 1\n"));
+    assert_eq!(merged.renumbered, HashMap::from([(2, 3)]));
+}
+
+fn merge_feature_keys() -> Vec<String> {
+    vec![
+        "auth".to_string(),
+        "billing".to_string(),
+        "auth-admin".to_string(),
+    ]
+}